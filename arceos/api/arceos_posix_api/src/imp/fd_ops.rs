@@ -1,10 +1,10 @@
-use alloc::sync::Arc;
+use alloc::{string::String, sync::Arc, vec::Vec};
 use core::ffi::c_int;
 
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
 use flatten_objects::FlattenObjects;
-use spin::RwLock;
+use spin::{Once, RwLock};
 
 use super::stdio::{stdin, stdout};
 use crate::ctypes;
@@ -16,40 +16,229 @@ pub trait FileLike: Send + Sync {
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize>;
     fn write(&self, buf: &[u8]) -> LinuxResult<usize>;
     fn stat(&self) -> LinuxResult<ctypes::stat>;
+    /// What the file is, as `/proc/<pid>/fd/<fd>` links to, e.g., the path
+    /// of a file or `pipe:[..]`.
+    fn path(&self) -> String;
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync>;
     fn poll(&self) -> LinuxResult<PollState>;
     fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult;
 }
 
+/// An opened file and its file descriptor flags.
+#[derive(Clone)]
+struct FdEntry {
+    file: Arc<dyn FileLike>,
+    /// The `FD_CLOEXEC` flag.
+    cloexec: bool,
+}
+
+/// A file descriptor table.
+///
+/// The unikernel uses a single global table, while each user process of a
+/// monolithic kernel owns its own one (see [`set_current_fd_table_fn`]).
+pub struct FdTable {
+    files: RwLock<FlattenObjects<FdEntry, AX_FILE_LIMIT>>,
+}
+
+impl FdTable {
+    /// Creates an empty file descriptor table.
+    pub fn new() -> Self {
+        Self {
+            files: RwLock::new(FlattenObjects::new()),
+        }
+    }
+
+    /// Creates a file descriptor table with stdin, stdout and stderr opened.
+    pub fn with_stdio() -> Self {
+        let table = Self::new();
+        let mut files = table.files.write();
+        for (fd, file) in [
+            Arc::new(stdin()) as Arc<dyn FileLike>, // stdin
+            Arc::new(stdout()) as _,                // stdout
+            Arc::new(stdout()) as _,                // stderr
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            files.add_at(fd, FdEntry { file, cloexec: false }).unwrap();
+        }
+        drop(files);
+        table
+    }
+
+    /// Duplicates the table for a child process created by `fork`/`clone`.
+    ///
+    /// The child refers to the same opened files, and keeps their
+    /// `FD_CLOEXEC` flags.
+    pub fn fork(&self) -> Self {
+        let files = self.files.read();
+        let mut new_files = FlattenObjects::new();
+        for fd in 0..AX_FILE_LIMIT {
+            if let Some(entry) = files.get(fd) {
+                new_files.add_at(fd, entry.clone());
+            }
+        }
+        Self {
+            files: RwLock::new(new_files),
+        }
+    }
+
+    /// Closes all file descriptors with the `FD_CLOEXEC` flag set, which is
+    /// done on a successful `execve`.
+    pub fn close_on_exec(&self) {
+        let mut files = self.files.write();
+        for fd in 0..AX_FILE_LIMIT {
+            if files.get(fd).is_some_and(|entry| entry.cloexec) {
+                files.remove(fd);
+            }
+        }
+    }
+
+    /// Returns the file referred to by `fd`.
+    pub fn get(&self, fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
+        if fd < 0 {
+            return Err(LinuxError::EBADF);
+        }
+        self.files
+            .read()
+            .get(fd as usize)
+            .map(|entry| entry.file.clone())
+            .ok_or(LinuxError::EBADF)
+    }
+
+    /// Returns the open file descriptors in ascending order.
+    pub fn fds(&self) -> Vec<c_int> {
+        let files = self.files.read();
+        (0..AX_FILE_LIMIT)
+            .filter(|&fd| files.get(fd).is_some())
+            .map(|fd| fd as c_int)
+            .collect()
+    }
+
+    /// Adds a file with the lowest available file descriptor.
+    pub fn add(&self, file: Arc<dyn FileLike>, cloexec: bool) -> LinuxResult<c_int> {
+        let fd = self
+            .files
+            .write()
+            .add(FdEntry { file, cloexec })
+            .ok_or(LinuxError::EMFILE)?;
+        Ok(fd as c_int)
+    }
+
+    /// Adds a file with the lowest available file descriptor greater than or
+    /// equal to `min_fd`.
+    pub fn add_from(
+        &self,
+        min_fd: c_int,
+        file: Arc<dyn FileLike>,
+        cloexec: bool,
+    ) -> LinuxResult<c_int> {
+        if min_fd < 0 || min_fd as usize >= AX_FILE_LIMIT {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut files = self.files.write();
+        let fd = (min_fd as usize..AX_FILE_LIMIT)
+            .find(|&fd| files.get(fd).is_none())
+            .ok_or(LinuxError::EMFILE)?;
+        files.add_at(fd, FdEntry { file, cloexec });
+        Ok(fd as c_int)
+    }
+
+    /// Installs a file at `fd`, closing the file previously opened there.
+    pub fn add_at(&self, fd: c_int, file: Arc<dyn FileLike>, cloexec: bool) -> LinuxResult<c_int> {
+        if fd < 0 || fd as usize >= AX_FILE_LIMIT {
+            return Err(LinuxError::EBADF);
+        }
+        let mut files = self.files.write();
+        let old = files.remove(fd as usize);
+        files
+            .add_at(fd as usize, FdEntry { file, cloexec })
+            .ok_or(LinuxError::EMFILE)?;
+        drop(files);
+        drop(old);
+        Ok(fd)
+    }
+
+    /// Closes the file descriptor `fd`.
+    pub fn remove(&self, fd: c_int) -> LinuxResult {
+        if fd < 0 {
+            return Err(LinuxError::EBADF);
+        }
+        let entry = self
+            .files
+            .write()
+            .remove(fd as usize)
+            .ok_or(LinuxError::EBADF)?;
+        drop(entry);
+        Ok(())
+    }
+
+    /// Returns whether the `FD_CLOEXEC` flag of `fd` is set.
+    pub fn cloexec(&self, fd: c_int) -> LinuxResult<bool> {
+        if fd < 0 {
+            return Err(LinuxError::EBADF);
+        }
+        self.files
+            .read()
+            .get(fd as usize)
+            .map(|entry| entry.cloexec)
+            .ok_or(LinuxError::EBADF)
+    }
+
+    /// Sets or clears the `FD_CLOEXEC` flag of `fd`.
+    pub fn set_cloexec(&self, fd: c_int, cloexec: bool) -> LinuxResult {
+        if fd < 0 {
+            return Err(LinuxError::EBADF);
+        }
+        self.files
+            .write()
+            .get_mut(fd as usize)
+            .map(|entry| entry.cloexec = cloexec)
+            .ok_or(LinuxError::EBADF)
+    }
+}
+
+impl Default for FdTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 lazy_static::lazy_static! {
-    static ref FD_TABLE: RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>> = {
-        let mut fd_table = FlattenObjects::new();
-        fd_table.add_at(0, Arc::new(stdin()) as _).unwrap(); // stdin
-        fd_table.add_at(1, Arc::new(stdout()) as _).unwrap(); // stdout
-        fd_table.add_at(2, Arc::new(stdout()) as _).unwrap(); // stderr
-        RwLock::new(fd_table)
-    };
+    static ref FD_TABLE: Arc<FdTable> = Arc::new(FdTable::with_stdio());
+}
+
+static CURRENT_FD_TABLE_FN: Once<fn() -> Option<Arc<FdTable>>> = Once::new();
+
+/// Registers the function that finds the file descriptor table of the current
+/// task, usually from its task extension.
+///
+/// If the function is not registered or returns `None` (e.g., for kernel
+/// tasks), the global file descriptor table is used.
+pub fn set_current_fd_table_fn(f: fn() -> Option<Arc<FdTable>>) {
+    CURRENT_FD_TABLE_FN.call_once(|| f);
+}
+
+/// Returns the file descriptor table of the current task.
+pub fn current_fd_table() -> Arc<FdTable> {
+    CURRENT_FD_TABLE_FN
+        .get()
+        .and_then(|f| f())
+        .unwrap_or_else(|| FD_TABLE.clone())
 }
 
 pub fn get_file_like(fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
-    FD_TABLE
-        .read()
-        .get(fd as usize)
-        .cloned()
-        .ok_or(LinuxError::EBADF)
+    current_fd_table().get(fd)
 }
 
-pub fn add_file_like(f: Arc<dyn FileLike>) -> LinuxResult<c_int> {
-    Ok(FD_TABLE.write().add(f).ok_or(LinuxError::EMFILE)? as c_int)
+/// Adds `f` to the file descriptor table of the current task, with the
+/// `FD_CLOEXEC` flag set if `cloexec` is true.
+pub fn add_file_like(f: Arc<dyn FileLike>, cloexec: bool) -> LinuxResult<c_int> {
+    current_fd_table().add(f, cloexec)
 }
 
 pub fn close_file_like(fd: c_int) -> LinuxResult {
-    let f = FD_TABLE
-        .write()
-        .remove(fd as usize)
-        .ok_or(LinuxError::EBADF)?;
-    drop(f);
-    Ok(())
+    current_fd_table().remove(fd)
 }
 
 /// Close a file by `fd`.
@@ -61,57 +250,68 @@ pub fn sys_close(fd: c_int) -> c_int {
     syscall_body!(sys_close, close_file_like(fd).map(|_| 0))
 }
 
-fn dup_fd(old_fd: c_int) -> LinuxResult<c_int> {
-    let f = get_file_like(old_fd)?;
-    let new_fd = add_file_like(f)?;
-    Ok(new_fd)
+fn dup_fd(old_fd: c_int, min_fd: c_int, cloexec: bool) -> LinuxResult<c_int> {
+    let table = current_fd_table();
+    let f = table.get(old_fd)?;
+    table.add_from(min_fd, f, cloexec)
 }
 
 /// Duplicate a file descriptor.
 pub fn sys_dup(old_fd: c_int) -> c_int {
     debug!("sys_dup <= {}", old_fd);
-    syscall_body!(sys_dup, dup_fd(old_fd))
+    syscall_body!(sys_dup, dup_fd(old_fd, 0, false))
 }
 
 /// Duplicate a file descriptor, but it uses the file descriptor number specified in `new_fd`.
 ///
-/// TODO: `dup2` should forcibly close new_fd if it is already opened.
+/// If `new_fd` is already opened, it is closed first.
 pub fn sys_dup2(old_fd: c_int, new_fd: c_int) -> c_int {
     debug!("sys_dup2 <= old_fd: {}, new_fd: {}", old_fd, new_fd);
     syscall_body!(sys_dup2, {
+        let table = current_fd_table();
+        let f = table.get(old_fd)?;
         if old_fd == new_fd {
-            let r = sys_fcntl(old_fd, ctypes::F_GETFD as _, 0);
-            if r >= 0 {
-                return Ok(old_fd);
-            } else {
-                return Ok(r);
-            }
-        }
-        if new_fd as usize >= AX_FILE_LIMIT {
-            return Err(LinuxError::EBADF);
+            return Ok(old_fd);
         }
+        table.add_at(new_fd, f, false)
+    })
+}
 
-        let f = get_file_like(old_fd)?;
-        FD_TABLE
-            .write()
-            .add_at(new_fd as usize, f)
-            .ok_or(LinuxError::EMFILE)?;
-
-        Ok(new_fd)
+/// Like [`sys_dup2`], but `flags` may contain `O_CLOEXEC` to set the
+/// `FD_CLOEXEC` flag of `new_fd`, and `old_fd` must not equal `new_fd`.
+pub fn sys_dup3(old_fd: c_int, new_fd: c_int, flags: c_int) -> c_int {
+    debug!(
+        "sys_dup3 <= old_fd: {}, new_fd: {}, flags: {:#x}",
+        old_fd, new_fd, flags
+    );
+    syscall_body!(sys_dup3, {
+        let flags = flags as u32;
+        if old_fd == new_fd || flags & !ctypes::O_CLOEXEC != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let table = current_fd_table();
+        let f = table.get(old_fd)?;
+        table.add_at(new_fd, f, flags & ctypes::O_CLOEXEC != 0)
     })
 }
 
 /// Manipulate file descriptor.
 ///
-/// TODO: `SET/GET` command is ignored, hard-code stdin/stdout
+/// TODO: `F_GETFL` is hard-coded and only `O_NONBLOCK` of `F_SETFL` is supported.
 pub fn sys_fcntl(fd: c_int, cmd: c_int, arg: usize) -> c_int {
     debug!("sys_fcntl <= fd: {} cmd: {} arg: {}", fd, cmd, arg);
     syscall_body!(sys_fcntl, {
         match cmd as u32 {
-            ctypes::F_DUPFD => dup_fd(fd),
-            ctypes::F_DUPFD_CLOEXEC => {
-                // TODO: Change fd flags
-                dup_fd(fd)
+            ctypes::F_DUPFD => dup_fd(fd, arg as c_int, false),
+            ctypes::F_DUPFD_CLOEXEC => dup_fd(fd, arg as c_int, true),
+            ctypes::F_GETFD => {
+                let cloexec = current_fd_table().cloexec(fd)?;
+                Ok(if cloexec { ctypes::FD_CLOEXEC as c_int } else { 0 })
+            }
+            ctypes::F_SETFD => {
+                let cloexec = arg & (ctypes::FD_CLOEXEC as usize) != 0;
+                current_fd_table().set_cloexec(fd, cloexec)?;
+                Ok(0)
             }
             ctypes::F_SETFL => {
                 if fd == 0 || fd == 1 || fd == 2 {
@@ -1,27 +1,90 @@
+use alloc::string::String;
 use alloc::sync::Arc;
 use core::ffi::{c_char, c_int};
 
-use axerrno::{LinuxError, LinuxResult};
-use axfs::fops::OpenOptions;
+use axerrno::{AxError, AxResult, LinuxError, LinuxResult};
+use axfs::cred::current_cred;
+use axfs::fops::{Cap, DirEntry, FileAttr, FilePerm, NodeRef, OpenOptions};
 use axio::{PollState, SeekFrom};
 use axsync::Mutex;
 
 use super::fd_ops::{get_file_like, FileLike};
 use crate::{ctypes, utils::char_ptr_to_str};
 
+/// `dirfd` of the `*at` functions that stands for the current directory.
+const AT_FDCWD: c_int = -100;
+/// Do not follow a symbolic link at the end of the path.
+const AT_SYMLINK_NOFOLLOW: c_int = 0x100;
+/// `unlinkat`: remove a directory instead of a file.
+const AT_REMOVEDIR: c_int = 0x200;
+/// Operate on `dirfd` itself if the path is empty.
+const AT_EMPTY_PATH: c_int = 0x1000;
+/// `renameat2`: do not replace the new path if it exists.
+const RENAME_NOREPLACE: c_int = 0x1;
+
+/// `faccessat` modes.
+const R_OK: c_int = 4;
+const W_OK: c_int = 2;
+const X_OK: c_int = 1;
+
+/// An ID argument of `chown` that keeps the current one.
+const KEEP_ID: u32 = u32::MAX;
+
+/// The maximum number of symbolic links followed to resolve a path.
+const MAX_SYMLINKS: usize = 40;
+
 pub struct File {
     inner: Mutex<axfs::fops::File>,
+    /// The absolute path of the file when it was opened.
+    path: String,
+}
+
+/// An opened directory, against which the `*at` functions resolve relative
+/// paths.
+pub struct Directory {
+    inner: Mutex<DirInner>,
+    /// The absolute path of the directory when it was opened, for renaming
+    /// across directories.
+    path: String,
+}
+
+struct DirInner {
+    dir: axfs::fops::Directory,
+    /// The offset (`d_off`) of the next entry.
+    offset: u64,
+    /// An entry that has been read but did not fit in the `getdents64`
+    /// buffer.
+    pending: Option<DirEntry>,
+}
+
+/// Converts file attributes and the owner `(uid, gid)` to `struct stat`.
+fn attr_to_stat(attr: &FileAttr, (uid, gid): (u32, u32)) -> ctypes::stat {
+    let ty = attr.file_type() as u8;
+    let perm = attr.perm().bits() as u32;
+    let st_mode = ((ty as u32) << 12) | perm;
+    ctypes::stat {
+        st_ino: 1,
+        st_nlink: 1,
+        st_mode,
+        st_uid: uid,
+        st_gid: gid,
+        st_size: attr.size() as _,
+        st_blocks: attr.blocks() as _,
+        st_blksize: 512,
+        ..Default::default()
+    }
 }
 
 impl File {
-    fn new(inner: axfs::fops::File) -> Self {
+    fn new(inner: axfs::fops::File, path: String) -> Self {
         Self {
             inner: Mutex::new(inner),
+            path,
         }
     }
 
-    fn add_to_fd_table(self) -> LinuxResult<c_int> {
-        super::fd_ops::add_file_like(Arc::new(self))
+    fn add_to_fd_table(self, cloexec: bool) -> LinuxResult<c_int> {
+        super::fd_ops::current_fd_table().add(Arc::new(self), cloexec)
     }
 
     fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
@@ -42,21 +105,12 @@ impl FileLike for File {
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        let metadata = self.inner.lock().get_attr()?;
-        let ty = metadata.file_type() as u8;
-        let perm = metadata.perm().bits() as u32;
-        let st_mode = ((ty as u32) << 12) | perm;
-        Ok(ctypes::stat {
-            st_ino: 1,
-            st_nlink: 1,
-            st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
-            st_size: metadata.size() as _,
-            st_blocks: metadata.blocks() as _,
-            st_blksize: 512,
-            ..Default::default()
-        })
+        let inner = self.inner.lock();
+        Ok(attr_to_stat(&inner.get_attr()?, inner.owner()?))
+    }
+
+    fn path(&self) -> String {
+        self.path.clone()
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
@@ -75,10 +129,204 @@ impl FileLike for File {
     }
 }
 
+impl Directory {
+    fn new(dir: axfs::fops::Directory, path: String) -> Self {
+        Self {
+            inner: Mutex::new(DirInner {
+                dir,
+                offset: 0,
+                pending: None,
+            }),
+            path,
+        }
+    }
+
+    fn add_to_fd_table(self, cloexec: bool) -> LinuxResult<c_int> {
+        super::fd_ops::current_fd_table().add(Arc::new(self), cloexec)
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        let f = super::fd_ops::get_file_like(fd)?;
+        f.into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::ENOTDIR)
+    }
+}
+
+impl FileLike for Directory {
+    fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EISDIR)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EBADF)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let inner = self.inner.lock();
+        Ok(attr_to_stat(&inner.dir.get_attr()?, inner.dir.owner()?))
+    }
+
+    fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+        Ok(())
+    }
+}
+
+/// The directory that the relative path of an `*at` function is resolved
+/// against.
+enum BaseDir {
+    Cwd,
+    Dir(Arc<Directory>),
+}
+
+impl BaseDir {
+    /// Returns the base directory of `path` given by `dirfd`, which is
+    /// ignored if `path` is absolute.
+    fn new(dirfd: c_int, path: &str) -> LinuxResult<Self> {
+        if dirfd == AT_FDCWD || path.starts_with('/') {
+            Ok(Self::Cwd)
+        } else {
+            Ok(Self::Dir(Directory::from_fd(dirfd)?))
+        }
+    }
+
+    fn open_file(&self, path: &str, opts: &OpenOptions) -> AxResult<axfs::fops::File> {
+        match self {
+            Self::Cwd => axfs::fops::File::open(path, opts),
+            Self::Dir(dir) => dir.inner.lock().dir.open_file_at(path, opts),
+        }
+    }
+
+    fn open_dir(&self, path: &str, opts: &OpenOptions) -> AxResult<axfs::fops::Directory> {
+        match self {
+            Self::Cwd => axfs::fops::Directory::open_dir(path, opts),
+            Self::Dir(dir) => dir.inner.lock().dir.open_dir_at(path, opts),
+        }
+    }
+
+    fn get_attr(&self, path: &str) -> AxResult<FileAttr> {
+        match self {
+            Self::Cwd => axfs::fops::get_attr(path),
+            Self::Dir(dir) => dir.inner.lock().dir.get_attr_at(path),
+        }
+    }
+
+    fn owner(&self, path: &str) -> AxResult<(u32, u32)> {
+        match self {
+            Self::Cwd => axfs::fops::owner(path),
+            Self::Dir(dir) => dir.inner.lock().dir.owner_at(path),
+        }
+    }
+
+    fn set_perm(&self, path: &str, perm: FilePerm) -> AxResult {
+        match self {
+            Self::Cwd => axfs::fops::set_perm(path, perm),
+            Self::Dir(dir) => dir.inner.lock().dir.set_perm_at(path, perm),
+        }
+    }
+
+    fn set_owner(&self, path: &str, uid: u32, gid: u32) -> AxResult {
+        match self {
+            Self::Cwd => axfs::fops::set_owner(path, uid, gid),
+            Self::Dir(dir) => dir.inner.lock().dir.set_owner_at(path, uid, gid),
+        }
+    }
+
+    fn read_link(&self, path: &str) -> AxResult<String> {
+        match self {
+            Self::Cwd => axfs::fops::read_link(path),
+            Self::Dir(dir) => dir.inner.lock().dir.read_link_at(path),
+        }
+    }
+
+    /// Checks the access to `path` with the real user and group IDs, as
+    /// `access` does.
+    fn check_access(&self, path: &str, cap: Cap) -> AxResult {
+        let cred = current_cred().with_real_ids();
+        match self {
+            Self::Cwd => axfs::fops::check_access(path, cap, &cred),
+            Self::Dir(dir) => dir.inner.lock().dir.check_access_at(path, cap, &cred),
+        }
+    }
+
+    fn create_dir(&self, path: &str) -> AxResult {
+        match self {
+            Self::Cwd => axfs::api::create_dir(path),
+            Self::Dir(dir) => dir.inner.lock().dir.create_dir(path),
+        }
+    }
+
+    fn remove_file(&self, path: &str) -> AxResult {
+        match self {
+            Self::Cwd => axfs::api::remove_file(path),
+            Self::Dir(dir) => dir.inner.lock().dir.remove_file(path),
+        }
+    }
+
+    fn remove_dir(&self, path: &str) -> AxResult {
+        match self {
+            Self::Cwd => axfs::api::remove_dir(path),
+            Self::Dir(dir) => dir.inner.lock().dir.remove_dir(path),
+        }
+    }
+
+    /// Returns the absolute path of `path`.
+    fn absolute_path(&self, path: &str) -> AxResult<String> {
+        match self {
+            Self::Cwd => axfs::api::canonicalize(path),
+            Self::Dir(dir) => axfs::api::canonicalize(&alloc::format!("{}/{}", dir.path, path)),
+        }
+    }
+}
+
+/// Follows `path` relative to `base` as long as it is a symbolic link, and
+/// returns the path of the file it refers to in the end. The VFS does not
+/// follow links within a path, so only the last component is followed.
+///
+/// Fails with `ELOOP` if there are too many links to follow.
+fn follow_link(base: &BaseDir, path: &str) -> LinuxResult<String> {
+    let mut path = String::from(path);
+    for i in 0..=MAX_SYMLINKS {
+        let target = match base.read_link(&path) {
+            Ok(target) => target,
+            Err(AxError::InvalidInput) => return Ok(path),
+            Err(e) => return Err(e.into()),
+        };
+        if i == MAX_SYMLINKS {
+            break;
+        }
+        // A relative target is relative to the directory of the link.
+        path = if target.starts_with('/') {
+            target
+        } else {
+            let link = base.absolute_path(&path)?;
+            let dir = &link[..link.rfind('/').unwrap_or(0)];
+            alloc::format!("{}/{}", dir, target)
+        };
+    }
+    Err(LinuxError::ELOOP)
+}
+
 /// Convert open flags to [`OpenOptions`].
-fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
+fn flags_to_options(flags: c_int, mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
     let mut options = OpenOptions::new();
+    options.mode(mode as u32 & 0o777);
     match flags & 0b11 {
         ctypes::O_RDONLY => options.read(true),
         ctypes::O_WRONLY => options.write(true),
@@ -102,6 +350,26 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     options
 }
 
+/// Opens `path` relative to `base`, as a [`Directory`] if it is one and is
+/// opened read-only, or if `O_DIRECTORY` is set.
+fn open_at(base: &BaseDir, path: &str, flags: c_int, mode: ctypes::mode_t) -> LinuxResult<c_int> {
+    let flags = flags as u32;
+    let cloexec = flags & ctypes::O_CLOEXEC != 0;
+    let is_dir = flags & ctypes::O_DIRECTORY != 0
+        || (flags & 0b11 == ctypes::O_RDONLY
+            && base.get_attr(path).is_ok_and(|attr| attr.is_dir()));
+    if is_dir {
+        let mut options = OpenOptions::new();
+        options.read(true);
+        let dir = base.open_dir(path, &options)?;
+        Directory::new(dir, base.absolute_path(path)?).add_to_fd_table(cloexec)
+    } else {
+        let options = flags_to_options(flags as c_int, mode);
+        let file = base.open_file(path, &options)?;
+        File::new(file, base.absolute_path(path)?).add_to_fd_table(cloexec)
+    }
+}
+
 /// Open a file by `filename` and insert it into the file descriptor table.
 ///
 /// Return its index in the file table (`fd`). Return `EMFILE` if it already
@@ -109,10 +377,28 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
 pub fn sys_open(filename: *const c_char, flags: c_int, mode: ctypes::mode_t) -> c_int {
     let filename = char_ptr_to_str(filename);
     debug!("sys_open <= {:?} {:#o} {:#o}", filename, flags, mode);
-    syscall_body!(sys_open, {
-        let options = flags_to_options(flags, mode);
-        let file = axfs::fops::File::open(filename?, &options)?;
-        File::new(file).add_to_fd_table()
+    syscall_body!(sys_open, { open_at(&BaseDir::Cwd, filename?, flags, mode) })
+}
+
+/// Open a file by `filename` relative to the directory `dirfd` (or the
+/// current directory if it is `AT_FDCWD`), and insert it into the file
+/// descriptor table.
+///
+/// Return its index in the file table (`fd`).
+pub fn sys_openat(
+    dirfd: c_int,
+    filename: *const c_char,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
+    let filename = char_ptr_to_str(filename);
+    debug!(
+        "sys_openat <= {} {:?} {:#o} {:#o}",
+        dirfd, filename, flags, mode
+    );
+    syscall_body!(sys_openat, {
+        let filename = filename?;
+        open_at(&BaseDir::new(dirfd, filename)?, filename, flags, mode)
     })
 }
 
@@ -133,6 +419,34 @@ pub fn sys_lseek(fd: c_int, offset: ctypes::off_t, whence: c_int) -> ctypes::off
     })
 }
 
+/// Truncate or extend the file indicated by `fd` to `length` bytes.
+///
+/// Return 0 if success.
+pub fn sys_ftruncate(fd: c_int, length: ctypes::off_t) -> c_int {
+    debug!("sys_ftruncate <= {} {}", fd, length);
+    syscall_body!(sys_ftruncate, {
+        if length < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        File::from_fd(fd)?.inner.lock().truncate(length as _)?;
+        Ok(0)
+    })
+}
+
+/// Returns the node of the regular file `fd` for mapping it into memory,
+/// with write access if `writable` (for a shared writable mapping).
+///
+/// Fails with `ENODEV` if `fd` is not a regular file, or `EACCES` if it was
+/// not opened for the access.
+pub fn file_mmap_node(fd: c_int, writable: bool) -> LinuxResult<NodeRef> {
+    let file = File::from_fd(fd).map_err(|e| match e {
+        LinuxError::EINVAL => LinuxError::ENODEV,
+        e => e,
+    })?;
+    let node = file.inner.lock().mmap_node(writable)?;
+    Ok(node)
+}
+
 /// Get the file metadata by `path` and write into `buf`.
 ///
 /// Return 0 if success.
@@ -145,8 +459,9 @@ pub unsafe fn sys_stat(path: *const c_char, buf: *mut ctypes::stat) -> c_int {
         }
         let mut options = OpenOptions::new();
         options.read(true);
-        let file = axfs::fops::File::open(path?, &options)?;
-        let st = File::new(file).stat()?;
+        let path = path?;
+        let file = axfs::fops::File::open(path, &options)?;
+        let st = File::new(file, axfs::api::canonicalize(path)?).stat()?;
         unsafe { *buf = st };
         Ok(0)
     })
@@ -215,3 +530,363 @@ pub fn sys_rename(old: *const c_char, new: *const c_char) -> c_int {
         Ok(0)
     })
 }
+
+/// Create a directory at `path` relative to the directory `dirfd`.
+///
+/// Return 0 if success.
+pub fn sys_mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_mkdirat <= {} {:?} {:#o}", dirfd, path, mode);
+    syscall_body!(sys_mkdirat, {
+        let path = path?;
+        let base = BaseDir::new(dirfd, path)?;
+        base.create_dir(path)?;
+        // Filesystems that do not record permission bits keep the default.
+        base.set_perm(path, FilePerm::from_bits_truncate((mode & 0o777) as _))
+            .ok();
+        Ok(0)
+    })
+}
+
+/// Remove the file, or the directory if `flags` has `AT_REMOVEDIR`, at
+/// `path` relative to the directory `dirfd`.
+///
+/// Return 0 if success.
+pub fn sys_unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_unlinkat <= {} {:?} {:#x}", dirfd, path, flags);
+    syscall_body!(sys_unlinkat, {
+        let path = path?;
+        let base = BaseDir::new(dirfd, path)?;
+        match flags {
+            0 => base.remove_file(path)?,
+            AT_REMOVEDIR => base.remove_dir(path)?,
+            _ => return Err(LinuxError::EINVAL),
+        }
+        Ok(0)
+    })
+}
+
+/// Rename `old` relative to the directory `olddirfd` to `new` relative to
+/// the directory `newdirfd`. Only `RENAME_NOREPLACE` is supported in `flags`.
+///
+/// Return 0 if success.
+pub fn sys_renameat2(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_renameat2, {
+        let old = char_ptr_to_str(old)?;
+        let new = char_ptr_to_str(new)?;
+        debug!(
+            "sys_renameat2 <= {} {:?} {} {:?} {:#x}",
+            olddirfd, old, newdirfd, new, flags
+        );
+        if flags & !RENAME_NOREPLACE != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let old_base = BaseDir::new(olddirfd, old)?;
+        let new_base = BaseDir::new(newdirfd, new)?;
+        old_base.get_attr(old)?;
+        if flags & RENAME_NOREPLACE != 0 && new_base.get_attr(new).is_ok() {
+            return Err(LinuxError::EEXIST);
+        }
+        // The VFS renames paths under the same node, so the two directories
+        // are replaced by their absolute paths.
+        let old = old_base.absolute_path(old)?;
+        let new = new_base.absolute_path(new)?;
+        axfs::api::rename(&old, &new)?;
+        Ok(0)
+    })
+}
+
+/// Get the metadata of the file at `path` relative to the directory `dirfd`
+/// and write into `buf`. If `path` is empty and `flags` has `AT_EMPTY_PATH`,
+/// get the metadata of `dirfd` itself.
+///
+/// Return 0 if success.
+pub unsafe fn sys_fstatat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut ctypes::stat,
+    flags: c_int,
+) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!(
+        "sys_fstatat <= {} {:?} {:#x} {:#x}",
+        dirfd, path, buf as usize, flags
+    );
+    syscall_body!(sys_fstatat, {
+        let path = path?;
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let st = if !path.is_empty() {
+            let base = BaseDir::new(dirfd, path)?;
+            let path = if flags & AT_SYMLINK_NOFOLLOW == 0 {
+                follow_link(&base, path)?
+            } else {
+                String::from(path)
+            };
+            attr_to_stat(&base.get_attr(&path)?, base.owner(&path)?)
+        } else if flags & AT_EMPTY_PATH == 0 {
+            return Err(LinuxError::ENOENT);
+        } else if dirfd == AT_FDCWD {
+            attr_to_stat(&axfs::fops::get_attr(".")?, axfs::fops::owner(".")?)
+        } else {
+            get_file_like(dirfd)?.stat()?
+        };
+        unsafe { *buf = st };
+        Ok(0)
+    })
+}
+
+/// Check whether the file at `path` relative to the directory `dirfd` can be
+/// accessed with `mode`, which is `F_OK` (0) or a mask of `R_OK`, `W_OK` and
+/// `X_OK`, by the real user and group of the calling task.
+///
+/// Return 0 if success.
+pub fn sys_faccessat(dirfd: c_int, path: *const c_char, mode: c_int) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_faccessat <= {} {:?} {:#o}", dirfd, path, mode);
+    syscall_body!(sys_faccessat, {
+        let path = path?;
+        if mode & !(R_OK | W_OK | X_OK) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut cap = Cap::empty();
+        if mode & R_OK != 0 {
+            cap |= Cap::READ;
+        }
+        if mode & W_OK != 0 {
+            cap |= Cap::WRITE;
+        }
+        if mode & X_OK != 0 {
+            cap |= Cap::EXECUTE;
+        }
+        BaseDir::new(dirfd, path)?.check_access(path, cap)?;
+        Ok(0)
+    })
+}
+
+/// Changes the permission bits of a file owned by `owner` to `mode` with
+/// `set_perm`. Only the owner and root can do it.
+fn chmod_with(
+    owner: (u32, u32),
+    mode: ctypes::mode_t,
+    set_perm: impl FnOnce(FilePerm) -> AxResult,
+) -> LinuxResult {
+    let cred = current_cred();
+    if !cred.is_privileged() && cred.euid != owner.0 {
+        return Err(LinuxError::EPERM);
+    }
+    set_perm(FilePerm::from_bits_truncate((mode & 0o777) as _))?;
+    Ok(())
+}
+
+/// Changes the owner of a file owned by `owner` to `uid` and `gid` with
+/// `set_owner`. Either may be -1 to keep the current one.
+///
+/// Only root can give a file away. The owner can only change the group, to
+/// one it belongs to.
+fn chown_with(
+    owner: (u32, u32),
+    uid: u32,
+    gid: u32,
+    set_owner: impl FnOnce(u32, u32) -> AxResult,
+) -> LinuxResult {
+    let cred = current_cred();
+    let uid = if uid == KEEP_ID { owner.0 } else { uid };
+    let gid = if gid == KEEP_ID { owner.1 } else { gid };
+    if !cred.is_privileged()
+        && (cred.euid != owner.0 || uid != owner.0 || (gid != owner.1 && !cred.in_group(gid)))
+    {
+        return Err(LinuxError::EPERM);
+    }
+    set_owner(uid, gid)?;
+    Ok(())
+}
+
+/// Change the permission bits of the file at `path` relative to the
+/// directory `dirfd` to `mode`.
+///
+/// Return 0 if success.
+pub fn sys_fchmodat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_fchmodat <= {} {:?} {:#o}", dirfd, path, mode);
+    syscall_body!(sys_fchmodat, {
+        let path = path?;
+        let base = BaseDir::new(dirfd, path)?;
+        chmod_with(base.owner(path)?, mode, |perm| base.set_perm(path, perm))?;
+        Ok(0)
+    })
+}
+
+/// Change the permission bits of the file or directory `fd` to `mode`.
+///
+/// Return 0 if success.
+pub fn sys_fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    debug!("sys_fchmod <= {} {:#o}", fd, mode);
+    syscall_body!(sys_fchmod, {
+        let f = get_file_like(fd)?.into_any();
+        if let Some(file) = f.downcast_ref::<File>() {
+            let file = file.inner.lock();
+            chmod_with(file.owner()?, mode, |perm| file.set_perm(perm))?;
+        } else if let Some(dir) = f.downcast_ref::<Directory>() {
+            let dir = &dir.inner.lock().dir;
+            chmod_with(dir.owner()?, mode, |perm| dir.set_perm(perm))?;
+        } else {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(0)
+    })
+}
+
+/// Change the owner of the file at `path` relative to the directory `dirfd`
+/// to `uid` and `gid`, or of `dirfd` itself if `path` is empty and `flags`
+/// has `AT_EMPTY_PATH`. Either ID may be -1 to keep the current one.
+///
+/// Return 0 if success.
+pub fn sys_fchownat(dirfd: c_int, path: *const c_char, uid: u32, gid: u32, flags: c_int) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!(
+        "sys_fchownat <= {} {:?} {} {} {:#x}",
+        dirfd, path, uid as i32, gid as i32, flags
+    );
+    syscall_body!(sys_fchownat, {
+        let path = path?;
+        if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        if path.is_empty() {
+            if flags & AT_EMPTY_PATH == 0 {
+                return Err(LinuxError::ENOENT);
+            }
+            if dirfd != AT_FDCWD {
+                return Ok(sys_fchown(dirfd, uid, gid));
+            }
+            chown_with(axfs::fops::owner(".")?, uid, gid, |uid, gid| {
+                axfs::fops::set_owner(".", uid, gid)
+            })?;
+            return Ok(0);
+        }
+        let base = BaseDir::new(dirfd, path)?;
+        let path = if flags & AT_SYMLINK_NOFOLLOW == 0 {
+            follow_link(&base, path)?
+        } else {
+            String::from(path)
+        };
+        chown_with(base.owner(&path)?, uid, gid, |uid, gid| {
+            base.set_owner(&path, uid, gid)
+        })?;
+        Ok(0)
+    })
+}
+
+/// Change the owner of the file or directory `fd` to `uid` and `gid`. Either
+/// ID may be -1 to keep the current one.
+///
+/// Return 0 if success.
+pub fn sys_fchown(fd: c_int, uid: u32, gid: u32) -> c_int {
+    debug!("sys_fchown <= {} {} {}", fd, uid as i32, gid as i32);
+    syscall_body!(sys_fchown, {
+        let f = get_file_like(fd)?.into_any();
+        if let Some(file) = f.downcast_ref::<File>() {
+            let file = file.inner.lock();
+            chown_with(file.owner()?, uid, gid, |uid, gid| file.set_owner(uid, gid))?;
+        } else if let Some(dir) = f.downcast_ref::<Directory>() {
+            let dir = &dir.inner.lock().dir;
+            chown_with(dir.owner()?, uid, gid, |uid, gid| dir.set_owner(uid, gid))?;
+        } else {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(0)
+    })
+}
+
+/// Read the target of the symbolic link at `path` relative to the directory
+/// `dirfd` into `buf`, truncated to `bufsize` bytes and not NUL-terminated.
+///
+/// Return the number of bytes stored, or `EINVAL` if `path` is not a
+/// symbolic link.
+pub fn sys_readlinkat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsize: usize,
+) -> ctypes::ssize_t {
+    let path = char_ptr_to_str(path);
+    debug!(
+        "sys_readlinkat <= {} {:?} {:#x} {}",
+        dirfd, path, buf as usize, bufsize
+    );
+    syscall_body!(sys_readlinkat, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let path = path?;
+        let target = BaseDir::new(dirfd, path)?.read_link(path)?;
+        let len = target.len().min(bufsize);
+        let buf = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+        buf.copy_from_slice(&target.as_bytes()[..len]);
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
+/// Read the entries of the directory `fd` into `buf` as `struct
+/// linux_dirent64`s.
+///
+/// Return the number of bytes read, or 0 at the end of the directory.
+pub unsafe fn sys_getdents64(fd: c_int, buf: *mut u8, len: usize) -> ctypes::ssize_t {
+    debug!("sys_getdents64 <= {} {:#x} {}", fd, buf as usize, len);
+    syscall_body!(sys_getdents64, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let buf = unsafe { core::slice::from_raw_parts_mut(buf, len) };
+        let dir = Directory::from_fd(fd)?;
+        let mut inner = dir.inner.lock();
+        let inner = &mut *inner;
+        let mut written = 0;
+        loop {
+            let entry = match inner.pending.take() {
+                Some(entry) => entry,
+                None => {
+                    let mut entries = [DirEntry::default()];
+                    if inner.dir.read_dir(&mut entries)? == 0 {
+                        break;
+                    }
+                    let [entry] = entries;
+                    entry
+                }
+            };
+            // `d_ino`, `d_off`, `d_reclen`, `d_type` and the NUL-terminated
+            // `d_name`, padded to 8 bytes.
+            let name = entry.name_as_bytes();
+            let reclen = (19 + name.len() + 1).next_multiple_of(8);
+            if written + reclen > buf.len() {
+                inner.pending = Some(entry);
+                if written == 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                break;
+            }
+            inner.offset += 1;
+            let record = &mut buf[written..written + reclen];
+            record.fill(0);
+            record[0..8].copy_from_slice(&1u64.to_ne_bytes()); // Some libcs skip `d_ino` 0
+            record[8..16].copy_from_slice(&inner.offset.to_ne_bytes());
+            record[16..18].copy_from_slice(&(reclen as u16).to_ne_bytes());
+            record[18] = entry.entry_type() as u8;
+            record[19..19 + name.len()].copy_from_slice(name);
+            written += reclen;
+        }
+        Ok(written as ctypes::ssize_t)
+    })
+}
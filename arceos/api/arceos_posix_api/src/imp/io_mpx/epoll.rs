@@ -4,6 +4,7 @@
 
 use alloc::collections::btree_map::Entry;
 use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::sync::Arc;
 use core::{ffi::c_int, time::Duration};
 
@@ -124,6 +125,10 @@ impl FileLike for EpollInstance {
         })
     }
 
+    fn path(&self) -> String {
+        "anon_inode:[eventpoll]".into()
+    }
+
     fn into_any(self: Arc<Self>) -> alloc::sync::Arc<dyn core::any::Any + Send + Sync> {
         self
     }
@@ -147,7 +152,22 @@ pub fn sys_epoll_create(size: c_int) -> c_int {
             return Err(LinuxError::EINVAL);
         }
         let epoll_instance = EpollInstance::new(0);
-        add_file_like(Arc::new(epoll_instance))
+        add_file_like(Arc::new(epoll_instance), false)
+    })
+}
+
+/// Like [`sys_epoll_create`], but `flags` may contain `EPOLL_CLOEXEC` to set
+/// the `FD_CLOEXEC` flag of the new file descriptor.
+pub fn sys_epoll_create1(flags: c_int) -> c_int {
+    debug!("sys_epoll_create1 <= {:#x}", flags);
+    syscall_body!(sys_epoll_create1, {
+        // `EPOLL_CLOEXEC` is `O_CLOEXEC`.
+        let flags = flags as u32;
+        if flags & !ctypes::O_CLOEXEC != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let epoll_instance = EpollInstance::new(0);
+        add_file_like(Arc::new(epoll_instance), flags & ctypes::O_CLOEXEC != 0)
     })
 }
 
@@ -2,6 +2,7 @@
 //!
 //! * [`select`](select::sys_select)
 //! * [`epoll_create`](epoll::sys_epoll_create)
+//! * [`epoll_create1`](epoll::sys_epoll_create1)
 //! * [`epoll_ctl`](epoll::sys_epoll_ctl)
 //! * [`epoll_wait`](epoll::sys_epoll_wait)
 
@@ -11,6 +12,6 @@ mod epoll;
 mod select;
 
 #[cfg(feature = "epoll")]
-pub use self::epoll::{sys_epoll_create, sys_epoll_ctl, sys_epoll_wait};
+pub use self::epoll::{sys_epoll_create, sys_epoll_create1, sys_epoll_ctl, sys_epoll_wait};
 #[cfg(feature = "select")]
 pub use self::select::sys_select;
@@ -1,4 +1,4 @@
-use alloc::sync::Arc;
+use alloc::{format, string::String, sync::Arc};
 use core::ffi::c_int;
 
 use axerrno::{LinuxError, LinuxResult};
@@ -173,6 +173,10 @@ impl FileLike for Pipe {
         })
     }
 
+    fn path(&self) -> String {
+        format!("pipe:[{}]", Arc::as_ptr(&self.buffer) as usize)
+    }
+
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
         self
     }
@@ -194,15 +198,28 @@ impl FileLike for Pipe {
 ///
 /// Return 0 if succeed
 pub fn sys_pipe(fds: &mut [c_int]) -> c_int {
-    debug!("sys_pipe <= {:#x}", fds.as_ptr() as usize);
-    syscall_body!(sys_pipe, {
+    sys_pipe2(fds, 0)
+}
+
+/// Create a pipe, with the `FD_CLOEXEC` flag set on both ends if `flags`
+/// contains `O_CLOEXEC`.
+///
+/// Return 0 if succeed
+pub fn sys_pipe2(fds: &mut [c_int], flags: c_int) -> c_int {
+    debug!("sys_pipe2 <= {:#x} {:#x}", fds.as_ptr() as usize, flags);
+    syscall_body!(sys_pipe2, {
         if fds.len() != 2 {
             return Err(LinuxError::EFAULT);
         }
+        let flags = flags as u32;
+        if flags & !ctypes::O_CLOEXEC != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let cloexec = flags & ctypes::O_CLOEXEC != 0;
 
         let (read_end, write_end) = Pipe::new();
-        let read_fd = add_file_like(Arc::new(read_end))?;
-        let write_fd = add_file_like(Arc::new(write_end)).inspect_err(|_| {
+        let read_fd = add_file_like(Arc::new(read_end), cloexec)?;
+        let write_fd = add_file_like(Arc::new(write_end), cloexec).inspect_err(|_| {
             close_file_like(read_fd).ok();
         })?;
 
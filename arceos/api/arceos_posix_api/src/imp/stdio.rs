@@ -3,7 +3,10 @@ use axio::{prelude::*, BufReader};
 use axsync::Mutex;
 
 #[cfg(feature = "fd")]
-use {alloc::sync::Arc, axerrno::LinuxError, axerrno::LinuxResult, axio::PollState};
+use {
+    alloc::string::String, alloc::sync::Arc, axerrno::LinuxError, axerrno::LinuxResult,
+    axio::PollState,
+};
 
 fn console_read_bytes() -> Option<u8> {
     axhal::console::getchar().map(|c| if c == b'\r' { b'\n' } else { c })
@@ -117,6 +120,10 @@ impl super::fd_ops::FileLike for Stdin {
         })
     }
 
+    fn path(&self) -> String {
+        "/dev/console".into()
+    }
+
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
         self
     }
@@ -153,6 +160,10 @@ impl super::fd_ops::FileLike for Stdout {
         })
     }
 
+    fn path(&self) -> String {
+        "/dev/console".into()
+    }
+
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
         self
     }
@@ -2,11 +2,12 @@ use alloc::collections::BTreeMap;
 use alloc::sync::{Arc, Weak};
 use alloc::{string::String, vec::Vec};
 use crate::alloc::string::ToString;
-use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType};
 use axfs_vfs::{VfsError, VfsResult};
 use spin::RwLock;
 
 use crate::file::FileNode;
+use crate::NodeMeta;
 
 /// The directory node in the RAM filesystem.
 ///
@@ -17,6 +18,7 @@ pub struct DirNode {
     children: RwLock<BTreeMap<String, VfsNodeRef>>,//目录项表
     //                        ↑文件名    ↑文件实体（相当于inode的抽象
     //BTreeMap数据结构相当于一个map 存键值对 但是将键值对插到平衡二叉树 内部是有序的并且迭代的时候按照键的顺序
+    pub(crate) meta: NodeMeta,
 }
 
 impl DirNode {
@@ -25,6 +27,7 @@ impl DirNode {
             this: this.clone(),
             parent: RwLock::new(parent.unwrap_or_else(|| Weak::<Self>::new())),
             children: RwLock::new(BTreeMap::new()),
+            meta: NodeMeta::new(VfsNodePerm::default_dir()),
         })
     }
 
@@ -94,7 +97,7 @@ impl DirNode {
 
 impl VfsNodeOps for DirNode {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_dir(4096, 0))
+        Ok(VfsNodeAttr::new(self.meta.perm(), VfsNodeType::Dir, 4096, 0))
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
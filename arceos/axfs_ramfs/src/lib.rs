@@ -8,12 +8,14 @@ extern crate alloc;
 
 mod dir;
 mod file;
+mod meta;
 
 #[cfg(test)]
 mod tests;
 
 pub use self::dir::DirNode;
 pub use self::file::FileNode;
+pub use self::meta::NodeMeta;
 
 use alloc::sync::Arc;
 use axfs_vfs::{VfsNodeRef, VfsOps, VfsResult};
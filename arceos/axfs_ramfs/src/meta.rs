@@ -0,0 +1,63 @@
+use axfs_vfs::{VfsNodeOps, VfsNodePerm};
+use spin::RwLock;
+
+use crate::{DirNode, FileNode};
+
+/// The permission bits and the owner of a node.
+///
+/// They are not part of [`axfs_vfs::VfsNodeOps`], so they are reached
+/// through [`NodeMeta::of`]. New nodes belong to root (uid and gid 0).
+pub struct NodeMeta {
+    inner: RwLock<MetaInner>,
+}
+
+struct MetaInner {
+    perm: VfsNodePerm,
+    uid: u32,
+    gid: u32,
+}
+
+impl NodeMeta {
+    pub(crate) const fn new(perm: VfsNodePerm) -> Self {
+        Self {
+            inner: RwLock::new(MetaInner {
+                perm,
+                uid: 0,
+                gid: 0,
+            }),
+        }
+    }
+
+    /// Returns the metadata of `node` if it is a node of a RAM filesystem.
+    pub fn of(node: &dyn VfsNodeOps) -> Option<&NodeMeta> {
+        let any = node.as_any();
+        if let Some(file) = any.downcast_ref::<FileNode>() {
+            Some(&file.meta)
+        } else {
+            any.downcast_ref::<DirNode>().map(|dir| &dir.meta)
+        }
+    }
+
+    /// Returns the permission bits.
+    pub fn perm(&self) -> VfsNodePerm {
+        self.inner.read().perm
+    }
+
+    /// Sets the permission bits.
+    pub fn set_perm(&self, perm: VfsNodePerm) {
+        self.inner.write().perm = perm;
+    }
+
+    /// Returns the user and group IDs of the owner.
+    pub fn owner(&self) -> (u32, u32) {
+        let inner = self.inner.read();
+        (inner.uid, inner.gid)
+    }
+
+    /// Sets the user and group IDs of the owner.
+    pub fn set_owner(&self, uid: u32, gid: u32) {
+        let mut inner = self.inner.write();
+        inner.uid = uid;
+        inner.gid = gid;
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+use axfs_vfs::{VfsError, VfsNodePerm, VfsNodeType, VfsResult};
 
 use crate::*;
 
@@ -134,3 +134,28 @@ fn test_ramfs() {
     assert_eq!(root.remove("./foo"), Ok(()));
     assert!(ramfs.root_dir_node().get_entries().is_empty());
 }
+
+#[test]
+fn test_ramfs_meta() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    root.create("foo", VfsNodeType::Dir).unwrap();
+    root.create("foo/f1", VfsNodeType::File).unwrap();
+
+    let foo = root.clone().lookup("foo").unwrap();
+    let f1 = root.lookup("foo/f1").unwrap();
+    let meta = NodeMeta::of(f1.as_ref()).unwrap();
+    assert_eq!(meta.owner(), (0, 0));
+    assert_eq!(f1.get_attr().unwrap().perm(), VfsNodePerm::default_file());
+    assert_eq!(
+        foo.get_attr().unwrap().perm(),
+        NodeMeta::of(foo.as_ref()).unwrap().perm()
+    );
+
+    meta.set_owner(1000, 100);
+    meta.set_perm(VfsNodePerm::from_bits_truncate(0o640));
+    assert_eq!(meta.owner(), (1000, 100));
+    assert_eq!(f1.get_attr().unwrap().perm().bits(), 0o640);
+    let f1 = foo.lookup("f1").unwrap();
+    assert_eq!(NodeMeta::of(f1.as_ref()).unwrap().owner(), (1000, 100));
+}
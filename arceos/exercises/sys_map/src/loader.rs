@@ -2,13 +2,14 @@ use std::io::{self, Read};
 use std::io::SeekFrom;
 use std::io::Seek;
 use std::fs::File;
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 use alloc::vec;
 use axhal::paging::MappingFlags;
-use axhal::mem::{PAGE_SIZE_4K, VirtAddr, MemoryAddr};
+use axhal::mem::{PAGE_SIZE_4K, VirtAddr};
 use axmm::AddrSpace;
 
-use elf::abi::{PT_INTERP, PT_LOAD};
+use elf::abi::{PF_R, PF_W, PF_X, PT_GNU_STACK, PT_INTERP, PT_LOAD};
 use elf::endian::AnyEndian;
 use elf::parse::ParseAt;
 use elf::segment::ProgramHeader;
@@ -17,37 +18,83 @@ use elf::ElfBytes;
 
 const ELF_HEAD_BUF_SIZE: usize = 256;
 
-pub fn load_user_app(fname: &str, uspace: &mut AddrSpace) -> io::Result<usize> {
+/// Loads the ELF file `fname` into `uspace`.
+///
+/// Returns the entry point, and whether `PT_GNU_STACK` asks for an
+/// executable stack.
+pub fn load_user_app(fname: &str, uspace: &mut AddrSpace) -> io::Result<(usize, bool)> {
     let mut file = File::open(fname)?;
     let (phdrs, entry, _, _) = load_elf_phdrs(&mut file)?;
 
-    for phdr in &phdrs {
+    let loads: Vec<&ProgramHeader> = phdrs.iter().filter(|phdr| phdr.p_type == PT_LOAD).collect();
+    for (start, end, flags) in segment_areas(&loads) {
+        debug!("{:#x} - {:#x} {:?}", start, end, flags);
+        uspace.map_alloc(start.into(), end - start, flags, true)?;
+    }
+
+    for phdr in loads {
         ax_println!(
             "phdr: offset: {:#X}=>{:#X} size: {:#X}=>{:#X}",
             phdr.p_offset, phdr.p_vaddr, phdr.p_filesz, phdr.p_memsz
         );
 
-        let vaddr = VirtAddr::from(phdr.p_vaddr as usize).align_down_4k();
-        let vaddr_end = VirtAddr::from((phdr.p_vaddr+phdr.p_memsz) as usize)
-            .align_up_4k();
+        // Only the file content is copied: frames are zeroed on allocation,
+        // which covers `.bss`, and the bytes of other segments sharing the
+        // first or last page are left intact.
+        let mut data = vec![0u8; phdr.p_filesz as usize];
+        file.seek(SeekFrom::Start(phdr.p_offset))?;
+        file.read_exact(&mut data)?;
+        uspace.write(VirtAddr::from(phdr.p_vaddr as usize), &data)?;
+    }
 
-        ax_println!("{:#x} - {:#x}", vaddr, vaddr_end);
-        uspace.map_alloc(vaddr, vaddr_end-vaddr, MappingFlags::READ|MappingFlags::WRITE|MappingFlags::EXECUTE|MappingFlags::USER, true)?;
+    let stack_exec = phdrs
+        .iter()
+        .find(|phdr| phdr.p_type == PT_GNU_STACK)
+        .is_some_and(|phdr| phdr.p_flags & PF_X != 0);
+    Ok((entry, stack_exec))
+}
 
-        let mut data = vec![0u8; phdr.p_memsz as usize];
-        file.seek(SeekFrom::Start(phdr.p_offset))?;
+/// Converts the `p_flags` of a segment to user mapping flags.
+fn segment_flags(p_flags: u32) -> MappingFlags {
+    let mut flags = MappingFlags::USER;
+    if p_flags & PF_R != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if p_flags & PF_W != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if p_flags & PF_X != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
 
-        let filesz = phdr.p_filesz as usize;
-        let mut index = 0;
-        while index < filesz {
-            let n = file.read(&mut data[index..filesz])?;
-            index += n;
+/// Computes the page-aligned areas `(start, end, flags)` to map for the
+/// loadable segments `loads`.
+///
+/// Adjacent segments may share a page (e.g. the end of `.text` and the start
+/// of `.data`), in which case that page gets the union of their permissions.
+fn segment_areas(loads: &[&ProgramHeader]) -> Vec<(usize, usize, MappingFlags)> {
+    let mut pages = BTreeMap::new();
+    for phdr in loads {
+        let start = phdr.p_vaddr as usize & !(PAGE_SIZE_4K - 1);
+        let end = (phdr.p_vaddr as usize + phdr.p_memsz as usize).div_ceil(PAGE_SIZE_4K)
+            * PAGE_SIZE_4K;
+        for page in (start..end).step_by(PAGE_SIZE_4K) {
+            *pages.entry(page).or_insert_with(MappingFlags::empty) |= segment_flags(phdr.p_flags);
         }
-        assert_eq!(index, filesz);
-        uspace.write(VirtAddr::from(phdr.p_vaddr as usize), &data)?;
     }
 
-    Ok(entry)
+    let mut areas: Vec<(usize, usize, MappingFlags)> = Vec::new();
+    for (page, flags) in pages {
+        match areas.last_mut() {
+            Some((_, end, last_flags)) if *end == page && *last_flags == flags => {
+                *end += PAGE_SIZE_4K;
+            }
+            _ => areas.push((page, page + PAGE_SIZE_4K, flags)),
+        }
+    }
+    areas
 }
 
 fn load_elf_phdrs(file: &mut File) -> io::Result<(Vec<ProgramHeader>, usize, usize, usize)> {
@@ -70,7 +117,7 @@ fn load_elf_phdrs(file: &mut File) -> io::Result<(Vec<ProgramHeader>, usize, usi
 
     let phdrs: Vec<ProgramHeader> = phdrs
         .iter()
-        .filter(|phdr| phdr.p_type == PT_LOAD || phdr.p_type == PT_INTERP)
+        .filter(|phdr| matches!(phdr.p_type, PT_LOAD | PT_INTERP | PT_GNU_STACK))
         .collect();
     Ok((phdrs, ehdr.e_entry as usize, ehdr.e_phoff as usize, ehdr.e_phnum as usize))
 }
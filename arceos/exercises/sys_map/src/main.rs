@@ -32,16 +32,19 @@ fn main() {
     let mut uspace = axmm::new_user_aspace().unwrap();
 
     // Load user app binary file into address space.
-    let entry = match load_user_app("/sbin/mapfile", &mut uspace) {
+    let (entry, stack_exec) = match load_user_app("/sbin/mapfile", &mut uspace) {
         Ok(e) => e,
         Err(err) => panic!("Cannot load app! {:?}", err),
     };
     ax_println!("entry: {:#x}", entry);
 
     // Init user stack.
-    let ustack_top = init_user_stack(&mut uspace, true).unwrap();
+    let ustack_top = init_user_stack(&mut uspace, true, stack_exec).unwrap();
     ax_println!("New user address space: {:#x?}", uspace);
 
+    syscall::init_syscalls();
+    axsyscall::set_current_aspace_fn(task::current_aspace);
+
     // Let's kick off the user process.
     let user_task = task::spawn_user_task(
         Arc::new(Mutex::new(uspace)),
@@ -53,17 +56,21 @@ fn main() {
     ax_println!("monolithic kernel exit [{:?}] normally!", exit_code);
 }
 
-fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtAddr> {
+fn init_user_stack(uspace: &mut AddrSpace, populating: bool, exec: bool) -> io::Result<VirtAddr> {
     let ustack_top = uspace.end();
     let ustack_vaddr = ustack_top - crate::USER_STACK_SIZE;
     ax_println!(
         "Mapping user stack: {:#x?} -> {:#x?}",
         ustack_vaddr, ustack_top
     );
+    let mut flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    if exec {
+        flags |= MappingFlags::EXECUTE;
+    }
     uspace.map_alloc(
         ustack_vaddr,
         crate::USER_STACK_SIZE,
-        MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
+        flags,
         populating,
     ).unwrap();
 
@@ -1,48 +1,24 @@
 #![allow(dead_code)]
 
 use core::ffi::{c_void, c_char, c_int};
+use alloc::ffi::CString;
+use alloc::vec;
+use axerrno::LinuxError;
 use axhal::arch::TrapFrame;
 use axhal::trap::{register_trap_handler, SYSCALL};
-use axerrno::LinuxError;
+use axsyscall::sysno::*;
+use axsyscall::{syscall_body, ArgFmt, Syscall, SyscallTable, UserPtr, UserSlice};
 use axtask::current;
 use axtask::TaskExtRef;
 use axhal::paging::MappingFlags;
 use arceos_posix_api as api;
 
-const SYS_IOCTL: usize = 29;
-const SYS_OPENAT: usize = 56;
-const SYS_CLOSE: usize = 57;
-const SYS_READ: usize = 63;
-const SYS_WRITE: usize = 64;
-const SYS_WRITEV: usize = 66;
-const SYS_EXIT: usize = 93;
-const SYS_EXIT_GROUP: usize = 94;
-const SYS_SET_TID_ADDRESS: usize = 96;
-const SYS_MMAP: usize = 222;
-
-const AT_FDCWD: i32 = -100;
-
-/// Macro to generate syscall body
-///
-/// It will receive a function which return Result<_, LinuxError> and convert it to
-/// the type which is specified by the caller.
-#[macro_export]
-macro_rules! syscall_body {
-    ($fn: ident, $($stmt: tt)*) => {{
-        #[allow(clippy::redundant_closure_call)]
-        let res = (|| -> axerrno::LinuxResult<_> { $($stmt)* })();
-        match res {
-            Ok(_) | Err(axerrno::LinuxError::EAGAIN) => debug!(concat!(stringify!($fn), " => {:?}"),  res),
-            Err(_) => info!(concat!(stringify!($fn), " => {:?}"), res),
-        }
-        match res {
-            Ok(v) => v as _,
-            Err(e) => {
-                -e.code() as _
-            }
-        }
-    }};
-}
+/// The maximum length of a path.
+const PATH_MAX: usize = 4096;
+/// The maximum number of buffers of `writev`.
+const IOV_MAX: i32 = 1024;
+/// Reads are cut at this size, to bound the kernel buffer.
+const MAX_RW_CHUNK: usize = 0x10000;
 
 bitflags::bitflags! {
     #[derive(Debug)]
@@ -96,39 +72,100 @@ bitflags::bitflags! {
     }
 }
 
+/// Registers the syscalls of this kernel.
+pub fn init_syscalls() {
+    use ArgFmt::*;
+    let mut table = SyscallTable::new();
+    table
+        .register(
+            SYS_IOCTL,
+            Syscall::new("ioctl", &[Int, Hex, Ptr], |args| {
+                sys_ioctl(args.arg(0), args.arg(1), args.arg(2)) as _
+            }),
+        )
+        .register(
+            SYS_SET_TID_ADDRESS,
+            Syscall::new("set_tid_address", &[Ptr], |args| {
+                sys_set_tid_address(args.arg(0))
+            }),
+        )
+        .register(
+            SYS_OPENAT,
+            Syscall::new(
+                "openat",
+                &[Int, Str, Flags(axsyscall::OPEN_FLAGS), Oct],
+                |args| sys_openat(args.arg(0), args.arg(1), args.arg(2), args.arg(3)),
+            ),
+        )
+        .register(
+            SYS_CLOSE,
+            Syscall::new("close", &[Int], |args| sys_close(args.arg(0))),
+        )
+        .register(
+            SYS_READ,
+            Syscall::new("read", &[Int, Ptr, Uint], |args| {
+                sys_read(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_WRITE,
+            Syscall::new("write", &[Int, Ptr, Uint], |args| {
+                sys_write(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_WRITEV,
+            Syscall::new("writev", &[Int, Ptr, Int], |args| {
+                sys_writev(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_EXIT_GROUP,
+            Syscall::new("exit_group", &[Int], |args| sys_exit_group(args.arg(0))).noreturn(),
+        )
+        .register(
+            SYS_EXIT,
+            Syscall::new("exit", &[Int], |args| sys_exit(args.arg(0))).noreturn(),
+        )
+        .register(
+            SYS_MMAP,
+            Syscall::new(
+                "mmap",
+                &[
+                    Ptr,
+                    Uint,
+                    Flags(axsyscall::PROT_FLAGS),
+                    Flags(axsyscall::MAP_FLAGS),
+                    Int,
+                    Hex,
+                ],
+                |args| {
+                    sys_mmap(
+                        args.arg(0),
+                        args.arg(1),
+                        args.arg(2),
+                        args.arg(3),
+                        args.arg(4),
+                        args.arg(5),
+                    )
+                },
+            ),
+        );
+    axsyscall::init(table);
+}
+
 #[register_trap_handler(SYSCALL)]
 fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
     ax_println!("handle_syscall [{}] ...", syscall_num);
-    let ret = match syscall_num {
-         SYS_IOCTL => sys_ioctl(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _) as _,
-        SYS_SET_TID_ADDRESS => sys_set_tid_address(tf.arg0() as _),
-        SYS_OPENAT => sys_openat(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _, tf.arg3() as _),
-        SYS_CLOSE => sys_close(tf.arg0() as _),
-        SYS_READ => sys_read(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITE => sys_write(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITEV => sys_writev(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_EXIT_GROUP => {
-            ax_println!("[SYS_EXIT_GROUP]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        SYS_EXIT => {
-            ax_println!("[SYS_EXIT]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        SYS_MMAP => sys_mmap(
-            tf.arg0() as _,
-            tf.arg1() as _,
-            tf.arg2() as _,
-            tf.arg3() as _,
-            tf.arg4() as _,
-            tf.arg5() as _,
-        ),
-        _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
-            -LinuxError::ENOSYS.code() as _
-        }
-    };
-    ret
+    axsyscall::dispatch(tf, syscall_num)
+}
+
+fn sys_exit_group(exit_code: i32) -> ! {
+    axtask::exit(exit_code)
+}
+
+fn sys_exit(exit_code: i32) -> ! {
+    axtask::exit(exit_code)
 }
 
 #[allow(unused_variables)]
@@ -143,34 +180,68 @@ fn sys_mmap(
     unimplemented!("no sys_mmap!");
 }
 
-fn sys_openat(dfd: c_int, fname: *const c_char, flags: c_int, mode: api::ctypes::mode_t) -> isize {
-    assert_eq!(dfd, AT_FDCWD);
-    api::sys_open(fname, flags, mode) as isize
+fn sys_openat(dfd: c_int, fname: UserPtr<c_char>, flags: c_int, mode: api::ctypes::mode_t) -> isize {
+    syscall_body!(sys_openat, {
+        let path = CString::new(fname.read_str(PATH_MAX)?).map_err(|_| LinuxError::EINVAL)?;
+        Ok(api::sys_openat(dfd, path.as_ptr(), flags, mode))
+    })
 }
 
 fn sys_close(fd: i32) -> isize {
     api::sys_close(fd) as isize
 }
 
-fn sys_read(fd: i32, buf: *mut c_void, count: usize) -> isize {
-    api::sys_read(fd, buf, count)
+/// Reads at most `count` bytes into the user buffer `buf`. Large reads are
+/// cut short, which the caller must handle anyway.
+fn sys_read(fd: i32, buf: UserPtr<u8>, count: usize) -> isize {
+    syscall_body!(sys_read, {
+        let mut kbuf = vec![0u8; count.min(MAX_RW_CHUNK)];
+        let n = api::sys_read(fd, kbuf.as_mut_ptr() as _, kbuf.len());
+        if n < 0 {
+            return Ok(n);
+        }
+        UserSlice::new(buf.addr(), count).write(&kbuf[..n as usize])?;
+        Ok(n)
+    })
 }
 
-fn sys_write(fd: i32, buf: *const c_void, count: usize) -> isize {
-    api::sys_write(fd, buf, count)
+fn sys_write(fd: i32, buf: UserPtr<u8>, count: usize) -> isize {
+    syscall_body!(sys_write, {
+        let kbuf = UserSlice::new(buf.addr(), count).read_to_vec()?;
+        Ok(api::sys_write(fd, kbuf.as_ptr() as _, kbuf.len()))
+    })
 }
 
-fn sys_writev(fd: i32, iov: *const api::ctypes::iovec, iocnt: i32) -> isize {
-    unsafe { api::sys_writev(fd, iov, iocnt) }
+/// Writes the user buffers described by `iov` in order, stopping at the first
+/// short write.
+fn sys_writev(fd: i32, iov: UserPtr<api::ctypes::iovec>, iocnt: i32) -> isize {
+    syscall_body!(sys_writev, {
+        if !(0..=IOV_MAX).contains(&iocnt) {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut total = 0;
+        for iov in iov.read_array(iocnt as usize)? {
+            let kbuf = UserSlice::new(iov.iov_base as usize, iov.iov_len).read_to_vec()?;
+            let n = api::sys_write(fd, kbuf.as_ptr() as _, kbuf.len());
+            if n < 0 {
+                return if total == 0 { Ok(n) } else { Ok(total) };
+            }
+            total += n;
+            if (n as usize) < kbuf.len() {
+                break;
+            }
+        }
+        Ok(total)
+    })
 }
 
-fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
+fn sys_set_tid_address(tid_ptd: UserPtr<i32>) -> isize {
     let curr = current();
-    curr.task_ext().set_clear_child_tid(tid_ptd as _);
+    curr.task_ext().set_clear_child_tid(tid_ptd.addr() as _);
     curr.id().as_u64() as isize
 }
 
-fn sys_ioctl(_fd: i32, _op: usize, _argp: *mut c_void) -> i32 {
+fn sys_ioctl(_fd: i32, _op: usize, _argp: UserPtr<c_void>) -> i32 {
     ax_println!("Ignore SYS_IOCTL");
     0
 }
@@ -48,6 +48,11 @@ impl TaskExt {
 
 axtask::def_task_ext!(TaskExt);
 
+/// Returns the address space of the current user task.
+pub fn current_aspace() -> Arc<Mutex<AddrSpace>> {
+    axtask::current().task_ext().aspace.clone()
+}
+
 pub fn spawn_user_task(aspace: Arc<Mutex<AddrSpace>>, uctx: UspaceContext) -> AxTaskRef {
     let mut task = TaskInner::new(
         || {
@@ -0,0 +1,121 @@
+//! Credentials that file permissions are checked against.
+//!
+//! The kernel keeps the credentials of its tasks, and provides those of the
+//! current task through [`set_current_cred_fn`]. Tasks without credentials
+//! (e.g., kernel tasks) act as root.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use cap_access::Cap;
+use lazyinit::LazyInit;
+
+use crate::fops::FilePerm;
+
+/// The user and group IDs of a task.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cred {
+    /// The real user ID.
+    pub uid: u32,
+    /// The effective user ID, which file permissions are checked against.
+    pub euid: u32,
+    /// The saved set-user-ID.
+    pub suid: u32,
+    /// The real group ID.
+    pub gid: u32,
+    /// The effective group ID.
+    pub egid: u32,
+    /// The saved set-group-ID.
+    pub sgid: u32,
+    /// The supplementary group IDs.
+    pub groups: Vec<u32>,
+}
+
+impl Cred {
+    /// The credentials of root, with all IDs 0.
+    pub const fn root() -> Self {
+        Self {
+            uid: 0,
+            euid: 0,
+            suid: 0,
+            gid: 0,
+            egid: 0,
+            sgid: 0,
+            groups: Vec::new(),
+        }
+    }
+
+    /// Whether the effective user is root, which bypasses permission checks.
+    pub fn is_privileged(&self) -> bool {
+        self.euid == 0
+    }
+
+    /// Whether the effective group or a supplementary group is `gid`.
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.egid == gid || self.groups.contains(&gid)
+    }
+
+    /// The credentials with the effective IDs replaced by the real ones, which
+    /// `access` checks against.
+    pub fn with_real_ids(&self) -> Self {
+        Self {
+            euid: self.uid,
+            egid: self.gid,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the rights on a file with the permission bits `perm`, owned by
+    /// `uid` and `gid`.
+    ///
+    /// Only one class of the bits applies: the owner's, or else the group's,
+    /// or else the others'. Root can read and write anything, and execute
+    /// (search) directories and files that anyone can execute.
+    pub fn access_cap(&self, perm: FilePerm, uid: u32, gid: u32, is_dir: bool) -> Cap {
+        let bits = perm.bits();
+        if self.is_privileged() {
+            let mut cap = Cap::READ | Cap::WRITE;
+            if is_dir || bits & 0o111 != 0 {
+                cap |= Cap::EXECUTE;
+            }
+            return cap;
+        }
+        let class = if self.euid == uid {
+            bits >> 6
+        } else if self.in_group(gid) {
+            bits >> 3
+        } else {
+            bits
+        };
+        let mut cap = Cap::empty();
+        if class & 0o4 != 0 {
+            cap |= Cap::READ;
+        }
+        if class & 0o2 != 0 {
+            cap |= Cap::WRITE;
+        }
+        if class & 0o1 != 0 {
+            cap |= Cap::EXECUTE;
+        }
+        cap
+    }
+}
+
+static CURRENT_CRED_FN: LazyInit<fn() -> Option<Arc<Cred>>> = LazyInit::new();
+
+/// Registers the function that returns the credentials of the current task,
+/// usually from its task extension.
+///
+/// If the function is not registered or returns `None`, the current task
+/// acts as root.
+pub fn set_current_cred_fn(f: fn() -> Option<Arc<Cred>>) {
+    CURRENT_CRED_FN.init_once(f);
+}
+
+/// Returns the credentials of the current task.
+pub fn current_cred() -> Arc<Cred> {
+    CURRENT_CRED_FN
+        .get()
+        .and_then(|f| f())
+        .unwrap_or_else(|| Arc::new(Cred::root()))
+}
@@ -1,11 +1,14 @@
 //! Low-level filesystem operations.
 
+use alloc::{string::String, vec};
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axfs_vfs::{VfsError, VfsNodeRef};
 use axio::SeekFrom;
-use cap_access::{Cap, WithCap};
+use cap_access::WithCap;
 use core::fmt;
 
+use crate::cred::{current_cred, Cred};
+
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
 #[cfg(feature = "myfs")]
@@ -19,6 +22,12 @@ pub type DirEntry = axfs_vfs::VfsDirEntry;
 pub type FileAttr = axfs_vfs::VfsNodeAttr;
 /// Alias of [`axfs_vfs::VfsNodePerm`].
 pub type FilePerm = axfs_vfs::VfsNodePerm;
+/// Alias of [`axfs_vfs::VfsNodeRef`].
+pub type NodeRef = axfs_vfs::VfsNodeRef;
+
+/// The access rights of an opened file, and the ones checked against the
+/// permission bits.
+pub use cap_access::Cap;
 
 /// An opened file object, with open permissions and a cursor.
 pub struct File {
@@ -46,7 +55,7 @@ pub struct OpenOptions {
     create_new: bool,
     // system-specific
     _custom_flags: i32,
-    _mode: u32,
+    mode: u32,
 }
 
 impl OpenOptions {
@@ -62,7 +71,7 @@ impl OpenOptions {
             create_new: false,
             // system-specific
             _custom_flags: 0,
-            _mode: 0o666,
+            mode: 0o666,
         }
     }
     /// Sets the option for read access.
@@ -89,6 +98,11 @@ impl OpenOptions {
     pub fn create_new(&mut self, create_new: bool) {
         self.create_new = create_new;
     }
+    /// Sets the permission bits of a file that is created, `0o666` by
+    /// default.
+    pub fn mode(&mut self, mode: u32) {
+        self.mode = mode;
+    }
 
     const fn is_valid(&self) -> bool {
         if !self.read && !self.write && !self.append {
@@ -123,6 +137,7 @@ impl File {
         }
 
         let node_option = crate::root::lookup(dir, path);
+        let mut created = false;
         let node = if opts.create || opts.create_new {
             match node_option {
                 Ok(node) => {
@@ -133,7 +148,11 @@ impl File {
                     node
                 }
                 // not exists, create new
-                Err(VfsError::NotFound) => crate::root::create_file(dir, path)?,
+                Err(VfsError::NotFound) => {
+                    created = true;
+                    let perm = FilePerm::from_bits_truncate(opts.mode as _);
+                    crate::root::create_file(dir, path, perm)?
+                }
                 Err(e) => return Err(e),
             }
         } else {
@@ -147,8 +166,9 @@ impl File {
         {
             return ax_err!(IsADirectory);
         }
+        // The creator can open the new file whatever its permission bits are.
         let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
+        if !created && !crate::root::access_cap(&node, &current_cred())?.contains(access_cap) {
             return ax_err!(PermissionDenied);
         }
 
@@ -246,6 +266,34 @@ impl File {
     pub fn get_attr(&self) -> AxResult<FileAttr> {
         self.access_node(Cap::empty())?.get_attr()
     }
+
+    /// Gets the owner of the file as `(uid, gid)`.
+    pub fn owner(&self) -> AxResult<(u32, u32)> {
+        Ok(crate::root::owner_of(self.access_node(Cap::empty())?))
+    }
+
+    /// Changes the permission bits of the file.
+    pub fn set_perm(&self, perm: FilePerm) -> AxResult {
+        crate::root::set_perm_of(self.access_node(Cap::empty())?, perm)
+    }
+
+    /// Changes the owner of the file.
+    pub fn set_owner(&self, uid: u32, gid: u32) -> AxResult {
+        crate::root::set_owner_of(self.access_node(Cap::empty())?, uid, gid)
+    }
+
+    /// Returns the underlying VFS node, for mapping the file into memory.
+    ///
+    /// The file must have been opened for reading, and also for writing if
+    /// `writable` is set (i.e., for a shared mapping with write permission).
+    pub fn mmap_node(&self, writable: bool) -> AxResult<VfsNodeRef> {
+        let cap = if writable {
+            Cap::READ | Cap::WRITE
+        } else {
+            Cap::READ
+        };
+        self.access_node(cap).cloned()
+    }
 }
 
 impl Directory {
@@ -267,14 +315,18 @@ impl Directory {
         if !attr.is_dir() {
             return ax_err!(NotADirectory);
         }
+        let cap = crate::root::access_cap(&node, &current_cred())?;
         let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
+        if !cap.contains(access_cap) {
             return ax_err!(PermissionDenied);
         }
+        // Resolving paths relative to the directory (the `*_at` methods)
+        // needs the search permission, which is not an open option.
+        let search_cap = cap & Cap::EXECUTE;
 
         node.open()?;
         Ok(Self {
-            node: WithCap::new(node, access_cap),
+            node: WithCap::new(node, access_cap | search_cap),
             entry_idx: 0,
         })
     }
@@ -305,14 +357,72 @@ impl Directory {
         File::_open_at(self.access_at(path)?, path, opts)
     }
 
+    /// Gets the directory attributes.
+    pub fn get_attr(&self) -> AxResult<FileAttr> {
+        self.access_node(Cap::empty())?.get_attr()
+    }
+
+    /// Gets the attributes of the file at the path relative to this
+    /// directory, without opening it.
+    pub fn get_attr_at(&self, path: &str) -> AxResult<FileAttr> {
+        crate::root::lookup(self.access_at(path)?, path)?.get_attr()
+    }
+
+    /// Gets the owner of the directory as `(uid, gid)`.
+    pub fn owner(&self) -> AxResult<(u32, u32)> {
+        Ok(crate::root::owner_of(self.access_node(Cap::empty())?))
+    }
+
+    /// Changes the permission bits of the directory.
+    pub fn set_perm(&self, perm: FilePerm) -> AxResult {
+        crate::root::set_perm_of(self.access_node(Cap::empty())?, perm)
+    }
+
+    /// Changes the owner of the directory.
+    pub fn set_owner(&self, uid: u32, gid: u32) -> AxResult {
+        crate::root::set_owner_of(self.access_node(Cap::empty())?, uid, gid)
+    }
+
+    /// Gets the owner of the file at the path relative to this directory as
+    /// `(uid, gid)`.
+    pub fn owner_at(&self, path: &str) -> AxResult<(u32, u32)> {
+        let node = crate::root::lookup(self.access_at(path)?, path)?;
+        Ok(crate::root::owner_of(&node))
+    }
+
+    /// Changes the permission bits of the file at the path relative to this
+    /// directory.
+    pub fn set_perm_at(&self, path: &str, perm: FilePerm) -> AxResult {
+        let node = crate::root::lookup(self.access_at(path)?, path)?;
+        crate::root::set_perm_of(&node, perm)
+    }
+
+    /// Changes the owner of the file at the path relative to this directory.
+    pub fn set_owner_at(&self, path: &str, uid: u32, gid: u32) -> AxResult {
+        let node = crate::root::lookup(self.access_at(path)?, path)?;
+        crate::root::set_owner_of(&node, uid, gid)
+    }
+
+    /// Checks whether the file at the path relative to this directory can be
+    /// accessed with `cap` by `cred`.
+    pub fn check_access_at(&self, path: &str, cap: Cap, cred: &Cred) -> AxResult {
+        check_access_with(&crate::root::lookup(self.access_at(path)?, path)?, cap, cred)
+    }
+
+    /// Reads the target of the symbolic link at the path relative to this
+    /// directory.
+    pub fn read_link_at(&self, path: &str) -> AxResult<String> {
+        read_link_of(&crate::root::lookup(self.access_at(path)?, path)?)
+    }
+
     /// Creates an empty file at the path relative to this directory.
     pub fn create_file(&self, path: &str) -> AxResult<VfsNodeRef> {
-        crate::root::create_file(self.access_at(path)?, path)
+        crate::root::create_file(self.access_at(path)?, path, FilePerm::default_file())
     }
 
     /// Creates an empty directory at the path relative to this directory.
     pub fn create_dir(&self, path: &str) -> AxResult {
-        crate::root::create_dir(self.access_at(path)?, path)
+        crate::root::create_dir(self.access_at(path)?, path, FilePerm::default_dir())
     }
 
     /// Removes a file at the path relative to this directory.
@@ -347,6 +457,64 @@ impl Directory {
     }
 }
 
+/// Gets the attributes of the file at the path relative to the current
+/// directory, without opening it.
+pub fn get_attr(path: &str) -> AxResult<FileAttr> {
+    crate::root::lookup(None, path)?.get_attr()
+}
+
+/// Gets the owner of the file at the path relative to the current directory
+/// as `(uid, gid)`.
+pub fn owner(path: &str) -> AxResult<(u32, u32)> {
+    Ok(crate::root::owner_of(&crate::root::lookup(None, path)?))
+}
+
+/// Changes the permission bits of the file at the path relative to the
+/// current directory.
+pub fn set_perm(path: &str, perm: FilePerm) -> AxResult {
+    crate::root::set_perm_of(&crate::root::lookup(None, path)?, perm)
+}
+
+/// Changes the owner of the file at the path relative to the current
+/// directory.
+pub fn set_owner(path: &str, uid: u32, gid: u32) -> AxResult {
+    crate::root::set_owner_of(&crate::root::lookup(None, path)?, uid, gid)
+}
+
+/// Checks whether the file at the path relative to the current directory can
+/// be accessed with `cap` by `cred`.
+///
+/// `execve` checks with the credentials of the current task, while `access`
+/// checks with its real user and group IDs ([`Cred::with_real_ids`]).
+pub fn check_access(path: &str, cap: Cap, cred: &Cred) -> AxResult {
+    check_access_with(&crate::root::lookup(None, path)?, cap, cred)
+}
+
+/// Reads the target of the symbolic link at the path relative to the current
+/// directory. Fails with `InvalidInput` if it is not a symbolic link.
+pub fn read_link(path: &str) -> AxResult<String> {
+    read_link_of(&crate::root::lookup(None, path)?)
+}
+
+fn read_link_of(node: &VfsNodeRef) -> AxResult<String> {
+    let attr = node.get_attr()?;
+    if !attr.file_type().is_symlink() {
+        return ax_err!(InvalidInput);
+    }
+    let mut buf = vec![0; attr.size() as usize];
+    let len = node.read_at(0, &mut buf)?;
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| AxError::InvalidData)
+}
+
+fn check_access_with(node: &VfsNodeRef, cap: Cap, cred: &Cred) -> AxResult {
+    if crate::root::access_cap(node, cred)?.contains(cap) {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
+
 impl Drop for File {
     fn drop(&mut self) {
         unsafe { self.node.access_unchecked().release().ok() };
@@ -396,17 +564,3 @@ impl From<&OpenOptions> for Cap {
         cap
     }
 }
-
-fn perm_to_cap(perm: FilePerm) -> Cap {
-    let mut cap = Cap::empty();
-    if perm.owner_readable() {
-        cap |= Cap::READ;
-    }
-    if perm.owner_writable() {
-        cap |= Cap::WRITE;
-    }
-    if perm.owner_executable() {
-        cap |= Cap::EXECUTE;
-    }
-    cap
-}
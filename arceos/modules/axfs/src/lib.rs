@@ -10,6 +10,8 @@
 //!    **enabled** by default.
 //! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
 //!    **enabled** by default.
+//! - `procfs`: Mount the proc filesystem, whose files are generated from the
+//!    kernel state, on `/proc`. This feature is **enabled** by default.
 //! - `myfs`: Allow users to define their custom filesystems to override the
 //!    default. In this case, [`MyFileSystemIf`] is required to be implemented
 //!    to create and initialize other filesystems. This feature is **disabled** by
@@ -32,7 +34,10 @@ mod mounts;
 mod root;
 
 pub mod api;
+pub mod cred;
 pub mod fops;
+#[cfg(feature = "procfs")]
+pub mod procfs;
 
 use axdriver::{prelude::*, AxDeviceContainer};
 
@@ -0,0 +1,189 @@
+//! The proc filesystem, mounted on `/proc`.
+//!
+//! Its files are not stored anywhere, but generated from the live kernel
+//! state whenever they are read: memory usage from the global allocator, the
+//! uptime, CPUs, interrupt counts and mount points, and a directory for each
+//! process with `stat`, `status`, `maps`, `cmdline`, `exe` and `fd/`. The
+//! processes are provided by the kernel through [`set_process_source`].
+//!
+//! All files are read-only.
+
+mod node;
+mod process;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use axfs_vfs::{VfsNodeOps, VfsNodeRef, VfsOps, VfsResult};
+
+use self::node::{ProcDir, ProcFile};
+
+pub use self::process::{set_process_source, MemArea, ProcessInfo, ProcessSource, ProcessState};
+
+/// The proc filesystem that implements [`axfs_vfs::VfsOps`].
+pub struct ProcFileSystem {
+    root: Arc<ProcDir>,
+}
+
+impl ProcFileSystem {
+    /// Creates a new instance.
+    pub fn new() -> Self {
+        Self {
+            root: ProcDir::new(None, root_entries),
+        }
+    }
+}
+
+impl Default for ProcFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsOps for ProcFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        self.root.set_parent(mount_point.parent());
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+/// The entries of `/proc`.
+fn root_entries(this: &VfsNodeRef) -> Vec<(String, VfsNodeRef)> {
+    let mut entries: Vec<(String, VfsNodeRef)> = alloc::vec![
+        ("cpuinfo".into(), ProcFile::text(cpuinfo)),
+        ("meminfo".into(), ProcFile::text(meminfo)),
+        ("mounts".into(), ProcFile::text(mounts)),
+        ("sys".into(), sys_dir(this)),
+        ("uptime".into(), ProcFile::text(uptime)),
+    ];
+    #[cfg(feature = "irq")]
+    entries.push(("interrupts".into(), ProcFile::text(interrupts)));
+    if let Some(pid) = process::current_pid() {
+        entries.push(("self".into(), process::pid_dir(this, pid)));
+    }
+    for pid in process::pids() {
+        entries.push((pid.to_string(), process::pid_dir(this, pid)));
+    }
+    entries
+}
+
+/// `/proc/sys`, with the few tunables that programs commonly read. They
+/// cannot be changed.
+fn sys_dir(parent: &VfsNodeRef) -> VfsNodeRef {
+    ProcDir::new(Some(parent.clone()), |this| {
+        let net = ProcDir::new(Some(this.clone()), |this| {
+            let core = ProcDir::new(Some(this.clone()), |_| {
+                alloc::vec![("somaxconn".into(), ProcFile::text(|| "4096\n".into()) as _)]
+            });
+            alloc::vec![("core".into(), core as _)]
+        });
+        let vm = ProcDir::new(Some(this.clone()), |_| {
+            alloc::vec![(
+                "overcommit_memory".into(),
+                ProcFile::text(|| "0\n".into()) as _
+            )]
+        });
+        alloc::vec![("net".into(), net as _), ("vm".into(), vm as _)]
+    })
+}
+
+/// `/proc/cpuinfo`.
+fn cpuinfo() -> String {
+    let mut buf = String::new();
+    for cpu_id in 0..axconfig::SMP {
+        writeln!(buf, "processor\t: {}", cpu_id).unwrap();
+        writeln!(
+            buf,
+            "model name\t: {} ({})\n",
+            axconfig::ARCH,
+            axconfig::PLATFORM
+        )
+        .unwrap();
+    }
+    buf
+}
+
+/// `/proc/meminfo`.
+///
+/// All memory comes from the global allocator: its free pages and the free
+/// bytes of its heap are free, and the heap is reported as unreclaimable
+/// slab. There is no page cache or swap.
+fn meminfo() -> String {
+    const PAGE_SIZE: usize = 0x1000;
+    let allocator = axalloc::global_allocator();
+    let total = (allocator.used_pages() + allocator.available_pages()) * PAGE_SIZE;
+    let free = allocator.available_pages() * PAGE_SIZE + allocator.available_bytes();
+    let slab = allocator.used_bytes();
+    let mut buf = String::new();
+    for (name, bytes) in [
+        ("MemTotal", total),
+        ("MemFree", free),
+        ("MemAvailable", free),
+        ("Buffers", 0),
+        ("Cached", 0),
+        ("SwapCached", 0),
+        ("Shmem", 0),
+        ("Slab", slab),
+        ("SReclaimable", 0),
+        ("SUnreclaim", slab),
+        ("SwapTotal", 0),
+        ("SwapFree", 0),
+    ] {
+        writeln!(buf, "{:<16}{:>8} kB", format!("{}:", name), bytes / 1024).unwrap();
+    }
+    buf
+}
+
+/// `/proc/mounts`.
+fn mounts() -> String {
+    let mut buf = String::new();
+    for (path, fstype) in crate::root::mounts() {
+        writeln!(buf, "{} {} {} rw 0 0", fstype, path, fstype).unwrap();
+    }
+    buf
+}
+
+/// `/proc/uptime`: the seconds since boot, and the idle time, which is not
+/// tracked.
+fn uptime() -> String {
+    let uptime = axhal::time::monotonic_time();
+    format!(
+        "{}.{:02} 0.00\n",
+        uptime.as_secs(),
+        uptime.subsec_millis() / 10
+    )
+}
+
+/// `/proc/interrupts`: how many times each IRQ has occurred on each CPU,
+/// leaving out the IRQs that never have.
+#[cfg(feature = "irq")]
+fn interrupts() -> String {
+    use axhal::irq::{irq_count, MAX_IRQ_COUNT};
+
+    let mut buf = String::from("    ");
+    for cpu_id in 0..axconfig::SMP {
+        write!(buf, "{:>11}", format!("CPU{}", cpu_id)).unwrap();
+    }
+    buf.push('\n');
+    for irq_num in 0..MAX_IRQ_COUNT {
+        let counts: Vec<usize> = (0..axconfig::SMP)
+            .map(|cpu_id| irq_count(cpu_id, irq_num))
+            .collect();
+        if counts.iter().all(|&count| count == 0) {
+            continue;
+        }
+        write!(buf, "{:>3}:", irq_num).unwrap();
+        for count in counts {
+            write!(buf, "{:>11}", count).unwrap();
+        }
+        buf.push('\n');
+    }
+    buf
+}
@@ -0,0 +1,164 @@
+use alloc::{boxed::Box, string::String, sync::Arc, sync::Weak, vec::Vec};
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef};
+use axfs_vfs::{VfsNodeType, VfsResult};
+use axsync::Mutex;
+
+type EntriesFn = dyn Fn(&VfsNodeRef) -> Vec<(String, VfsNodeRef)> + Send + Sync;
+type ContentFn = dyn Fn() -> VfsResult<Vec<u8>> + Send + Sync;
+
+/// A directory whose entries are generated whenever it is looked up or read.
+///
+/// The entries are created with the directory itself as their parent, and
+/// dropped once they are no longer used.
+pub struct ProcDir {
+    this: Weak<ProcDir>,
+    parent: Mutex<Option<VfsNodeRef>>,
+    entries: Box<EntriesFn>,
+}
+
+impl ProcDir {
+    pub fn new(
+        parent: Option<VfsNodeRef>,
+        entries: impl Fn(&VfsNodeRef) -> Vec<(String, VfsNodeRef)> + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            parent: Mutex::new(parent),
+            entries: Box::new(entries),
+        })
+    }
+
+    pub fn set_parent(&self, parent: Option<VfsNodeRef>) {
+        *self.parent.lock() = parent;
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeRef)> {
+        let this: VfsNodeRef = self.this.upgrade().unwrap();
+        (self.entries)(&this)
+    }
+}
+
+impl VfsNodeOps for ProcDir {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = VfsNodePerm::from_bits_truncate(0o555);
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::Dir, 0, 0))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.lock().clone()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => self.clone() as VfsNodeRef,
+            ".." => self.parent().ok_or(VfsError::NotFound)?,
+            _ => self
+                .entries()
+                .into_iter()
+                .find(|(entry, _)| entry == name)
+                .map(|(_, node)| node)
+                .ok_or(VfsError::NotFound)?,
+        };
+        match rest {
+            Some(rest) => node.lookup(rest),
+            None => Ok(node),
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = self.entries();
+        let mut entries = entries.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => match entries.next() {
+                    Some((name, node)) => {
+                        *ent = VfsDirEntry::new(name, node.get_attr()?.file_type());
+                    }
+                    None => return Ok(i),
+                },
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+/// A read-only file whose content is generated whenever it is read.
+///
+/// As on Linux, the size is 0, and a reader that reads in several calls may
+/// see the content change in between.
+pub struct ProcFile {
+    content: Box<ContentFn>,
+}
+
+impl ProcFile {
+    pub fn new(content: impl Fn() -> VfsResult<Vec<u8>> + Send + Sync + 'static) -> Arc<Self> {
+        Arc::new(Self {
+            content: Box::new(content),
+        })
+    }
+
+    /// A file with the text generated by `text`.
+    pub fn text(text: impl Fn() -> String + Send + Sync + 'static) -> Arc<Self> {
+        Self::new(move || Ok(text().into_bytes()))
+    }
+}
+
+impl VfsNodeOps for ProcFile {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = VfsNodePerm::from_bits_truncate(0o444);
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::File, 0, 0))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.content)()?;
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(start + buf.len());
+        buf[..end - start].copy_from_slice(&content[start..end]);
+        Ok(end - start)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// A symbolic link to a fixed target, which reads as the target.
+///
+/// Path lookups do not follow it.
+pub struct ProcLink {
+    target: String,
+}
+
+impl ProcLink {
+    pub fn new(target: String) -> Arc<Self> {
+        Arc::new(Self { target })
+    }
+}
+
+impl VfsNodeOps for ProcLink {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = VfsNodePerm::from_bits_truncate(0o777);
+        let size = self.target.len() as u64;
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::SymLink, size, 0))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let target = self.target.as_bytes();
+        let start = target.len().min(offset as usize);
+        let end = target.len().min(start + buf.len());
+        buf[..end - start].copy_from_slice(&target[start..end]);
+        Ok(end - start)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
@@ -0,0 +1,274 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::time::Duration;
+
+use axfs_vfs::{VfsError, VfsNodeRef};
+use lazyinit::LazyInit;
+
+use super::node::{ProcDir, ProcFile, ProcLink};
+use crate::cred::Cred;
+
+/// The clock ticks per second that times in `/proc/<pid>/stat` count, which
+/// `sysconf(_SC_CLK_TCK)` returns.
+const USER_HZ: u128 = 100;
+
+/// The state of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    /// Some thread is running or ready to run.
+    Running,
+    /// All threads are waiting for something.
+    Sleeping,
+    /// All threads have exited, and the parent has not reaped the process.
+    Zombie,
+}
+
+impl ProcessState {
+    /// The letter in `/proc/<pid>/stat`.
+    const fn code(self) -> char {
+        match self {
+            Self::Running => 'R',
+            Self::Sleeping => 'S',
+            Self::Zombie => 'Z',
+        }
+    }
+
+    /// The description in `/proc/<pid>/status`.
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Running => "R (running)",
+            Self::Sleeping => "S (sleeping)",
+            Self::Zombie => "Z (zombie)",
+        }
+    }
+}
+
+/// A mapped area of the address space of a process, a line of
+/// `/proc/<pid>/maps`.
+#[derive(Debug, Clone)]
+pub struct MemArea {
+    pub start: usize,
+    pub end: usize,
+    pub readable: bool,
+    pub writable: bool,
+    pub executable: bool,
+    /// Whether the area is shared with other processes rather than private.
+    pub shared: bool,
+    /// What is mapped, e.g., `[heap]`, or empty for anonymous memory.
+    pub name: String,
+}
+
+/// A snapshot of a process, from which the files of `/proc/<pid>` are
+/// generated.
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    pub pid: usize,
+    /// The process ID of the parent, or 0 if there is none.
+    pub ppid: usize,
+    pub pgid: usize,
+    pub state: ProcessState,
+    /// The path of the program.
+    pub exe: String,
+    /// The arguments of the program, starting with its name.
+    pub args: Vec<String>,
+    /// The number of threads.
+    pub threads: usize,
+    pub cred: Arc<Cred>,
+    /// When the process was created, since boot.
+    pub start_time: Duration,
+    /// The mapped areas, in ascending order of addresses.
+    pub areas: Vec<MemArea>,
+    /// The open file descriptors and what each refers to, such as the path
+    /// of a file.
+    pub fds: Vec<(usize, String)>,
+}
+
+impl ProcessInfo {
+    /// The name of the program, cut to 15 characters as the `comm` of Linux.
+    fn name(&self) -> &str {
+        let name = self.exe.rsplit('/').next().unwrap_or_default();
+        match name.char_indices().nth(15) {
+            Some((end, _)) => &name[..end],
+            None => name,
+        }
+    }
+
+    /// The size of the address space in bytes.
+    fn vm_size(&self) -> usize {
+        self.areas.iter().map(|area| area.end - area.start).sum()
+    }
+}
+
+/// Where the processes of `/proc` come from.
+///
+/// Processes belong to the kernel rather than the filesystem, so the kernel
+/// provides them with [`set_process_source`].
+pub struct ProcessSource {
+    /// Returns the process IDs of all processes, in ascending order.
+    pub pids: fn() -> Vec<usize>,
+    /// Returns the process ID of the current process that `/proc/self`
+    /// refers to, or `None` for kernel tasks.
+    pub current_pid: fn() -> Option<usize>,
+    /// Returns a snapshot of the process with the given process ID, or
+    /// `None` if there is none.
+    pub info: fn(usize) -> Option<ProcessInfo>,
+}
+
+static PROCESS_SOURCE: LazyInit<ProcessSource> = LazyInit::new();
+
+/// Registers where the processes of `/proc` come from. Without it, `/proc`
+/// has no process directories.
+pub fn set_process_source(source: ProcessSource) {
+    PROCESS_SOURCE.init_once(source);
+}
+
+pub(super) fn pids() -> Vec<usize> {
+    PROCESS_SOURCE
+        .get()
+        .map_or_else(Vec::new, |source| (source.pids)())
+}
+
+pub(super) fn current_pid() -> Option<usize> {
+    PROCESS_SOURCE
+        .get()
+        .and_then(|source| (source.current_pid)())
+}
+
+fn process_info(pid: usize) -> Option<ProcessInfo> {
+    PROCESS_SOURCE.get().and_then(|source| (source.info)(pid))
+}
+
+/// Generates a file of `/proc/<pid>` from the snapshot of the process, or
+/// fails with `NotFound` if it has been reaped.
+fn pid_file(pid: usize, gen: fn(&ProcessInfo) -> Vec<u8>) -> VfsNodeRef {
+    ProcFile::new(move || {
+        process_info(pid)
+            .map(|info| gen(&info))
+            .ok_or(VfsError::NotFound)
+    })
+}
+
+/// The directory `/proc/<pid>`.
+pub(super) fn pid_dir(parent: &VfsNodeRef, pid: usize) -> VfsNodeRef {
+    ProcDir::new(Some(parent.clone()), move |this| {
+        let Some(info) = process_info(pid) else {
+            return Vec::new();
+        };
+        let fd_dir = ProcDir::new(Some(this.clone()), move |_| {
+            let fds = process_info(pid).map_or_else(Vec::new, |info| info.fds);
+            fds.into_iter()
+                .map(|(fd, target)| (fd.to_string(), ProcLink::new(target) as VfsNodeRef))
+                .collect()
+        });
+        alloc::vec![
+            ("cmdline".into(), pid_file(pid, cmdline)),
+            ("exe".into(), ProcLink::new(info.exe) as _),
+            ("fd".into(), fd_dir as _),
+            ("maps".into(), pid_file(pid, maps)),
+            ("stat".into(), pid_file(pid, stat)),
+            ("status".into(), pid_file(pid, status)),
+        ]
+    })
+}
+
+/// `/proc/<pid>/cmdline`: the arguments, each terminated by a NUL.
+fn cmdline(info: &ProcessInfo) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for arg in &info.args {
+        buf.extend_from_slice(arg.as_bytes());
+        buf.push(0);
+    }
+    buf
+}
+
+/// `/proc/<pid>/stat`. Fields that are not tracked are 0.
+fn stat(info: &ProcessInfo) -> Vec<u8> {
+    let zeros = |n| " 0".repeat(n);
+    let start_time = info.start_time.as_millis() * USER_HZ / 1000;
+    format!(
+        "{} ({}) {} {} {} 0 0 -1 0{} 20 0 {} 0 {} {} 0 {}{} 17{}\n",
+        info.pid,
+        info.name(),
+        info.state.code(),
+        info.ppid,
+        info.pgid,
+        zeros(8), // page faults and times
+        info.threads,
+        start_time,
+        info.vm_size(),
+        u64::MAX, // rsslim
+        zeros(12),
+        zeros(14),
+    )
+    .into_bytes()
+}
+
+/// `/proc/<pid>/status`.
+fn status(info: &ProcessInfo) -> Vec<u8> {
+    let cred = &info.cred;
+    let mut groups = String::new();
+    for gid in &cred.groups {
+        write!(groups, "{} ", gid).unwrap();
+    }
+    format!(
+        "Name:\t{}\n\
+         State:\t{}\n\
+         Tgid:\t{}\n\
+         Pid:\t{}\n\
+         PPid:\t{}\n\
+         Uid:\t{}\t{}\t{}\t{}\n\
+         Gid:\t{}\t{}\t{}\t{}\n\
+         Groups:\t{}\n\
+         VmSize:\t{:8} kB\n\
+         Threads:\t{}\n",
+        info.name(),
+        info.state.name(),
+        info.pid,
+        info.pid,
+        info.ppid,
+        cred.uid,
+        cred.euid,
+        cred.suid,
+        cred.euid,
+        cred.gid,
+        cred.egid,
+        cred.sgid,
+        cred.egid,
+        groups,
+        info.vm_size() / 1024,
+        info.threads,
+    )
+    .into_bytes()
+}
+
+/// `/proc/<pid>/maps`. Nothing is mapped from files, so the offset, device
+/// and inode are 0.
+fn maps(info: &ProcessInfo) -> Vec<u8> {
+    // Linux pads the line to this width before the name.
+    const NAME_COLUMN: usize = 73;
+    let mut buf = String::new();
+    for area in &info.areas {
+        let line_start = buf.len();
+        write!(
+            buf,
+            "{:08x}-{:08x} {}{}{}{} 00000000 00:00 0",
+            area.start,
+            area.end,
+            if area.readable { 'r' } else { '-' },
+            if area.writable { 'w' } else { '-' },
+            if area.executable { 'x' } else { '-' },
+            if area.shared { 's' } else { 'p' },
+        )
+        .unwrap();
+        if !area.name.is_empty() {
+            let width = buf.len() - line_start;
+            buf.push_str(&" ".repeat(NAME_COLUMN.saturating_sub(width).max(1)));
+            buf.push_str(&area.name);
+        }
+        buf.push('\n');
+    }
+    buf.into_bytes()
+}
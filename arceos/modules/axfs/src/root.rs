@@ -7,8 +7,11 @@ use alloc::{string::String, sync::Arc, vec::Vec};
 use axerrno::{ax_err, AxError, AxResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
 use axsync::Mutex;
+use cap_access::Cap;
 use lazyinit::LazyInit;
 use core::any;
+use crate::cred::{current_cred, Cred};
+use crate::fops::FilePerm;
 use crate::{api::FileType, fs, mounts};
 
 static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
@@ -16,19 +19,22 @@ static CURRENT_DIR: LazyInit<Mutex<VfsNodeRef>> = LazyInit::new();
 
 struct MountPoint {
     path: &'static str,
+    /// The filesystem type shown in `/proc/mounts`.
+    fstype: &'static str,
     fs: Arc<dyn VfsOps>,
 }
 
 struct RootDirectory {
     main_fs: Arc<dyn VfsOps>,
+    main_fstype: &'static str,
     mounts: Vec<MountPoint>,
 }
 
 static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
 
 impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
-        Self { path, fs }
+    pub fn new(path: &'static str, fstype: &'static str, fs: Arc<dyn VfsOps>) -> Self {
+        Self { path, fstype, fs }
     }
 }
 
@@ -39,14 +45,20 @@ impl Drop for MountPoint {
 }
 
 impl RootDirectory {
-    pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+    pub const fn new(main_fs: Arc<dyn VfsOps>, main_fstype: &'static str) -> Self {
         Self {
             main_fs,
+            main_fstype,
             mounts: Vec::new(),
         }
     }
 
-    pub fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
+    pub fn mount(
+        &mut self,
+        path: &'static str,
+        fstype: &'static str,
+        fs: Arc<dyn VfsOps>,
+    ) -> AxResult {
         if path == "/" {
             return ax_err!(InvalidInput, "cannot mount root filesystem");
         }
@@ -59,7 +71,7 @@ impl RootDirectory {
         // create the mount point in the main filesystem if it does not exist
         self.main_fs.root_dir().create(path, FileType::Dir)?;
         fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.push(MountPoint::new(path, fs));
+        self.mounts.push(MountPoint::new(path, fstype, fs));
         //
         // log::error!("[root.rs --mount]mount path:{} ",path);
         Ok(())
@@ -182,46 +194,65 @@ pub(crate) fn init_rootfs(disk: Option<crate::dev::Disk>) {
     cfg_if::cfg_if! {
         if #[cfg(feature = "ramfs")] {// 新增：优先使用 ramfs
             let main_fs = mounts::ramfs();
+            let main_fstype = "ramfs";
             info!("Using RAMFS as main filesystem");
         }else if #[cfg(feature = "myfs")] { // override the default filesystem
             let main_fs = fs::myfs::new_myfs(disk.unwrap());
+            let main_fstype = "myfs";
         }
         else if #[cfg(feature = "fatfs")] {
             static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
             FAT_FS.init_once(Arc::new(fs::fatfs::FatFileSystem::new(disk.unwrap())));
             FAT_FS.init();
             let main_fs = FAT_FS.clone();
+            let main_fstype = "vfat";
         }
     }
 
-    let mut root_dir = RootDirectory::new(main_fs);
+    let mut root_dir = RootDirectory::new(main_fs, main_fstype);
 
     #[cfg(feature = "devfs")]
     root_dir
-        .mount("/dev", mounts::devfs())
+        .mount("/dev", "devfs", mounts::devfs())
         .expect("failed to mount devfs at /dev");
 
     #[cfg(feature = "ramfs")]
-    root_dir
-        .mount("/tmp", mounts::ramfs())
-        .expect("failed to mount ramfs at /tmp");
+    {
+        // Anyone can create files in /tmp.
+        let tmp = mounts::ramfs();
+        let tmp_root = tmp.root_dir();
+        let meta = fs::ramfs::NodeMeta::of(tmp_root.as_ref()).unwrap();
+        meta.set_perm(FilePerm::from_bits_truncate(0o777));
+        root_dir
+            .mount("/tmp", "ramfs", tmp)
+            .expect("failed to mount ramfs at /tmp");
+    }
 
-    // Mount another ramfs as procfs
     #[cfg(feature = "procfs")]
     root_dir // should not fail
-        .mount("/proc", mounts::procfs().unwrap())
+        .mount("/proc", "proc", mounts::procfs())
         .expect("fail to mount procfs at /proc");
 
     // Mount another ramfs as sysfs
     #[cfg(feature = "sysfs")]
     root_dir // should not fail
-        .mount("/sys", mounts::sysfs().unwrap())
+        .mount("/sys", "sysfs", mounts::sysfs().unwrap())
         .expect("fail to mount sysfs at /sys");
 
     ROOT_DIR.init_once(Arc::new(root_dir));
     CURRENT_DIR.init_once(Mutex::new(ROOT_DIR.clone()));
     *CURRENT_DIR_PATH.lock() = "/".into();
 }
+/// Returns the path and the filesystem type of every mount point, starting
+/// with the root.
+#[cfg(feature = "procfs")]
+pub(crate) fn mounts() -> Vec<(&'static str, &'static str)> {
+    let root_dir = ROOT_DIR.get().expect("the root filesystem is not initialized");
+    let mut mounts = alloc::vec![("/", root_dir.main_fstype)];
+    mounts.extend(root_dir.mounts.iter().map(|mp| (mp.path, mp.fstype)));
+    mounts
+}
+
 //找父目录节点 dir是起始目录 path是路径
 fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
     if path.starts_with('/') {//path是绝对路径 那么返回根目录
@@ -233,6 +264,97 @@ fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
     }
 }
 
+/// Returns the directory that contains the last component of `path`.
+fn parent_dir_of(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+    let start = parent_node_of(dir, path);
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(idx) if !path[..idx].trim_matches('/').is_empty() => Ok(start.lookup(&path[..idx])?),
+        _ => Ok(start),
+    }
+}
+
+/// Returns the owner of `node` as `(uid, gid)`. Only the RAM filesystem
+/// records owners, the nodes of other filesystems belong to root.
+pub(crate) fn owner_of(node: &VfsNodeRef) -> (u32, u32) {
+    #[cfg(feature = "ramfs")]
+    if let Some(meta) = fs::ramfs::NodeMeta::of(node.as_ref()) {
+        return meta.owner();
+    }
+    let _ = node;
+    (0, 0)
+}
+
+/// Changes the owner of `node`, if its filesystem records owners.
+pub(crate) fn set_owner_of(node: &VfsNodeRef, uid: u32, gid: u32) -> AxResult {
+    #[cfg(feature = "ramfs")]
+    if let Some(meta) = fs::ramfs::NodeMeta::of(node.as_ref()) {
+        meta.set_owner(uid, gid);
+        return Ok(());
+    }
+    let _ = (node, uid, gid);
+    ax_err!(Unsupported)
+}
+
+/// Changes the permission bits of `node`, if its filesystem records them.
+pub(crate) fn set_perm_of(node: &VfsNodeRef, perm: FilePerm) -> AxResult {
+    #[cfg(feature = "ramfs")]
+    if let Some(meta) = fs::ramfs::NodeMeta::of(node.as_ref()) {
+        meta.set_perm(perm);
+        return Ok(());
+    }
+    let _ = (node, perm);
+    ax_err!(Unsupported)
+}
+
+/// Makes the current task own the new `node`, with the permission bits
+/// `perm`. It is not an error if the filesystem does not record them.
+fn init_owner(node: &VfsNodeRef, perm: FilePerm) {
+    let cred = current_cred();
+    set_owner_of(node, cred.euid, cred.egid).ok();
+    set_perm_of(node, perm).ok();
+}
+
+/// Returns the rights of `cred` on `node`.
+pub(crate) fn access_cap(node: &VfsNodeRef, cred: &Cred) -> AxResult<Cap> {
+    let attr = node.get_attr()?;
+    let (uid, gid) = owner_of(node);
+    Ok(cred.access_cap(attr.perm(), uid, gid, attr.is_dir()))
+}
+
+/// Checks that the current task has the rights `cap` on `node`.
+fn check_access(node: &VfsNodeRef, cap: Cap) -> AxResult {
+    if access_cap(node, &current_cred())?.contains(cap) {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
+
+/// Checks that the current task can search the directories that `path` goes
+/// through, from the one it is resolved against to the parent of the last
+/// component.
+///
+/// Each directory is looked up from the start again, so that mount points
+/// are crossed as [`RootDirectory`] does.
+fn check_search(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+    let start = parent_node_of(dir, path);
+    check_access(&start, Cap::EXECUTE)?;
+    let path = path.trim_end_matches('/');
+    for (idx, _) in path.match_indices('/') {
+        let prefix = &path[..idx];
+        if prefix.trim_matches('/').is_empty() {
+            continue;
+        }
+        let node = start.clone().lookup(prefix)?;
+        if !node.get_attr()?.is_dir() {
+            return ax_err!(NotADirectory);
+        }
+        check_access(&node, Cap::EXECUTE)?;
+    }
+    Ok(())
+}
+
 pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
     if path.starts_with('/') {
         Ok(axfs_vfs::path::canonicalize(path))
@@ -246,6 +368,7 @@ pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRe
     if path.is_empty() {
         return ax_err!(NotFound);
     }
+    check_search(dir, path)?;
     let node = parent_node_of(dir, path).lookup(path)?;
     if path.ends_with('/') && !node.get_attr()?.is_dir() {
         ax_err!(NotADirectory)
@@ -254,21 +377,39 @@ pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRe
     }
 }
 
-pub(crate) fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+/// Creates a file owned by the current task with the permission bits
+/// `perm`, which needs the write and search permissions of its directory.
+pub(crate) fn create_file(
+    dir: Option<&VfsNodeRef>,
+    path: &str,
+    perm: FilePerm,
+) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     } else if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
+    check_search(dir, path)?;
+    check_access(&parent_dir_of(dir, path)?, Cap::WRITE | Cap::EXECUTE)?;
     let parent = parent_node_of(dir, path);
     parent.create(path, VfsNodeType::File)?;
-    parent.lookup(path)
+    let node = parent.lookup(path)?;
+    init_owner(&node, perm);
+    Ok(node)
 }
 
-pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+/// Creates a directory owned by the current task with the permission bits
+/// `perm`, which needs the write and search permissions of its parent.
+pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str, perm: FilePerm) -> AxResult {
     match lookup(dir, path) {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).create(path, VfsNodeType::Dir),
+        Err(AxError::NotFound) => {
+            check_access(&parent_dir_of(dir, path)?, Cap::WRITE | Cap::EXECUTE)?;
+            let parent = parent_node_of(dir, path);
+            parent.create(path, VfsNodeType::Dir)?;
+            init_owner(&parent.lookup(path)?, perm);
+            Ok(())
+        }
         Err(e) => Err(e),
     }
 }
@@ -278,9 +419,8 @@ pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     let attr = node.get_attr()?;
     if attr.is_dir() {
         ax_err!(IsADirectory)
-    } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
     } else {
+        check_access(&parent_dir_of(dir, path)?, Cap::WRITE | Cap::EXECUTE)?;
         parent_node_of(dir, path).remove(path)
     }
 }
@@ -307,9 +447,8 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     let attr = node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
-    } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
     } else {
+        check_access(&parent_dir_of(dir, path)?, Cap::WRITE | Cap::EXECUTE)?;
         parent_node_of(dir, path).remove(path)
     }
 }
@@ -333,7 +472,7 @@ pub(crate) fn set_current_dir(path: &str) -> AxResult {
     let attr = node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
-    } else if !attr.perm().owner_executable() {
+    } else if check_access(&node, Cap::EXECUTE).is_err() {
         ax_err!(PermissionDenied)
     } else {
         *CURRENT_DIR.lock() = node;
@@ -344,6 +483,11 @@ pub(crate) fn set_current_dir(path: &str) -> AxResult {
 
 //pub(crate) fn 是对当前crate公开 外部不能访问  pub fn是完全公开
 pub(crate) fn rename(old: &str, new: &str) -> AxResult {
+    // Both directories are changed.
+    for path in [old, new] {
+        check_search(None, path)?;
+        check_access(&parent_dir_of(None, path)?, Cap::WRITE | Cap::EXECUTE)?;
+    }
     if parent_node_of(None, new).lookup(new).is_ok() {
         warn!("dst file already exist, now remove it");
         remove_file(None, new)?;
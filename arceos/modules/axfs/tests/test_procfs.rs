@@ -0,0 +1,123 @@
+#![cfg(feature = "procfs")]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axfs::cred::Cred;
+use axfs::procfs::{MemArea, ProcFileSystem, ProcessInfo, ProcessSource, ProcessState};
+use axfs_vfs::{VfsError, VfsNodeRef, VfsOps};
+
+const PID: usize = 42;
+
+fn area(start: usize, end: usize, perm: &str, name: &str) -> MemArea {
+    MemArea {
+        start,
+        end,
+        readable: perm.contains('r'),
+        writable: perm.contains('w'),
+        executable: perm.contains('x'),
+        shared: perm.contains('s'),
+        name: name.into(),
+    }
+}
+
+fn process_info(pid: usize) -> Option<ProcessInfo> {
+    if pid != PID {
+        return None;
+    }
+    Some(ProcessInfo {
+        pid,
+        ppid: 1,
+        pgid: pid,
+        state: ProcessState::Sleeping,
+        exe: "/usr/bin/a-very-long-program-name".into(),
+        args: vec!["a-very-long-program-name".into(), "-v".into()],
+        threads: 2,
+        cred: Arc::new(Cred {
+            uid: 1000,
+            euid: 0,
+            suid: 0,
+            gid: 100,
+            egid: 100,
+            sgid: 100,
+            groups: vec![10, 20],
+        }),
+        start_time: Duration::from_millis(1500),
+        areas: vec![
+            area(0x40_0000, 0x40_2000, "r-xp", ""),
+            area(0x7fff_0000, 0x7fff_1000, "rw-s", "[stack]"),
+        ],
+        fds: vec![(0, "/dev/null".into()), (1, "/dev/console".into())],
+    })
+}
+
+fn read(root: &VfsNodeRef, path: &str) -> String {
+    let node = root.clone().lookup(path).unwrap();
+    let mut buf = vec![0; 4096];
+    let len = node.read_at(0, &mut buf).unwrap();
+    buf.truncate(len);
+    String::from_utf8(buf).unwrap()
+}
+
+fn test_status(root: &VfsNodeRef) {
+    assert_eq!(
+        read(root, "42/status"),
+        "Name:\ta-very-long-pro\n\
+         State:\tS (sleeping)\n\
+         Tgid:\t42\n\
+         Pid:\t42\n\
+         PPid:\t1\n\
+         Uid:\t1000\t0\t0\t0\n\
+         Gid:\t100\t100\t100\t100\n\
+         Groups:\t10 20 \n\
+         VmSize:\t      12 kB\n\
+         Threads:\t2\n"
+    );
+}
+
+fn test_stat(root: &VfsNodeRef) {
+    let stat = read(root, "42/stat");
+    assert!(stat.ends_with('\n'));
+    let fields: Vec<&str> = stat.split_whitespace().collect();
+    // As on Linux, there are 52 fields.
+    assert_eq!(fields.len(), 52);
+    assert_eq!(fields[..5], ["42", "(a-very-long-pro)", "S", "1", "42"]);
+    assert_eq!(fields[19], "2"); // num_threads
+    assert_eq!(fields[21], "150"); // starttime, in clock ticks
+    assert_eq!(fields[22], "12288"); // vsize
+}
+
+fn test_maps(root: &VfsNodeRef) {
+    let stack = "7fff0000-7fff1000 rw-s 00000000 00:00 0";
+    assert_eq!(
+        read(root, "42/maps"),
+        format!(
+            "00400000-00402000 r-xp 00000000 00:00 0\n{:<73}[stack]\n",
+            stack
+        )
+    );
+}
+
+#[test]
+fn test_procfs() {
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::procfs::set_process_source(ProcessSource {
+        pids: || vec![PID],
+        current_pid: || Some(PID),
+        info: process_info,
+    });
+    let root = ProcFileSystem::new().root_dir();
+
+    test_status(&root);
+    test_stat(&root);
+    test_maps(&root);
+    assert_eq!(read(&root, "42/cmdline"), "a-very-long-program-name\0-v\0");
+    assert_eq!(read(&root, "42/exe"), "/usr/bin/a-very-long-program-name");
+    assert_eq!(read(&root, "42/fd/1"), "/dev/console");
+    assert_eq!(read(&root, "self/status"), read(&root, "42/status"));
+    assert!(matches!(root.clone().lookup("7"), Err(VfsError::NotFound)));
+    assert!(matches!(
+        root.clone().lookup("42/fd/2"),
+        Err(VfsError::NotFound)
+    ));
+}
@@ -7,10 +7,11 @@ use std::sync::Arc;
 use axdriver::AxDeviceContainer;
 use axdriver_block::ramdisk::RamDisk;
 use axfs::api::{self as fs, File};
-use axfs::fops::{Disk, MyFileSystemIf};
+use axfs::cred::Cred;
+use axfs::fops::{self, Cap, Disk, FilePerm, MyFileSystemIf};
 use axfs_ramfs::RamFileSystem;
 use axfs_vfs::VfsOps;
-use axio::{Result, Write};
+use axio::{Error, Result, Write};
 
 struct MyFileSystemIfImpl;
 
@@ -41,6 +42,74 @@ fn create_init_files() -> Result<()> {
     Ok(())
 }
 
+/// The credentials of a user that is not root, without set-ID bits.
+fn user(uid: u32, gid: u32, groups: &[u32]) -> Cred {
+    Cred {
+        uid,
+        euid: uid,
+        suid: uid,
+        gid,
+        egid: gid,
+        sgid: gid,
+        groups: groups.to_vec(),
+    }
+}
+
+fn test_owner_permissions() -> Result<()> {
+    let fname = "/perm.txt";
+    println!("test owner permissions {:?}:", fname);
+    fs::write(fname, "Rust is cool!\n")?;
+    fops::set_owner(fname, 1000, 100)?;
+    fops::set_perm(fname, FilePerm::from_bits_truncate(0o640))?;
+    let check = |cap, cred: &Cred| fops::check_access(fname, cap, cred);
+    let denied = Err(Error::PermissionDenied);
+
+    // the owner
+    let owner = user(1000, 1000, &[]);
+    assert_eq!(check(Cap::READ | Cap::WRITE, &owner), Ok(()));
+    assert_eq!(check(Cap::EXECUTE, &owner), denied);
+
+    // the group, as the effective group or a supplementary one
+    let member = user(1001, 100, &[]);
+    assert_eq!(check(Cap::READ, &member), Ok(()));
+    assert_eq!(check(Cap::WRITE, &member), denied);
+    let supplementary = user(1002, 1002, &[100]);
+    assert_eq!(check(Cap::READ, &supplementary), Ok(()));
+    assert_eq!(check(Cap::WRITE, &supplementary), denied);
+
+    // others
+    let other = user(1003, 1003, &[]);
+    assert_eq!(check(Cap::READ, &other), denied);
+
+    // only the first class that matches applies
+    fops::set_perm(fname, FilePerm::from_bits_truncate(0o047))?;
+    let owner_in_group = user(1000, 100, &[]);
+    assert_eq!(check(Cap::READ, &owner_in_group), denied);
+    assert_eq!(check(Cap::READ, &member), Ok(()));
+    assert_eq!(check(Cap::READ | Cap::WRITE, &other), Ok(()));
+
+    // root reads and writes anything, but executes only what someone can
+    let root = Cred::root();
+    fops::set_perm(fname, FilePerm::from_bits_truncate(0o000))?;
+    assert_eq!(check(Cap::READ | Cap::WRITE, &root), Ok(()));
+    assert_eq!(check(Cap::EXECUTE, &root), denied);
+    fops::set_perm(fname, FilePerm::from_bits_truncate(0o010))?;
+    assert_eq!(check(Cap::EXECUTE, &root), Ok(()));
+
+    // the real IDs, as `access` checks against
+    let setuid = Cred {
+        euid: 1000,
+        ..user(1003, 1003, &[])
+    };
+    fops::set_perm(fname, FilePerm::from_bits_truncate(0o600))?;
+    assert_eq!(check(Cap::READ, &setuid), Ok(()));
+    assert_eq!(check(Cap::READ, &setuid.with_real_ids()), denied);
+
+    fs::remove_file(fname)?;
+    println!("test_owner_permissions() OK!");
+    Ok(())
+}
+
 #[test]
 fn test_ramfs() {
     println!("Testing ramfs ...");
@@ -53,4 +122,5 @@ fn test_ramfs() {
     }
 
     test_common::test_all();
+    test_owner_permissions().expect("test_owner_permissions() failed");
 }
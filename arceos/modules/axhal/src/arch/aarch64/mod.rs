@@ -1,5 +1,7 @@
 mod context;
 pub(crate) mod trap;
+#[cfg(feature = "uspace")]
+mod uaccess;
 
 use core::arch::asm;
 
@@ -8,6 +10,10 @@ use memory_addr::{PhysAddr, VirtAddr};
 use tock_registers::interfaces::{Readable, Writeable};
 
 pub use self::context::{FpState, TaskContext, TrapFrame};
+#[cfg(feature = "uspace")]
+pub use self::context::UspaceContext;
+#[cfg(feature = "uspace")]
+pub use self::uaccess::copy_user;
 
 /// Allows the current CPU to respond to interrupts.
 #[inline]
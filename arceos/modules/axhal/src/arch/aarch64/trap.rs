@@ -37,8 +37,26 @@ fn invalid_exception(tf: &TrapFrame, kind: TrapKind, source: TrapSource) {
 }
 
 #[no_mangle]
-fn handle_irq_exception(_tf: &TrapFrame) {
+#[cfg_attr(not(feature = "uspace"), allow(unused_variables))]
+fn handle_irq_exception(tf: &mut TrapFrame) {
     handle_trap!(IRQ, 0);
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::return_to_user(tf);
+    }
+}
+
+#[cfg(feature = "uspace")]
+fn handle_user_exception(tf: &TrapFrame, exception: crate::trap::UserException) {
+    if !handle_trap!(USER_EXCEPTION, tf, exception) {
+        panic!(
+            "Unhandled user exception {:?} @ {:#x}, ESR={:#x}:\n{:#x?}",
+            exception,
+            tf.elr,
+            ESR_EL1.get(),
+            tf
+        );
+    }
 }
 
 fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
@@ -52,6 +70,10 @@ fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
     if !matches!(iss & 0b111100, 0b0100 | 0b1100) // IFSC or DFSC bits
         || !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user)
     {
+        #[cfg(feature = "uspace")]
+        if is_user {
+            return handle_user_exception(tf, crate::trap::UserException::AccessFault);
+        }
         panic!(
             "Unhandled {} Instruction Abort @ {:#x}, fault_vaddr={:#x}, ISS={:#x} ({:?}):\n{:#x?}",
             if is_user { "EL0" } else { "EL1" },
@@ -64,7 +86,7 @@ fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
     }
 }
 
-fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
+fn handle_data_abort(tf: &mut TrapFrame, iss: u64, is_user: bool) {
     let wnr = (iss & (1 << 6)) != 0; // WnR: Write not Read
     let cm = (iss & (1 << 8)) != 0; // CM: Cache maintenance
     let mut access_flags = if wnr & !cm {
@@ -77,10 +99,21 @@ fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
     }
     let vaddr = va!(FAR_EL1.get() as usize);
 
+    #[cfg(feature = "uspace")]
+    if is_user && iss & 0b111111 == 0b100001 {
+        // DFSC: Alignment fault
+        return handle_user_exception(tf, crate::trap::UserException::Misaligned);
+    }
     // Only handle Translation fault and Permission fault
     if !matches!(iss & 0b111100, 0b0100 | 0b1100) // IFSC or DFSC bits
         || !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user)
     {
+        #[cfg(feature = "uspace")]
+        if is_user {
+            return handle_user_exception(tf, crate::trap::UserException::AccessFault);
+        } else if super::uaccess::fixup_exception(tf) {
+            return;
+        }
         panic!(
             "Unhandled {} Data Abort @ {:#x}, fault_vaddr={:#x}, ISS=0b{:08b} ({:?}):\n{:#x?}",
             if is_user { "EL0" } else { "EL1" },
@@ -98,9 +131,24 @@ fn handle_sync_exception(tf: &mut TrapFrame) {
     let esr = ESR_EL1.extract();
     let iss = esr.read(ESR_EL1::ISS);
     match esr.read_as_enum(ESR_EL1::EC) {
+        #[cfg(feature = "uspace")]
+        Some(ESR_EL1::EC::Value::SVC64) if tf.is_user() => {
+            // `elr` already points to the next instruction.
+            tf.r[0] = crate::trap::handle_syscall(tf, tf.r[8] as usize) as u64;
+        }
         Some(ESR_EL1::EC::Value::SVC64) => {
             warn!("No syscall is supported currently!");
         }
+        #[cfg(feature = "uspace")]
+        Some(ESR_EL1::EC::Value::Unknown) if tf.is_user() => {
+            handle_user_exception(tf, crate::trap::UserException::IllegalInstruction)
+        }
+        #[cfg(feature = "uspace")]
+        Some(ESR_EL1::EC::Value::PCAlignmentFault | ESR_EL1::EC::Value::SPAlignmentFault)
+            if tf.is_user() =>
+        {
+            handle_user_exception(tf, crate::trap::UserException::Misaligned)
+        }
         Some(ESR_EL1::EC::Value::InstrAbortLowerEL) => handle_instruction_abort(tf, iss, true),
         Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => handle_instruction_abort(tf, iss, false),
         Some(ESR_EL1::EC::Value::DataAbortLowerEL) => handle_data_abort(tf, iss, true),
@@ -119,4 +167,8 @@ fn handle_sync_exception(tf: &mut TrapFrame) {
             );
         }
     }
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::return_to_user(tf);
+    }
 }
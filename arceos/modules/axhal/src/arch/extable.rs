@@ -0,0 +1,29 @@
+//! The exception table, which lists the instructions that may fault on user
+//! memory, and where to resume if they do.
+//!
+//! Entries are added by the `EXTABLE` assembler macro of each architecture.
+
+/// An entry of the exception table: a fault at `insn` resumes at `fixup`.
+#[repr(C)]
+struct ExceptionEntry {
+    insn: usize,
+    fixup: usize,
+}
+
+/// Returns where to resume after a fault at the instruction `pc`, or `None`
+/// if it is not in the exception table.
+pub(crate) fn search_exception_table(pc: usize) -> Option<usize> {
+    extern "C" {
+        fn __start_ex_table();
+        fn __stop_ex_table();
+    }
+    let table = unsafe {
+        let start = __start_ex_table as usize as *const ExceptionEntry;
+        let len = (__stop_ex_table as usize - start as usize) / core::mem::size_of::<ExceptionEntry>();
+        core::slice::from_raw_parts(start, len)
+    };
+    table
+        .iter()
+        .find(|entry| entry.insn == pc)
+        .map(|entry| entry.fixup)
+}
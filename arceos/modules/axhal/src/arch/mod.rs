@@ -1,5 +1,8 @@
 //! Architecture-specific types and operations.
 
+#[cfg(feature = "uspace")]
+mod extable;
+
 cfg_if::cfg_if! {
     if #[cfg(target_arch = "x86_64")] {
         mod x86_64;
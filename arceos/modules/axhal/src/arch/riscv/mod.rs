@@ -3,6 +3,8 @@ mod macros;
 
 mod context;
 mod trap;
+#[cfg(feature = "uspace")]
+mod uaccess;
 
 use memory_addr::{PhysAddr, VirtAddr};
 use riscv::asm;
@@ -10,6 +12,8 @@ use riscv::register::{satp, sstatus, stvec};
 
 #[cfg(feature = "uspace")]
 pub use self::context::UspaceContext;
+#[cfg(feature = "uspace")]
+pub use self::uaccess::copy_user;
 pub use self::context::{GeneralRegisters, TaskContext, TrapFrame};
 
 /// Allows the current CPU to respond to interrupts.
@@ -16,12 +16,16 @@ fn handle_breakpoint(sepc: &mut usize) {
     *sepc += 2
 }
 
-fn handle_page_fault(tf: &TrapFrame, mut access_flags: MappingFlags, is_user: bool) {
+fn handle_page_fault(tf: &mut TrapFrame, mut access_flags: MappingFlags, is_user: bool) {
     if is_user {
         access_flags |= MappingFlags::USER;
     }
     let vaddr = va!(stval::read());
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user) {
+        #[cfg(feature = "uspace")]
+        if !is_user && super::uaccess::fixup_exception(tf) {
+            return;
+        }
         panic!(
             "Unhandled {} Page Fault @ {:#x}, fault_vaddr={:#x} ({:?}):\n{:#x?}",
             if is_user { "User" } else { "Supervisor" },
@@ -33,14 +37,28 @@ fn handle_page_fault(tf: &TrapFrame, mut access_flags: MappingFlags, is_user: bo
     }
 }
 
+#[cfg(feature = "uspace")]
+fn handle_user_exception(tf: &TrapFrame, exception: crate::trap::UserException) {
+    if !handle_trap!(USER_EXCEPTION, tf, exception) {
+        panic!(
+            "Unhandled user exception {:?} @ {:#x}, stval={:#x}:\n{:#x?}",
+            exception,
+            tf.sepc,
+            stval::read(),
+            tf
+        );
+    }
+}
+
 #[no_mangle]
 fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
     let scause = scause::read();
     match scause.cause() {
         #[cfg(feature = "uspace")]
         Trap::Exception(E::UserEnvCall) => {
-            tf.regs.a0 = crate::trap::handle_syscall(tf, tf.regs.a7) as usize;
+            // Return to the next instruction, as on other architectures.
             tf.sepc += 4;
+            tf.regs.a0 = crate::trap::handle_syscall(tf, tf.regs.a7) as usize;
         }
         Trap::Exception(E::LoadPageFault) => handle_page_fault(tf, MappingFlags::READ, from_user),
         Trap::Exception(E::StorePageFault) => handle_page_fault(tf, MappingFlags::WRITE, from_user),
@@ -48,6 +66,20 @@ fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
             handle_page_fault(tf, MappingFlags::EXECUTE, from_user)
         }
         Trap::Exception(E::Breakpoint) => handle_breakpoint(&mut tf.sepc),
+        #[cfg(feature = "uspace")]
+        Trap::Exception(E::IllegalInstruction) if from_user => {
+            handle_user_exception(tf, crate::trap::UserException::IllegalInstruction)
+        }
+        #[cfg(feature = "uspace")]
+        Trap::Exception(E::InstructionMisaligned | E::LoadMisaligned | E::StoreMisaligned)
+            if from_user =>
+        {
+            handle_user_exception(tf, crate::trap::UserException::Misaligned)
+        }
+        #[cfg(feature = "uspace")]
+        Trap::Exception(E::InstructionFault | E::LoadFault | E::StoreFault) if from_user => {
+            handle_user_exception(tf, crate::trap::UserException::AccessFault)
+        }
         Trap::Interrupt(_) => {
             handle_trap!(IRQ, scause.bits());
         }
@@ -60,4 +92,8 @@ fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
             );
         }
     }
+    #[cfg(feature = "uspace")]
+    if from_user {
+        crate::trap::return_to_user(tf);
+    }
 }
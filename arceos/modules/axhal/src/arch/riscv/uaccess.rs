@@ -0,0 +1,42 @@
+//! Fault-safe access to user memory.
+
+use super::TrapFrame;
+
+include_asm_marcos!();
+
+core::arch::global_asm!(include_str!("uaccess.S"));
+
+extern "C" {
+    fn __copy_user(dst: *mut u8, src: *const u8, len: usize) -> usize;
+}
+
+/// Copies `len` bytes from `src` to `dst`, where either of them can be a
+/// user address that may fault.
+///
+/// Page faults in the copy are first passed to the `PAGE_FAULT` handlers as
+/// supervisor faults. If they are not handled, the copy stops instead of
+/// panicking.
+///
+/// Returns the number of bytes that are not copied, i.e., 0 on success.
+///
+/// # Safety
+///
+/// The kernel memory in the ranges must be valid, and the user memory must be
+/// accessible by the kernel (`sstatus.SUM` is set).
+pub unsafe fn copy_user(dst: *mut u8, src: *const u8, len: usize) -> usize {
+    __copy_user(dst, src, len)
+}
+
+/// Resumes from a fault at an instruction in the exception table, by
+/// redirecting `tf` to its fixup code.
+///
+/// Returns `false` if the faulting instruction is not in the table.
+pub(super) fn fixup_exception(tf: &mut TrapFrame) -> bool {
+    match crate::arch::extable::search_exception_table(tf.sepc) {
+        Some(fixup) => {
+            tf.sepc = fixup;
+            true
+        }
+        None => false,
+    }
+}
@@ -6,6 +6,32 @@ use x86_64::structures::gdt::{Descriptor, DescriptorFlags};
 use x86_64::structures::{tss::TaskStateSegment, DescriptorTablePointer};
 use x86_64::{addr::VirtAddr, PrivilegeLevel};
 
+/// The Task State Segment (TSS) of each CPU.
+///
+/// Its `RSP0` is the kernel stack that the CPU switches to on traps from user
+/// space, which is also used by the `syscall` entry.
+#[percpu::def_percpu]
+#[no_mangle]
+static TSS: TaskStateSegment = TaskStateSegment::new();
+
+/// Returns the Task State Segment of the current CPU.
+pub(crate) fn current_tss() -> &'static TaskStateSegment {
+    unsafe { TSS.current_ref_raw() }
+}
+
+/// Sets the kernel stack used on traps from user space on the current CPU,
+/// i.e., `RSP0` of its TSS.
+///
+/// # Safety
+///
+/// This function is unsafe because the stack must be valid for the current
+/// task before it returns to user space.
+#[cfg(feature = "uspace")]
+pub(crate) unsafe fn tss_set_rsp0(kstack_top: memory_addr::VirtAddr) {
+    TSS.current_ref_mut_raw().privilege_stack_table[0] =
+        VirtAddr::new(kstack_top.as_usize() as u64);
+}
+
 /// A wrapper of the Global Descriptor Table (GDT) with maximum 16 entries.
 #[repr(align(16))]
 pub struct GdtStruct {
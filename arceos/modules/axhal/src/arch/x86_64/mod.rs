@@ -5,6 +5,11 @@ mod idt;
 #[cfg(target_os = "none")]
 mod trap;
 
+#[cfg(feature = "uspace")]
+mod syscall;
+#[cfg(feature = "uspace")]
+mod uaccess;
+
 use core::arch::asm;
 
 use memory_addr::{MemoryAddr, PhysAddr, VirtAddr};
@@ -13,6 +18,15 @@ use x86_64::instructions::interrupts;
 
 pub use self::context::{ExtendedState, FxsaveArea, TaskContext, TrapFrame};
 pub use self::gdt::GdtStruct;
+pub(crate) use self::gdt::current_tss;
+#[cfg(feature = "uspace")]
+pub(crate) use self::gdt::tss_set_rsp0;
+#[cfg(feature = "uspace")]
+pub use self::context::UspaceContext;
+#[cfg(feature = "uspace")]
+pub(crate) use self::syscall::init_syscall;
+#[cfg(feature = "uspace")]
+pub use self::uaccess::copy_user;
 pub use self::idt::IdtStruct;
 pub use x86_64::structures::tss::TaskStateSegment;
 
@@ -108,3 +122,16 @@ pub fn read_thread_pointer() -> usize {
 pub unsafe fn write_thread_pointer(fs_base: usize) {
     unsafe { msr::wrmsr(msr::IA32_FS_BASE, fs_base as u64) }
 }
+
+/// Stores `cpu_id` in `IA32_TSC_AUX` of the current CPU if `rdtscp` is
+/// supported, so that user space can get its CPU number with `rdtscp`
+/// (e.g., `getcpu` in the vDSO).
+#[cfg(feature = "uspace")]
+pub(crate) fn init_tsc_aux(cpu_id: usize) {
+    let has_rdtscp = raw_cpuid::CpuId::new()
+        .get_extended_processor_and_feature_identifiers()
+        .is_some_and(|info| info.has_rdtscp());
+    if has_rdtscp {
+        unsafe { msr::wrmsr(msr::IA32_TSC_AUX, cpu_id as u64) }
+    }
+}
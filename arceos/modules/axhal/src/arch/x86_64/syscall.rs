@@ -0,0 +1,57 @@
+//! Syscalls from user space with the `syscall` instruction.
+
+use x86_64::addr::VirtAddr;
+use x86_64::registers::model_specific::{Efer, EferFlags, KernelGsBase, LStar, SFMask, Star};
+use x86_64::registers::rflags::RFlags;
+use x86_64::structures::tss::TaskStateSegment;
+
+use super::{GdtStruct, TrapFrame};
+
+core::arch::global_asm!(
+    include_str!("syscall.S"),
+    tss_rsp0_offset = const core::mem::offset_of!(TaskStateSegment, privilege_stack_table),
+    ucode64 = const GdtStruct::UCODE64_SELECTOR.0,
+    udata = const GdtStruct::UDATA_SELECTOR.0,
+);
+
+/// The user stack pointer, saved by `syscall_entry` before it switches to the
+/// kernel stack.
+#[percpu::def_percpu]
+#[no_mangle]
+static USER_RSP: usize = 0;
+
+#[no_mangle]
+fn x86_syscall_handler(tf: &mut TrapFrame) {
+    tf.rax = crate::trap::handle_syscall(tf, tf.rax as usize) as u64;
+    crate::trap::return_to_user(tf);
+}
+
+/// Enables the `syscall` instruction on the current CPU.
+///
+/// `syscall` jumps to `syscall_entry` with interrupts disabled, and `sysret`
+/// returns with the user code and data segments of [`GdtStruct`].
+pub(crate) fn init_syscall() {
+    extern "C" {
+        fn syscall_entry();
+    }
+    LStar::write(VirtAddr::new(syscall_entry as usize as u64));
+    Star::write(
+        GdtStruct::UCODE64_SELECTOR,
+        GdtStruct::UDATA_SELECTOR,
+        GdtStruct::KCODE64_SELECTOR,
+        GdtStruct::KDATA_SELECTOR,
+    )
+    .unwrap();
+    SFMask::write(
+        RFlags::TRAP_FLAG
+            | RFlags::INTERRUPT_FLAG
+            | RFlags::DIRECTION_FLAG
+            | RFlags::IOPL_LOW
+            | RFlags::IOPL_HIGH
+            | RFlags::NESTED_TASK
+            | RFlags::ALIGNMENT_CHECK,
+    );
+    unsafe { Efer::update(|efer| *efer |= EferFlags::SYSTEM_CALL_EXTENSIONS) };
+    // The kernel `GS` base is swapped in by `swapgs` on entry.
+    KernelGsBase::write(VirtAddr::new(0));
+}
@@ -9,11 +9,15 @@ core::arch::global_asm!(include_str!("trap.S"));
 const IRQ_VECTOR_START: u8 = 0x20;
 const IRQ_VECTOR_END: u8 = 0xff;
 
-fn handle_page_fault(tf: &TrapFrame) {
+fn handle_page_fault(tf: &mut TrapFrame) {
     let access_flags = err_code_to_flags(tf.error_code)
         .unwrap_or_else(|e| panic!("Invalid #PF error code: {:#x}", e));
     let vaddr = va!(unsafe { cr2() });
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, tf.is_user()) {
+        #[cfg(feature = "uspace")]
+        if !tf.is_user() && super::uaccess::fixup_exception(tf) {
+            return;
+        }
         panic!(
             "Unhandled {} #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x} ({:?}):\n{:#x?}",
             if tf.is_user() { "user" } else { "kernel" },
@@ -26,11 +30,41 @@ fn handle_page_fault(tf: &TrapFrame) {
     }
 }
 
+#[cfg(feature = "uspace")]
+fn handle_user_exception(tf: &TrapFrame, exception: crate::trap::UserException) {
+    if !handle_trap!(USER_EXCEPTION, tf, exception) {
+        panic!(
+            "Unhandled user exception {:?} ({}, error_code={:#x}) @ {:#x}:\n{:#x?}",
+            exception,
+            vec_to_str(tf.vector),
+            tf.error_code,
+            tf.rip,
+            tf
+        );
+    }
+}
+
 #[no_mangle]
-fn x86_trap_handler(tf: &TrapFrame) {
+fn x86_trap_handler(tf: &mut TrapFrame) {
     match tf.vector as u8 {
         PAGE_FAULT_VECTOR => handle_page_fault(tf),
         BREAKPOINT_VECTOR => debug!("#BP @ {:#x} ", tf.rip),
+        #[cfg(feature = "uspace")]
+        INVALID_OPCODE_VECTOR if tf.is_user() => {
+            handle_user_exception(tf, crate::trap::UserException::IllegalInstruction)
+        }
+        #[cfg(feature = "uspace")]
+        ALIGNMENT_CHECK_VECTOR if tf.is_user() => {
+            handle_user_exception(tf, crate::trap::UserException::Misaligned)
+        }
+        #[cfg(feature = "uspace")]
+        GENERAL_PROTECTION_FAULT_VECTOR if tf.is_user() => {
+            handle_user_exception(tf, crate::trap::UserException::AccessFault)
+        }
+        #[cfg(feature = "uspace")]
+        DIVIDE_ERROR_VECTOR if tf.is_user() => {
+            handle_user_exception(tf, crate::trap::UserException::Arithmetic)
+        }
         GENERAL_PROTECTION_FAULT_VECTOR => {
             panic!(
                 "#GP @ {:#x}, error_code={:#x}:\n{:#x?}",
@@ -51,6 +85,10 @@ fn x86_trap_handler(tf: &TrapFrame) {
             );
         }
     }
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::return_to_user(tf);
+    }
 }
 
 fn vec_to_str(vec: u64) -> &'static str {
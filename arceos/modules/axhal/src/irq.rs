@@ -1,21 +1,42 @@
 //! Interrupt management.
 
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use handler_table::HandlerTable;
 
 use crate::platform::irq::{dispatch_irq, MAX_IRQ_COUNT};
 use crate::trap::{register_trap_handler, IRQ};
 
-pub use crate::platform::irq::{register_handler, set_enable};
+pub use crate::platform::irq::{register_handler, set_enable, MAX_IRQ_COUNT};
 
 /// The type if an IRQ handler.
 pub type IrqHandler = handler_table::Handler;
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+/// The number of times each IRQ has occurred on each CPU.
+static IRQ_COUNTS: [[AtomicUsize; MAX_IRQ_COUNT]; axconfig::SMP] =
+    [const { [const { AtomicUsize::new(0) }; MAX_IRQ_COUNT] }; axconfig::SMP];
+
+/// Returns the number of times the IRQ `irq_num` has occurred on the CPU
+/// `cpu_id`.
+pub fn irq_count(cpu_id: usize, irq_num: usize) -> usize {
+    IRQ_COUNTS[cpu_id][irq_num].load(Ordering::Relaxed)
+}
+
+/// Counts an occurrence of the IRQ `irq_num` on the current CPU.
+#[allow(dead_code)]
+pub(crate) fn count_irq(irq_num: usize) {
+    if irq_num < MAX_IRQ_COUNT {
+        IRQ_COUNTS[crate::cpu::this_cpu_id()][irq_num].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
 /// Platform-independent IRQ dispatching.
 #[allow(dead_code)]
 pub(crate) fn dispatch_irq_common(irq_num: usize) {
     trace!("IRQ {}", irq_num);
+    count_irq(irq_num);
     if !IRQ_HANDLER_TABLE.handle(irq_num) {
         warn!("Unhandled IRQ {}", irq_num);
     }
@@ -83,4 +83,15 @@ pub(crate) fn init_percpu() {
         CNTP_TVAL_EL0.set(0);
         crate::platform::irq::set_enable(crate::platform::irq::TIMER_IRQ_NUM, true);
     }
+    // Let EL0 read `CNTPCT_EL0` (`CNTKCTL_EL1.EL0PCTEN`), e.g., for
+    // `clock_gettime` in the vDSO.
+    #[cfg(feature = "uspace")]
+    unsafe {
+        core::arch::asm!(
+            "mrs {0}, cntkctl_el1",
+            "orr {0}, {0}, #1",
+            "msr cntkctl_el1, {0}",
+            out(reg) _,
+        )
+    };
 }
@@ -57,4 +57,9 @@ pub(super) fn init_early() {
 pub(super) fn init_percpu() {
     #[cfg(feature = "irq")]
     sbi_rt::set_timer(0);
+    // Let user space read the `time` CSR, e.g., for `clock_gettime` in the vDSO.
+    #[cfg(feature = "uspace")]
+    unsafe {
+        riscv::register::scounteren::set_tm()
+    };
 }
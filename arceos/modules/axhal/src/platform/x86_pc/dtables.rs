@@ -1,26 +1,26 @@
 //! Description tables (per-CPU GDT, per-CPU ISS, IDT)
 
-use crate::arch::{GdtStruct, IdtStruct, TaskStateSegment};
+use crate::arch::{GdtStruct, IdtStruct};
 use lazyinit::LazyInit;
 
 static IDT: LazyInit<IdtStruct> = LazyInit::new();
 
-#[percpu::def_percpu]
-static TSS: LazyInit<TaskStateSegment> = LazyInit::new();
-
 #[percpu::def_percpu]
 static GDT: LazyInit<GdtStruct> = LazyInit::new();
 
 fn init_percpu() {
     unsafe {
         IDT.load();
-        let tss = TSS.current_ref_mut_raw();
         let gdt = GDT.current_ref_mut_raw();
-        tss.init_once(TaskStateSegment::new());
-        gdt.init_once(GdtStruct::new(tss));
+        gdt.init_once(GdtStruct::new(crate::arch::current_tss()));
         gdt.load();
         gdt.load_tss();
     }
+    #[cfg(feature = "uspace")]
+    {
+        crate::arch::init_syscall();
+        crate::arch::init_tsc_aux(crate::cpu::this_cpu_id());
+    }
 }
 
 /// Initializes IDT, GDT on the primary CPU.
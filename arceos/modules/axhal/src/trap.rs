@@ -22,6 +22,33 @@ pub static PAGE_FAULT: [fn(VirtAddr, MappingFlags, bool) -> bool];
 #[def_trap_handler]
 pub static SYSCALL: [fn(&TrapFrame, usize) -> isize];
 
+/// A slice of handler functions for exceptions from user space other than
+/// page faults and syscalls (e.g., illegal instructions).
+#[cfg(feature = "uspace")]
+#[def_trap_handler]
+pub static USER_EXCEPTION: [fn(&TrapFrame, UserException) -> bool];
+
+/// A slice of functions called right before returning to user space from a
+/// trap, which may modify the user context (e.g., to deliver signals).
+#[cfg(feature = "uspace")]
+#[def_trap_handler]
+pub static RETURN_TO_USER: [fn(&mut TrapFrame)];
+
+/// Exceptions from user space that are passed to [`USER_EXCEPTION`] handlers.
+#[cfg(feature = "uspace")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserException {
+    /// An illegal or unsupported instruction.
+    IllegalInstruction,
+    /// A misaligned instruction fetch or memory access.
+    Misaligned,
+    /// A memory access denied by physical memory protection, or a protection
+    /// fault other than a page fault (e.g., `#GP` on x86_64).
+    AccessFault,
+    /// An arithmetic error, e.g., integer division by zero on x86_64.
+    Arithmetic,
+}
+
 #[allow(unused_macros)]
 macro_rules! handle_trap {
     ($trap:ident, $($args:tt)*) => {{
@@ -43,3 +70,11 @@ macro_rules! handle_trap {
 pub(crate) fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
     SYSCALL[0](tf, syscall_num)
 }
+
+/// Call the functions to run before returning to user space.
+#[cfg(feature = "uspace")]
+pub(crate) fn return_to_user(tf: &mut TrapFrame) {
+    for func in RETURN_TO_USER.iter() {
+        func(tf);
+    }
+}
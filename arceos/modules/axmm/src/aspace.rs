@@ -1,24 +1,45 @@
 use core::fmt;
 
 use axerrno::{ax_err, AxError, AxResult};
+use axfs_vfs::VfsNodeRef;
 use axhal::{
     mem::phys_to_virt,
     paging::{MappingFlags, PageTable},
 };
 use memory_addr::{
-    is_aligned_4k, pa, MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K,
+    is_aligned_4k, MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K,
 };
 use memory_set::{MemoryArea, MemorySet};
-use crate::backend::Backend;
+use crate::backend::{Backend, SharedPages};
 use crate::paging_err_to_ax_err;
 use crate::mapping_err_to_ax_err;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 
+/// The gap kept free below the stack, so that a stack overflow faults instead
+/// of running into another mapping.
+const STACK_GUARD_GAP: usize = 256 * PAGE_SIZE_4K;
+
 /// The virtual memory address space.
 pub struct AddrSpace {
     va_range: VirtAddrRange,
     areas: MemorySet<Backend>,
     pt: PageTable,
+    /// The start of the heap, i.e., the initial program break.
+    heap_start: VirtAddr,
+    /// The current program break.
+    brk: VirtAddr,
+    /// The top of the stack, or the base of the address space if there is
+    /// none.
+    stack_top: VirtAddr,
+    /// The lowest address of the stack, which grows down on page faults
+    /// below it.
+    stack_bottom: VirtAddr,
+    /// The maximum size of the stack.
+    stack_limit: usize,
+    /// Where the search for a free area starts if the caller has no
+    /// preference (see [`find_free_area`](Self::find_free_area)).
+    mmap_base: VirtAddr,
 }
 
 impl AddrSpace {
@@ -47,6 +68,14 @@ impl AddrSpace {
         self.pt.root_paddr()
     }
 
+    /// Returns the start address, size and flags of each mapped area, in
+    /// ascending order of addresses.
+    pub fn areas(&self) -> impl Iterator<Item = (VirtAddr, usize, MappingFlags)> + '_ {
+        self.areas
+            .iter()
+            .map(|area| (area.start(), area.size(), area.flags()))
+    }
+
     /// Checks if the address space contains the given address range.
     pub fn contains_range(&self, start: VirtAddr, size: usize) -> bool {
         self.va_range
@@ -59,6 +88,12 @@ impl AddrSpace {
             va_range: VirtAddrRange::from_start_size(base, size),
             areas: MemorySet::new(),
             pt: PageTable::try_new().map_err(|_| AxError::NoMemory)?,
+            heap_start: base,
+            brk: base,
+            stack_top: base,
+            stack_bottom: base,
+            stack_limit: 0,
+            mmap_base: base,
         })
     }
 
@@ -77,9 +112,90 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Duplicates the user address space for a child process (e.g., `fork`),
+    /// with copy-on-write semantics.
+    ///
+    /// The kernel portion is shared as in [`new_user_aspace`], and all areas
+    /// are recreated with the same flags in the new address space. Instead of
+    /// copying the content, the physical frames already mapped are shared by
+    /// both address spaces, and the write permission is removed from the
+    /// private pages, so that the first write from either side will copy the
+    /// page (see [`handle_page_fault`]). Pages of shared file mappings and
+    /// shared memory remain writable and shared.
+    ///
+    /// [`new_user_aspace`]: crate::new_user_aspace
+    /// [`handle_page_fault`]: Self::handle_page_fault
+    pub fn clone_cow(&mut self) -> AxResult<Self> {
+        let mut aspace = Self::new_empty(self.base(), self.size())?;
+        if !cfg!(target_arch = "aarch64") {
+            aspace.copy_mappings_from(&crate::kernel_aspace().lock())?;
+        }
+        aspace.heap_start = self.heap_start;
+        aspace.brk = self.brk;
+        aspace.stack_top = self.stack_top;
+        aspace.stack_bottom = self.stack_bottom;
+        aspace.stack_limit = self.stack_limit;
+        aspace.mmap_base = self.mmap_base;
+
+        for area in self.areas.iter() {
+            let backend = match area.backend() {
+                // Frames are shared with the parent, so there is no need to
+                // populate the new area.
+                Backend::Alloc { .. } => Backend::new_alloc(false),
+                backend => backend.clone(),
+            };
+            let shared = matches!(area.backend(), Backend::File(file) if file.is_shared());
+            let linear = matches!(area.backend(), Backend::Linear { .. });
+            // The frames of shared memory are owned by the `SharedPages`.
+            let owned = !matches!(area.backend(), Backend::Shared(_));
+            let flags = area.flags();
+            let new_area = MemoryArea::new(area.start(), area.size(), flags, backend);
+            aspace
+                .areas
+                .map(new_area, &mut aspace.pt, false)
+                .map_err(mapping_err_to_ax_err)?;
+            if linear {
+                continue; // Already mapped to the same frames.
+            }
+
+            let cow_flags = if shared || !owned {
+                flags
+            } else {
+                flags - MappingFlags::WRITE
+            };
+            for vaddr in PageIter4K::new(area.start(), area.end()).unwrap() {
+                if let Ok((frame, page_flags, _)) = self.pt.query(vaddr) {
+                    if page_flags.is_empty() {
+                        continue; // Not populated yet.
+                    }
+                    if owned {
+                        crate::backend::share_frame(frame);
+                    }
+                    aspace
+                        .pt
+                        .remap(vaddr, frame, cow_flags)
+                        .map_err(paging_err_to_ax_err)?
+                        .1
+                        .ignore();
+                    if cow_flags != flags {
+                        self.pt
+                            .remap(vaddr, frame, cow_flags)
+                            .map_err(paging_err_to_ax_err)?
+                            .1
+                            .ignore();
+                    }
+                }
+            }
+        }
+        // The parent may have cached the writable mappings.
+        axhal::arch::flush_tlb(None);
+        Ok(aspace)
+    }
+
     /// Finds a free area that can accommodate the given size.
     ///
     /// The search starts from the given hint address, and the area should be within the given limit range.
+    /// The range that the stack may grow into, and its guard gap, are not free.
     ///
     /// Returns the start address of the free area. Returns None if no such area is found.
     pub fn find_free_area(
@@ -88,7 +204,14 @@ impl AddrSpace {
         size: usize,
         limit: VirtAddrRange,
     ) -> Option<VirtAddr> {
-        self.areas.find_free_area(hint, size, limit)
+        let Some(reserved) = self.stack_reservation() else {
+            return self.areas.find_free_area(hint, size, limit);
+        };
+        let below = VirtAddrRange::new(limit.start, reserved.start.clamp(limit.start, limit.end));
+        let above = VirtAddrRange::new(reserved.end.clamp(limit.start, limit.end), limit.end);
+        self.areas
+            .find_free_area(hint.max(below.start), size, below)
+            .or_else(|| self.areas.find_free_area(hint.max(above.start), size, above))
     }
 
     /// Add a new linear mapping.
@@ -115,17 +238,10 @@ impl AddrSpace {
         }
 
         let offset = start_vaddr.as_usize() - start_paddr.as_usize();
-        self.pt
-            .map_region(
-                start_vaddr,
-                |va| pa!(va.as_usize() - offset),
-                size,
-                flags,
-                false, // allow_huge
-                false, // flush_tlb_by_page
-            )
-            .map_err(paging_err_to_ax_err)?
-            .flush_all();
+        let area = MemoryArea::new(start_vaddr, size, flags, Backend::new_linear(offset));
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
         Ok(())
     }
 
@@ -158,8 +274,88 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Add a new file mapping.
+    ///
+    /// The page at `start` is mapped to the content of the file `node` at
+    /// `offset`, and the pages are read from the file on demand (by handling
+    /// page faults). If `shared` is `true`, the modifications are written back
+    /// to the file by [`msync`](Self::msync) or when the pages are unmapped
+    /// (`MAP_SHARED`). Otherwise, the pages are private to this mapping
+    /// (`MAP_PRIVATE`).
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn map_file(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        node: VfsNodeRef,
+        offset: u64,
+        shared: bool,
+    ) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) || !is_aligned_4k(offset as usize) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        let backend = Backend::new_file(node, start, offset, shared);
+        let area = MemoryArea::new(start, size, flags, backend);
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
+    /// Add a new shared memory mapping.
+    ///
+    /// The page at `start` is mapped to `pages` at `offset`, and the frames
+    /// are obtained from `pages` on demand (by handling page faults). They
+    /// are shared with every other mapping of `pages`, including the ones
+    /// inherited by [`clone_cow`](Self::clone_cow).
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn map_shared(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pages: Arc<SharedPages>,
+        offset: usize,
+    ) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) || !is_aligned_4k(offset) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        let backend = Backend::new_shared(pages, start, offset);
+        let area = MemoryArea::new(start, size, flags, backend);
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
+    /// Returns the shared pages mapped at `vaddr` and the address that their
+    /// start (offset 0) is mapped to, if `vaddr` is in a shared memory
+    /// mapping.
+    pub fn shared_pages_at(&self, vaddr: VirtAddr) -> Option<(Arc<SharedPages>, VirtAddr)> {
+        match self.areas.find(vaddr)?.backend() {
+            Backend::Shared(shared) => Some((shared.pages().clone(), shared.base())),
+            _ => None,
+        }
+    }
+
     /// Removes mappings within the specified virtual address range.
     ///
+    /// The areas partially covered by the range are shrunk or split. Pages of
+    /// shared file mappings are written back before being released.
+    ///
     /// Returns an error if the address range is out of the address space or not
     /// aligned.
     pub fn unmap(&mut self, start: VirtAddr, size: usize) -> AxResult {
@@ -170,13 +366,190 @@ impl AddrSpace {
             return ax_err!(InvalidInput, "address not aligned");
         }
 
-        self.pt
-            .unmap_region(start, size, true)
-            .map_err(paging_err_to_ax_err)?
-            .ignore();
+        self.areas
+            .unmap(start, size, &mut self.pt)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
+    /// Writes the modified pages of shared file mappings within the specified
+    /// virtual address range back to their files.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn msync(&self, start: VirtAddr, size: usize) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        let end = start + size;
+        for area in self.areas.iter() {
+            let sync_start = area.start().max(start);
+            let sync_end = area.end().min(end);
+            if sync_start < sync_end {
+                area.backend()
+                    .sync(sync_start, sync_end - sync_start, &self.pt);
+            }
+        }
         Ok(())
     }
 
+    /// Removes all mappings in the address space.
+    ///
+    /// Returns an error if a backend fails to unmap an area.
+    pub fn clear(&mut self) -> AxResult {
+        self.areas
+            .clear(&mut self.pt)
+            .map_err(mapping_err_to_ax_err)?;
+        self.heap_start = self.base();
+        self.brk = self.base();
+        self.stack_top = self.base();
+        self.stack_bottom = self.base();
+        self.stack_limit = 0;
+        self.mmap_base = self.base();
+        Ok(())
+    }
+
+    /// Returns the address where the search for a free area for a new
+    /// mapping starts, if the caller has no preference.
+    pub const fn mmap_base(&self) -> VirtAddr {
+        self.mmap_base
+    }
+
+    /// Sets the address where the search for a free area for a new mapping
+    /// starts, usually well above the program and its heap, and possibly
+    /// randomized.
+    pub fn set_mmap_base(&mut self, mmap_base: VirtAddr) {
+        self.mmap_base = mmap_base.align_up_4k();
+    }
+
+    /// Returns the start of the heap.
+    pub const fn heap_start(&self) -> VirtAddr {
+        self.heap_start
+    }
+
+    /// Returns the current program break.
+    pub const fn brk(&self) -> VirtAddr {
+        self.brk
+    }
+
+    /// Returns the range that the stack occupies, if there is a stack.
+    pub fn stack(&self) -> Option<VirtAddrRange> {
+        (self.stack_top != self.base())
+            .then(|| VirtAddrRange::new(self.stack_bottom, self.stack_top))
+    }
+
+    /// Sets up an empty heap at `start` (aligned up to the page boundary),
+    /// usually right after the highest loaded segment.
+    pub fn init_heap(&mut self, start: VirtAddr) {
+        self.heap_start = start.align_up_4k();
+        self.brk = self.heap_start;
+    }
+
+    /// Moves the program break to `new_brk`.
+    ///
+    /// Pages between the old and new break are mapped with `flags` when the
+    /// heap grows, populated lazily on page faults, and unmapped when it
+    /// shrinks.
+    ///
+    /// Returns an error if `new_brk` is below the start of the heap, or the
+    /// heap would overlap other mappings.
+    pub fn set_brk(&mut self, new_brk: VirtAddr, flags: MappingFlags) -> AxResult {
+        if new_brk < self.heap_start {
+            return ax_err!(InvalidInput, "break below the heap start");
+        }
+        let old_end = self.brk.align_up_4k();
+        let new_end = new_brk.align_up_4k();
+        if new_end > old_end {
+            self.map_alloc(old_end, new_end - old_end, flags, false)?;
+        } else if new_end < old_end {
+            self.unmap(new_end, old_end - new_end)?;
+        }
+        self.brk = new_brk;
+        Ok(())
+    }
+
+    /// Maps the stack `[top - size, top)` with `flags`, populated.
+    ///
+    /// The stack grows down on page faults below it, up to `limit` bytes,
+    /// while leaving a guard gap to the area below. The range it may grow
+    /// into is reserved, i.e., not returned by
+    /// [`find_free_area`](Self::find_free_area).
+    pub fn init_stack(
+        &mut self,
+        top: VirtAddr,
+        size: usize,
+        limit: usize,
+        flags: MappingFlags,
+    ) -> AxResult {
+        self.map_alloc(top - size, size, flags, true)?;
+        self.stack_top = top;
+        self.stack_bottom = top - size;
+        self.stack_limit = limit;
+        Ok(())
+    }
+
+    /// Sets the maximum size of the stack, e.g., after `RLIMIT_STACK` is
+    /// changed. A stack already larger than it is not shrunk.
+    pub fn set_stack_limit(&mut self, limit: usize) {
+        self.stack_limit = limit;
+    }
+
+    /// The range reserved for the stack and its guard gap, if there is a
+    /// stack.
+    fn stack_reservation(&self) -> Option<VirtAddrRange> {
+        if self.stack_top == self.base() {
+            return None;
+        }
+        let size = self.stack_limit.max(self.stack_top - self.stack_bottom) + STACK_GUARD_GAP;
+        let start = self.stack_top.as_usize().saturating_sub(size);
+        Some(VirtAddrRange::new(
+            VirtAddr::from(start).align_down_4k().max(self.base()),
+            self.stack_top,
+        ))
+    }
+
+    /// Grows the stack down to cover `vaddr`, if `vaddr` is below the stack
+    /// and within its limit, and the guard gap to the area below stays free.
+    ///
+    /// The stack grows at least to twice its size (within the bounds), so
+    /// that a deep stack consists of only a few areas. The new pages are
+    /// populated lazily. Returns whether the stack has grown.
+    fn grow_stack(&mut self, vaddr: VirtAddr) -> bool {
+        if self.stack_top == self.base() || vaddr >= self.stack_bottom {
+            return false;
+        }
+        // The stack may be unmapped, in which case it does not grow.
+        let Some(flags) = self.areas.find(self.stack_bottom).map(|area| area.flags()) else {
+            return false;
+        };
+        let floor = self
+            .areas
+            .iter()
+            .map(|area| area.end())
+            .filter(|&end| end <= self.stack_bottom)
+            .max()
+            .unwrap_or(self.base());
+        let lowest = VirtAddr::from(self.stack_top.as_usize().saturating_sub(self.stack_limit))
+            .align_up_4k()
+            .max(floor + STACK_GUARD_GAP);
+        if vaddr < lowest {
+            debug!("stack overflow at {:?}", vaddr);
+            return false;
+        }
+        let size = self.stack_top - self.stack_bottom;
+        let doubled = VirtAddr::from(self.stack_top.as_usize().saturating_sub(2 * size));
+        let bottom = vaddr.align_down_4k().min(doubled.max(lowest));
+        if self.map_alloc(bottom, self.stack_bottom - bottom, flags, false).is_err() {
+            return false;
+        }
+        self.stack_bottom = bottom;
+        true
+    }
+
     /// To process data in this area with the given function.
     ///
     /// Now it supports reading and writing data in the given interval.
@@ -223,6 +596,34 @@ impl AddrSpace {
         })
     }
 
+    /// Reads the page at `vaddr`, even if it is not populated yet: such a
+    /// page is read from where it would be faulted in from, i.e., the file of
+    /// a file mapping or the pages of a shared mapping. Otherwise, it reads as
+    /// zeros. No frames are allocated.
+    pub fn read_page(&self, vaddr: VirtAddr, buf: &mut [u8; PAGE_SIZE_4K]) -> AxResult {
+        let vaddr = vaddr.align_down_4k();
+        if let Ok((paddr, flags, _)) = self.pt.query(vaddr) {
+            if !flags.is_empty() {
+                buf.copy_from_slice(unsafe {
+                    core::slice::from_raw_parts(phys_to_virt(paddr).as_ptr(), PAGE_SIZE_4K)
+                });
+                return Ok(());
+            }
+        }
+        let area = self.areas.find(vaddr).ok_or(AxError::BadAddress)?;
+        match area.backend() {
+            Backend::File(file) => file.read_page(vaddr, buf)?,
+            Backend::Shared(shared) => {
+                buf.fill(0);
+                shared
+                    .pages()
+                    .read_at(shared.page_index(vaddr) * PAGE_SIZE_4K, buf);
+            }
+            _ => buf.fill(0),
+        }
+        Ok(())
+    }
+
     /// To write data to the address space.
     ///
     /// # Arguments
@@ -235,10 +636,14 @@ impl AddrSpace {
         })
     }
 
-    /// Updates mapping within the specified virtual address range.
+    /// Updates the flags of the mappings within the specified virtual address
+    /// range.
     ///
-    /// Returns an error if the address range is out of the address space or not
-    /// aligned.
+    /// The areas partially covered by the range are split. Copy-on-write pages
+    /// stay read-only until they are copied.
+    ///
+    /// Returns an error if the address range is out of the address space, not
+    /// aligned, or not fully mapped.
     pub fn protect(&mut self, start: VirtAddr, size: usize, flags: MappingFlags) -> AxResult {
         if !self.contains_range(start, size) {
             return ax_err!(InvalidInput, "address out of range");
@@ -246,17 +651,160 @@ impl AddrSpace {
         if !start.is_aligned_4k() || !is_aligned_4k(size) {
             return ax_err!(InvalidInput, "address not aligned");
         }
+        if !self.is_range_mapped(start, size) {
+            return ax_err!(NoMemory, "address not mapped");
+        }
 
-        self.pt
-            .protect_region(start, size, flags, true)
-            .map_err(paging_err_to_ax_err)?
-            .ignore();
+        self.areas
+            .protect(start, size, |_| Some(flags), &mut self.pt)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
+    /// Whether every page in the range belongs to some area.
+    fn is_range_mapped(&self, start: VirtAddr, size: usize) -> bool {
+        let mut next = start;
+        let end = start + size;
+        for area in self.areas.iter() {
+            if next >= end {
+                break;
+            }
+            if area.end() <= next {
+                continue;
+            }
+            if area.start() > next {
+                return false;
+            }
+            next = area.end();
+        }
+        next >= end
+    }
+
+    /// Resizes the allocation mapping at `old_start` from `old_size` to
+    /// `new_size` bytes, as `mremap` does.
+    ///
+    /// Shrinking unmaps the tail, and growing maps lazily allocated pages
+    /// after the mapping if they are free. Otherwise, if `may_move` is set,
+    /// the mapping is moved to a free area of `new_size` bytes, which takes
+    /// over the populated frames, or to `new_start` if it is given. The old
+    /// range must be covered by one area.
+    ///
+    /// Returns the new start address.
+    pub fn resize(
+        &mut self,
+        old_start: VirtAddr,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+        new_start: Option<VirtAddr>,
+    ) -> AxResult<VirtAddr> {
+        if !old_start.is_aligned_4k() || !is_aligned_4k(old_size) || !is_aligned_4k(new_size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+        if new_size == 0 {
+            return ax_err!(InvalidInput, "zero size");
+        }
+        let old_end = old_start + old_size;
+        let flags = match self.areas.find(old_start) {
+            Some(area) if area.end() >= old_end => {
+                if !matches!(area.backend(), Backend::Alloc { .. }) {
+                    return ax_err!(Unsupported, "only allocation mappings can be resized");
+                }
+                area.flags()
+            }
+            _ => return ax_err!(BadAddress, "address not mapped"),
+        };
+
+        if new_start.is_none() {
+            if new_size <= old_size {
+                if new_size < old_size {
+                    self.unmap(old_start + new_size, old_size - new_size)?;
+                }
+                return Ok(old_start);
+            }
+            let grow = VirtAddrRange::from_start_size(old_end, new_size - old_size);
+            if self.contains_range(grow.start, grow.size()) && !self.areas.overlaps(grow) {
+                self.map_alloc(grow.start, grow.size(), flags, false)?;
+                return Ok(old_start);
+            }
+        }
+        if !may_move {
+            return ax_err!(NoMemory, "cannot grow in place");
+        }
+
+        let new_start = match new_start {
+            Some(new_start) => {
+                if !new_start.is_aligned_4k() {
+                    return ax_err!(InvalidInput, "address not aligned");
+                }
+                let new_range = VirtAddrRange::from_start_size(new_start, new_size);
+                if new_range.overlaps(VirtAddrRange::from_start_size(old_start, old_size)) {
+                    return ax_err!(InvalidInput, "overlapping ranges");
+                }
+                self.unmap(new_start, new_size)?;
+                new_start
+            }
+            None => self
+                .find_free_area(self.mmap_base, new_size, self.va_range)
+                .or_else(|| self.find_free_area(self.base(), new_size, self.va_range))
+                .ok_or(AxError::NoMemory)?,
+        };
+        self.map_alloc(new_start, new_size, flags, false)?;
+        // Move the populated pages. The new area is lazily mapped, so its
+        // entries already exist and can be remapped.
+        let moved = old_size.min(new_size);
+        for offset in (0..moved).step_by(PAGE_SIZE_4K) {
+            let Ok((frame, page_flags, _)) = self.pt.query(old_start + offset) else {
+                continue;
+            };
+            if page_flags.is_empty() {
+                continue;
+            }
+            self.pt
+                .remap(new_start + offset, frame, page_flags)
+                .map_err(paging_err_to_ax_err)?
+                .1
+                .flush();
+            self.pt
+                .unmap(old_start + offset)
+                .map_err(paging_err_to_ax_err)?
+                .2
+                .flush();
+        }
+        // Only the pages that were not moved are released.
+        self.unmap(old_start, old_size)?;
+        Ok(new_start)
+    }
+
+    /// Makes all pages in the given range accessible with `access_flags`, by
+    /// handling their page faults in advance (e.g., allocating lazy pages, or
+    /// copying copy-on-write pages for writing).
+    ///
+    /// It is used before the kernel accesses user memory through
+    /// [`read`](Self::read) or [`write`](Self::write), which do not trigger
+    /// page faults. Returns an error if some page cannot be accessed.
+    pub fn prefault(&mut self, start: VirtAddr, size: usize, access_flags: MappingFlags) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(BadAddress);
+        }
+        for vaddr in PageIter4K::new(start.align_down_4k(), (start + size).align_up_4k()).unwrap() {
+            match self.pt.query(vaddr) {
+                Ok((_, flags, _)) if flags.contains(access_flags) => {}
+                _ => {
+                    if !self.handle_page_fault(vaddr, access_flags) {
+                        return ax_err!(BadAddress);
+                    }
+                }
+            }
+        }
         Ok(())
     }
 
     /// Handles a page fault at the given address.
     ///
     /// `access_flags` indicates the access type that caused the page fault.
+    /// A fault below the stack grows it, unless it hits the guard gap or the
+    /// limit of the stack.
     ///
     /// Returns `true` if the page fault is handled successfully (not a real
     /// fault).
@@ -264,6 +812,9 @@ impl AddrSpace {
         if !self.va_range.contains(vaddr) {
             return false;
         }
+        if self.areas.find(vaddr).is_none() && !self.grow_stack(vaddr) {
+            return false;
+        }
         if let Some(area) = self.areas.find(vaddr) {
             let orig_flags = area.flags();
             if orig_flags.contains(access_flags) {
@@ -332,3 +883,11 @@ impl fmt::Debug for AddrSpace {
             .finish()
     }
 }
+
+impl Drop for AddrSpace {
+    fn drop(&mut self) {
+        if let Err(e) = self.clear() {
+            warn!("Failed to clear the address space: {:?}", e);
+        }
+    }
+}
@@ -1,11 +1,19 @@
+use alloc::collections::BTreeMap;
+
 use axalloc::global_allocator;
 use axhal::mem::{phys_to_virt, virt_to_phys};
 use axhal::paging::{MappingFlags, PageSize, PageTable};
+use kspin::SpinNoIrq;
 use memory_addr::{PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
 
 use super::Backend;
 
-fn alloc_frame(zeroed: bool) -> Option<PhysAddr> {
+/// Reference counts of the frames shared by more than one mapping.
+///
+/// Frames that are not in the table have only one owner.
+static SHARED_FRAMES: SpinNoIrq<BTreeMap<usize, usize>> = SpinNoIrq::new(BTreeMap::new());
+
+pub(super) fn alloc_frame(zeroed: bool) -> Option<PhysAddr> {
     let vaddr = VirtAddr::from(global_allocator().alloc_pages(1, PAGE_SIZE_4K).ok()?);
     if zeroed {
         unsafe { core::ptr::write_bytes(vaddr.as_mut_ptr(), 0, PAGE_SIZE_4K) };
@@ -14,11 +22,67 @@ fn alloc_frame(zeroed: bool) -> Option<PhysAddr> {
     Some(paddr)
 }
 
-fn dealloc_frame(frame: PhysAddr) {
+/// Drops a reference to the frame, and deallocates it if it was the last one.
+pub(super) fn dealloc_frame(frame: PhysAddr) {
+    {
+        let mut shared = SHARED_FRAMES.lock();
+        if let Some(count) = shared.get_mut(&frame.as_usize()) {
+            *count -= 1;
+            if *count == 1 {
+                shared.remove(&frame.as_usize());
+            }
+            return;
+        }
+    }
     let vaddr = phys_to_virt(frame);
     global_allocator().dealloc_pages(vaddr.as_usize(), 1);
 }
 
+/// Adds a reference to the frame, so it can be mapped once more.
+pub(crate) fn share_frame(frame: PhysAddr) {
+    *SHARED_FRAMES.lock().entry(frame.as_usize()).or_insert(1) += 1;
+}
+
+/// Returns the number of mappings that refer to the frame.
+pub(super) fn frame_ref_count(frame: PhysAddr) -> usize {
+    SHARED_FRAMES
+        .lock()
+        .get(&frame.as_usize())
+        .copied()
+        .unwrap_or(1)
+}
+
+/// Handles a write page fault on a copy-on-write page mapped to `frame`.
+///
+/// If the frame is still shared, the content is copied to a new frame that is
+/// private to the faulting mapping. Otherwise, the frame is simply remapped
+/// with the original (writable) flags.
+pub(crate) fn handle_cow_fault(
+    vaddr: VirtAddr,
+    frame: PhysAddr,
+    orig_flags: MappingFlags,
+    pt: &mut PageTable,
+) -> bool {
+    let new_frame = if frame_ref_count(frame) == 1 {
+        frame
+    } else if let Some(new_frame) = alloc_frame(false) {
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                phys_to_virt(frame).as_ptr(),
+                phys_to_virt(new_frame).as_mut_ptr(),
+                PAGE_SIZE_4K,
+            )
+        };
+        dealloc_frame(frame);
+        new_frame
+    } else {
+        return false;
+    };
+    pt.remap(vaddr, new_frame, orig_flags)
+        .map(|(_, tlb)| tlb.flush())
+        .is_ok()
+}
+
 impl Backend {
     /// Creates a new allocation mapping backend.
     pub const fn new_alloc(populate: bool) -> Self {
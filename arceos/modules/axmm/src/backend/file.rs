@@ -0,0 +1,188 @@
+use axfs_vfs::{VfsNodeRef, VfsResult};
+use axhal::mem::phys_to_virt;
+use axhal::paging::{MappingFlags, PageTable};
+use memory_addr::{MemoryAddr, PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use super::alloc::{alloc_frame, dealloc_frame};
+use super::Backend;
+
+/// The file backing a [`Backend::File`] mapping.
+#[derive(Clone)]
+pub struct FileBacking {
+    /// The file node to read pages from (and write back to).
+    node: VfsNodeRef,
+    /// The virtual address that corresponds to `offset` in the file.
+    ///
+    /// It remains unchanged when the area is split, so every page can still
+    /// find its file offset.
+    start: VirtAddr,
+    /// The file offset of `start`.
+    offset: u64,
+    /// Whether the modifications are carried through to the file
+    /// (`MAP_SHARED`), or kept private to the mapping (`MAP_PRIVATE`).
+    shared: bool,
+}
+
+impl FileBacking {
+    /// Whether the modifications are carried through to the file.
+    pub const fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    fn file_offset(&self, vaddr: VirtAddr) -> u64 {
+        self.offset + (vaddr.align_down_4k() - self.start) as u64
+    }
+
+    /// Reads the file content of the page at `vaddr` into `buf`. Bytes
+    /// beyond the end of the file are zero.
+    pub(crate) fn read_page(&self, vaddr: VirtAddr, buf: &mut [u8; PAGE_SIZE_4K]) -> VfsResult {
+        let offset = self.file_offset(vaddr);
+        let mut read = 0;
+        while read < PAGE_SIZE_4K {
+            match self.node.read_at(offset + read as u64, &mut buf[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        buf[read..].fill(0);
+        Ok(())
+    }
+
+    /// Allocates a frame and fills it with the file content of the page at
+    /// `vaddr`. Bytes beyond the end of the file are zero.
+    fn load_page(&self, vaddr: VirtAddr) -> Option<PhysAddr> {
+        let frame = alloc_frame(true)?;
+        let buf = unsafe { &mut *(phys_to_virt(frame).as_mut_ptr() as *mut [u8; PAGE_SIZE_4K]) };
+        if let Err(e) = self.read_page(vaddr, buf) {
+            warn!(
+                "failed to read file page at offset {:#x}: {:?}",
+                self.file_offset(vaddr),
+                e
+            );
+            dealloc_frame(frame);
+            return None;
+        }
+        Some(frame)
+    }
+
+    /// Writes the page at `vaddr` (backed by `frame`) back to the file.
+    ///
+    /// The file is never extended: only the part of the page within the
+    /// current file size is written.
+    fn write_back_page(&self, vaddr: VirtAddr, frame: PhysAddr) {
+        let offset = self.file_offset(vaddr);
+        let file_size = match self.node.get_attr() {
+            Ok(attr) => attr.size(),
+            Err(_) => return,
+        };
+        if offset >= file_size {
+            return;
+        }
+        let len = ((file_size - offset) as usize).min(PAGE_SIZE_4K);
+        let buf = unsafe { core::slice::from_raw_parts(phys_to_virt(frame).as_ptr(), len) };
+        if let Err(e) = self.node.write_at(offset, buf) {
+            warn!("failed to write back file page at offset {:#x}: {:?}", offset, e);
+        }
+    }
+}
+
+impl Backend {
+    /// Creates a new file mapping backend.
+    ///
+    /// The page at `start` is mapped to the file content at `offset`. If
+    /// `shared` is `true`, modifications are written back to the file on
+    /// [`msync`] or unmapping.
+    ///
+    /// [`msync`]: crate::AddrSpace::msync
+    pub fn new_file(node: VfsNodeRef, start: VirtAddr, offset: u64, shared: bool) -> Self {
+        Self::File(FileBacking {
+            node,
+            start,
+            offset,
+            shared,
+        })
+    }
+
+    pub(crate) fn map_file(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+        file: &FileBacking,
+    ) -> bool {
+        debug!(
+            "map_file: [{:#x}, {:#x}) {:?} (offset={:#x}, shared={})",
+            start,
+            start + size,
+            flags,
+            file.file_offset(start),
+            file.shared
+        );
+        // Map to a empty entry for on-demand mapping.
+        let flags = MappingFlags::empty();
+        pt.map_region(start, |_| 0.into(), size, flags, false, false)
+            .map(|tlb| tlb.ignore())
+            .is_ok()
+    }
+
+    pub(crate) fn unmap_file(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        pt: &mut PageTable,
+        file: &FileBacking,
+    ) -> bool {
+        debug!("unmap_file: [{:#x}, {:#x})", start, start + size);
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            if let Ok((frame, page_size, tlb)) = pt.unmap(addr) {
+                if page_size.is_huge() {
+                    return false;
+                }
+                tlb.flush();
+                if file.shared {
+                    file.write_back_page(addr, frame);
+                }
+                dealloc_frame(frame);
+            }
+        }
+        true
+    }
+
+    pub(crate) fn sync_file(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        pt: &PageTable,
+        file: &FileBacking,
+    ) {
+        if !file.shared {
+            return; // Nothing to do for private mappings.
+        }
+        debug!("sync_file: [{:#x}, {:#x})", start, start + size);
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            match pt.query(addr) {
+                // Pages that have never been touched are not populated yet.
+                Ok((frame, flags, _)) if !flags.is_empty() => file.write_back_page(addr, frame),
+                _ => {}
+            }
+        }
+    }
+
+    pub(crate) fn handle_page_fault_file(
+        &self,
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+        file: &FileBacking,
+    ) -> bool {
+        if let Some(frame) = file.load_page(vaddr) {
+            // Read the page lazily and map it to the fault address.
+            pt.remap(vaddr, frame, orig_flags)
+                .map(|(_, tlb)| tlb.flush())
+                .is_ok()
+        } else {
+            false
+        }
+    }
+}
@@ -2,20 +2,31 @@
 #![allow(dead_code)]
 
 use axhal::paging::{MappingFlags, PageTable};
-use memory_addr::VirtAddr;
+use memory_addr::{PageIter4K, VirtAddr};
 use memory_set::MappingBackend;
 
 mod alloc;
+mod file;
 mod linear;
+mod shared;
+
+pub(crate) use self::alloc::share_frame;
+pub use self::file::FileBacking;
+pub use self::shared::{SharedBacking, SharedPages};
 
 /// A unified enum type for different memory mapping backends.
 ///
-/// Currently, two backends are implemented:
+/// Currently, four backends are implemented:
 ///
 /// - **Linear**: used for linear mappings. The target physical frames are
 ///   contiguous and their addresses should be known when creating the mapping.
 /// - **Allocation**: used in general, or for lazy mappings. The target physical
 ///   frames are obtained from the global allocator.
+/// - **File**: used for file mappings. The target physical frames are obtained
+///   from the global allocator and filled with the file content.
+/// - **Shared**: used for shared memory. The target physical frames belong to
+///   a [`SharedPages`] object, which may be mapped into several address
+///   spaces.
 #[derive(Clone)]
 pub enum Backend {
     /// Linear mapping backend.
@@ -37,6 +48,18 @@ pub enum Backend {
         /// Whether to populate the physical frames when creating the mapping.
         populate: bool,
     },
+    /// File mapping backend.
+    ///
+    /// Physical frames are allocated like the allocation backend, and filled
+    /// with the content of the file. For shared mappings, the frames are
+    /// written back to the file when they are synchronized or unmapped.
+    File(FileBacking),
+    /// Shared memory backend.
+    ///
+    /// Physical frames are obtained from the [`SharedPages`] on demand, and
+    /// remain shared by all mappings of it, even across `fork`. They are
+    /// never copied on write.
+    Shared(SharedBacking),
 }
 
 impl MappingBackend for Backend {
@@ -47,6 +70,8 @@ impl MappingBackend for Backend {
         match *self {
             Self::Linear { pa_va_offset } => self.map_linear(start, size, flags, pt, pa_va_offset),
             Self::Alloc { populate } => self.map_alloc(start, size, flags, pt, populate),
+            Self::File(ref file) => self.map_file(start, size, flags, pt, file),
+            Self::Shared(ref shared) => self.map_shared(start, size, flags, pt, shared),
         }
     }
 
@@ -54,6 +79,8 @@ impl MappingBackend for Backend {
         match *self {
             Self::Linear { pa_va_offset } => self.unmap_linear(start, size, pt, pa_va_offset),
             Self::Alloc { populate } => self.unmap_alloc(start, size, pt, populate),
+            Self::File(ref file) => self.unmap_file(start, size, pt, file),
+            Self::Shared(_) => self.unmap_shared(start, size, pt),
         }
     }
 
@@ -64,25 +91,81 @@ impl MappingBackend for Backend {
         new_flags: Self::Flags,
         page_table: &mut Self::PageTable,
     ) -> bool {
-        page_table
-            .protect_region(start, size, new_flags, true)
-            .map(|tlb| tlb.ignore())
-            .is_ok()
+        // Pages that are not populated yet get `new_flags` when they are
+        // faulted in, so only the present ones are updated.
+        for vaddr in PageIter4K::new(start, start + size).unwrap() {
+            let Ok((frame, flags, _)) = page_table.query(vaddr) else {
+                continue;
+            };
+            if flags.is_empty() {
+                continue;
+            }
+            // Copy-on-write pages must stay read-only until they are copied.
+            let flags = if self.is_private() && alloc::frame_ref_count(frame) > 1 {
+                new_flags - MappingFlags::WRITE
+            } else {
+                new_flags
+            };
+            match page_table.protect(vaddr, flags) {
+                Ok((_, tlb)) => tlb.flush(),
+                Err(_) => return false,
+            }
+        }
+        true
     }
 }
 
 impl Backend {
+    /// Whether the pages are private to the mapping, i.e., they are copied
+    /// on write if shared with another address space.
+    fn is_private(&self) -> bool {
+        match self {
+            Self::Linear { .. } => false,
+            Self::Alloc { .. } => true,
+            Self::File(file) => !file.is_shared(),
+            Self::Shared(_) => false,
+        }
+    }
+
     pub(crate) fn handle_page_fault(
         &self,
         vaddr: VirtAddr,
         orig_flags: MappingFlags,
         page_table: &mut PageTable,
     ) -> bool {
+        if !matches!(*self, Self::Linear { .. } | Self::Shared(_)) {
+            match page_table.query(vaddr) {
+                // The page is present, so the fault can only be caused by
+                // writing to a copy-on-write page, whose write permission has
+                // been removed.
+                Ok((frame, flags, _)) if !flags.is_empty() => {
+                    return orig_flags.contains(MappingFlags::WRITE)
+                        && !flags.contains(MappingFlags::WRITE)
+                        && alloc::handle_cow_fault(vaddr, frame, orig_flags, page_table);
+                }
+                // Not populated yet.
+                _ => {}
+            }
+        }
         match *self {
             Self::Linear { .. } => false, // Linear mappings should not trigger page faults.
             Self::Alloc { populate } => {
                 self.handle_page_fault_alloc(vaddr, orig_flags, page_table, populate)
             }
+            Self::File(ref file) => {
+                self.handle_page_fault_file(vaddr, orig_flags, page_table, file)
+            }
+            Self::Shared(ref shared) => {
+                self.handle_page_fault_shared(vaddr, orig_flags, page_table, shared)
+            }
+        }
+    }
+
+    /// Writes the modified pages in the given range back to the backing
+    /// storage, if any.
+    pub(crate) fn sync(&self, start: VirtAddr, size: usize, page_table: &PageTable) {
+        if let Self::File(ref file) = *self {
+            self.sync_file(start, size, page_table, file)
         }
     }
 }
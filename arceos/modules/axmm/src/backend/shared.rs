@@ -0,0 +1,248 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+
+use axhal::mem::phys_to_virt;
+use axhal::paging::{MappingFlags, PageTable};
+use kspin::SpinNoIrq;
+use memory_addr::{MemoryAddr, PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use super::alloc::{alloc_frame, dealloc_frame};
+use super::Backend;
+
+/// A set of physical frames that can be mapped into several address spaces
+/// at once, e.g., for `MAP_SHARED | MAP_ANONYMOUS`, `memfd_create` or System
+/// V shared memory.
+///
+/// Frames are allocated (zeroed) on first access, and owned by the object
+/// rather than by the mappings: they are released when the last reference
+/// is dropped, i.e., when all mappings are gone and the owner (a file
+/// descriptor or a shared memory segment) has let it go.
+pub struct SharedPages {
+    inner: SpinNoIrq<SharedPagesInner>,
+}
+
+struct SharedPagesInner {
+    /// The size in bytes. Pages beyond it cannot be faulted in.
+    size: usize,
+    /// The allocated frames, keyed by page index.
+    frames: BTreeMap<usize, PhysAddr>,
+}
+
+impl SharedPages {
+    /// Creates an object of `size` bytes, with no frames allocated yet.
+    pub fn new(size: usize) -> Arc<Self> {
+        Arc::new(Self {
+            inner: SpinNoIrq::new(SharedPagesInner {
+                size,
+                frames: BTreeMap::new(),
+            }),
+        })
+    }
+
+    /// Returns the size in bytes.
+    pub fn size(&self) -> usize {
+        self.inner.lock().size
+    }
+
+    /// Resizes the object to `size` bytes, as `ftruncate` does.
+    ///
+    /// The frames beyond the new size are zeroed rather than released, as
+    /// they may still be mapped somewhere. They read as zeros if the object
+    /// grows again.
+    pub fn set_size(&self, size: usize) {
+        let mut inner = self.inner.lock();
+        if size < inner.size {
+            let first = size / PAGE_SIZE_4K;
+            for (&index, &frame) in inner.frames.range(first..) {
+                let start = if index == first {
+                    size % PAGE_SIZE_4K
+                } else {
+                    0
+                };
+                unsafe {
+                    core::ptr::write_bytes(
+                        phys_to_virt(frame).as_mut_ptr().add(start),
+                        0,
+                        PAGE_SIZE_4K - start,
+                    )
+                };
+            }
+        }
+        inner.size = size;
+    }
+
+    /// Returns the frame of the page `index`, allocating it if needed.
+    ///
+    /// Returns `None` if the page is beyond the size, or out of memory.
+    fn frame(&self, index: usize) -> Option<PhysAddr> {
+        let mut inner = self.inner.lock();
+        if index >= inner.size.div_ceil(PAGE_SIZE_4K) {
+            return None;
+        }
+        if let Some(&frame) = inner.frames.get(&index) {
+            return Some(frame);
+        }
+        let frame = alloc_frame(true)?;
+        inner.frames.insert(index, frame);
+        Some(frame)
+    }
+
+    /// Reads the content at `offset` into `buf`, stopping at the end of the
+    /// object.
+    ///
+    /// Returns the number of bytes read.
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let len = buf.len().min(self.size().saturating_sub(offset));
+        let mut read = 0;
+        while read < len {
+            let pos = offset + read;
+            let n = (PAGE_SIZE_4K - pos % PAGE_SIZE_4K).min(len - read);
+            let dst = &mut buf[read..read + n];
+            // Pages that have never been touched are zero.
+            match self.inner.lock().frames.get(&(pos / PAGE_SIZE_4K)) {
+                Some(&frame) => unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        phys_to_virt(frame).as_ptr().add(pos % PAGE_SIZE_4K),
+                        dst.as_mut_ptr(),
+                        n,
+                    )
+                },
+                None => dst.fill(0),
+            }
+            read += n;
+        }
+        len
+    }
+
+    /// Writes `buf` at `offset`, growing the object if it ends beyond the
+    /// size.
+    ///
+    /// Returns the number of bytes written, which is less than `buf.len()`
+    /// only if out of memory.
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        {
+            let mut inner = self.inner.lock();
+            inner.size = inner.size.max(offset + buf.len());
+        }
+        let mut written = 0;
+        while written < buf.len() {
+            let pos = offset + written;
+            let n = (PAGE_SIZE_4K - pos % PAGE_SIZE_4K).min(buf.len() - written);
+            let Some(frame) = self.frame(pos / PAGE_SIZE_4K) else {
+                break;
+            };
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    buf[written..].as_ptr(),
+                    phys_to_virt(frame).as_mut_ptr().add(pos % PAGE_SIZE_4K),
+                    n,
+                )
+            };
+            written += n;
+        }
+        written
+    }
+}
+
+impl Drop for SharedPages {
+    fn drop(&mut self) {
+        for &frame in self.inner.get_mut().frames.values() {
+            dealloc_frame(frame);
+        }
+    }
+}
+
+/// The shared pages behind a [`Backend::Shared`] mapping.
+#[derive(Clone)]
+pub struct SharedBacking {
+    pages: Arc<SharedPages>,
+    /// The virtual address that corresponds to `offset` in the pages.
+    ///
+    /// It remains unchanged when the area is split, as in
+    /// [`FileBacking`](super::FileBacking).
+    start: VirtAddr,
+    /// The offset of `start` in the pages.
+    offset: usize,
+}
+
+impl SharedBacking {
+    /// Returns the shared pages.
+    pub fn pages(&self) -> &Arc<SharedPages> {
+        &self.pages
+    }
+
+    /// Returns the virtual address that the start of the pages is mapped to,
+    /// i.e., the address of offset 0.
+    pub fn base(&self) -> VirtAddr {
+        VirtAddr::from(self.start.as_usize().wrapping_sub(self.offset))
+    }
+
+    pub(crate) fn page_index(&self, vaddr: VirtAddr) -> usize {
+        (self.offset + (vaddr.align_down_4k() - self.start)) / PAGE_SIZE_4K
+    }
+}
+
+impl Backend {
+    /// Creates a new shared mapping backend.
+    ///
+    /// The page at `start` is mapped to the shared pages at `offset`.
+    pub fn new_shared(pages: Arc<SharedPages>, start: VirtAddr, offset: usize) -> Self {
+        Self::Shared(SharedBacking {
+            pages,
+            start,
+            offset,
+        })
+    }
+
+    pub(crate) fn map_shared(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+        shared: &SharedBacking,
+    ) -> bool {
+        debug!(
+            "map_shared: [{:#x}, {:#x}) {:?} (page={})",
+            start,
+            start + size,
+            flags,
+            shared.page_index(start)
+        );
+        // Map to a empty entry for on-demand mapping.
+        let flags = MappingFlags::empty();
+        pt.map_region(start, |_| 0.into(), size, flags, false, false)
+            .map(|tlb| tlb.ignore())
+            .is_ok()
+    }
+
+    pub(crate) fn unmap_shared(&self, start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
+        debug!("unmap_shared: [{:#x}, {:#x})", start, start + size);
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            if let Ok((_, page_size, tlb)) = pt.unmap(addr) {
+                if page_size.is_huge() {
+                    return false;
+                }
+                // The frame belongs to the shared pages.
+                tlb.flush();
+            }
+        }
+        true
+    }
+
+    pub(crate) fn handle_page_fault_shared(
+        &self,
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+        shared: &SharedBacking,
+    ) -> bool {
+        if let Some(frame) = shared.pages.frame(shared.page_index(vaddr)) {
+            pt.remap(vaddr, frame, orig_flags)
+                .map(|(_, tlb)| tlb.flush())
+                .is_ok()
+        } else {
+            false
+        }
+    }
+}
@@ -10,6 +10,7 @@ mod aspace;
 mod backend;
 
 pub use self::aspace::AddrSpace;
+pub use self::backend::SharedPages;
 
 use axerrno::{AxError, AxResult};
 use axhal::mem::phys_to_virt;
@@ -47,7 +48,11 @@ fn paging_err_to_ax_err(err: PagingError) -> AxError {
 /// Creates a new address space for user processes.
 pub fn new_user_aspace() -> AxResult<AddrSpace> {
     let mut aspace = AddrSpace::new_empty(VirtAddr::from(USER_ASPACE_BASE), USER_ASPACE_SIZE)?;
-    aspace.copy_mappings_from(&kernel_aspace().lock())?;
+    if !cfg!(target_arch = "aarch64") {
+        // On aarch64, the kernel is mapped by a separate page table
+        // (`TTBR1_EL1`), so it need not be copied to the user page table.
+        aspace.copy_mappings_from(&kernel_aspace().lock())?;
+    }
     Ok(aspace)
 }
 
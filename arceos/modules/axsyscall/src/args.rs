@@ -0,0 +1,150 @@
+use axhal::arch::TrapFrame;
+
+/// Arguments of a syscall, read from the trap frame.
+#[derive(Clone, Copy)]
+pub struct SyscallArgs<'a> {
+    tf: &'a TrapFrame,
+}
+
+impl<'a> SyscallArgs<'a> {
+    /// Creates the arguments of the syscall trapped with `tf`.
+    pub const fn new(tf: &'a TrapFrame) -> Self {
+        Self { tf }
+    }
+
+    /// The trap frame of the syscall.
+    pub const fn trap_frame(&self) -> &'a TrapFrame {
+        self.tf
+    }
+
+    /// Returns the `i`-th argument as a raw register value.
+    pub const fn raw(&self, i: usize) -> usize {
+        match i {
+            0 => self.tf.arg0(),
+            1 => self.tf.arg1(),
+            2 => self.tf.arg2(),
+            3 => self.tf.arg3(),
+            4 => self.tf.arg4(),
+            5 => self.tf.arg5(),
+            _ => panic!("syscall argument index out of range"),
+        }
+    }
+
+    /// Returns the `i`-th argument converted to `T`.
+    pub fn arg<T: FromArg>(&self, i: usize) -> T {
+        T::from_arg(self.raw(i))
+    }
+}
+
+/// Types that a syscall argument can be decoded as.
+pub trait FromArg {
+    /// Converts the raw register value to `Self`.
+    fn from_arg(raw: usize) -> Self;
+}
+
+macro_rules! impl_from_arg {
+    ($($t:ty),*) => {$(
+        impl FromArg for $t {
+            fn from_arg(raw: usize) -> Self {
+                raw as _
+            }
+        }
+    )*};
+}
+
+impl_from_arg!(i8, u8, i16, u16, i32, u32, i64, u64, isize, usize);
+
+impl<T> FromArg for *const T {
+    fn from_arg(raw: usize) -> Self {
+        raw as _
+    }
+}
+
+impl<T> FromArg for *mut T {
+    fn from_arg(raw: usize) -> Self {
+        raw as _
+    }
+}
+
+/// How to print a syscall argument when tracing.
+#[derive(Clone, Copy, Debug)]
+pub enum ArgFmt {
+    /// A signed integer, e.g., a file descriptor.
+    Int,
+    /// An unsigned integer, e.g., a length.
+    Uint,
+    /// A hexadecimal integer, e.g., an address.
+    Hex,
+    /// An octal integer, e.g., a file mode.
+    Oct,
+    /// A user pointer, printed as `NULL` if it is zero.
+    Ptr,
+    /// A NUL-terminated user string.
+    Str,
+    /// A bit set, printed as the `|`-separated names of the set bits.
+    Flags(&'static [(&'static str, usize)]),
+}
+
+// `O_DIRECTORY` and `O_NOFOLLOW` have other values on AArch64.
+#[cfg(target_arch = "aarch64")]
+const O_DIRECTORY: usize = 0o40000;
+#[cfg(target_arch = "aarch64")]
+const O_NOFOLLOW: usize = 0o100000;
+#[cfg(not(target_arch = "aarch64"))]
+const O_DIRECTORY: usize = 0o200000;
+#[cfg(not(target_arch = "aarch64"))]
+const O_NOFOLLOW: usize = 0o400000;
+
+/// Flags of `openat`.
+pub const OPEN_FLAGS: &[(&str, usize)] = &[
+    ("O_WRONLY", 0o1),
+    ("O_RDWR", 0o2),
+    ("O_CREAT", 0o100),
+    ("O_EXCL", 0o200),
+    ("O_NOCTTY", 0o400),
+    ("O_TRUNC", 0o1000),
+    ("O_APPEND", 0o2000),
+    ("O_NONBLOCK", 0o4000),
+    ("O_DIRECTORY", O_DIRECTORY),
+    ("O_NOFOLLOW", O_NOFOLLOW),
+    ("O_CLOEXEC", 0o2000000),
+];
+
+/// Flags of the `*at` syscalls, e.g., `newfstatat` and `unlinkat`.
+pub const AT_FLAGS: &[(&str, usize)] = &[
+    ("AT_SYMLINK_NOFOLLOW", 0x100),
+    ("AT_REMOVEDIR", 0x200),
+    ("AT_EMPTY_PATH", 0x1000),
+];
+
+/// Flags of `clone`, except the exit signal in the low byte.
+pub const CLONE_FLAGS: &[(&str, usize)] = &[
+    ("CLONE_VM", 0x100),
+    ("CLONE_FS", 0x200),
+    ("CLONE_FILES", 0x400),
+    ("CLONE_SIGHAND", 0x800),
+    ("CLONE_VFORK", 0x4000),
+    ("CLONE_PARENT", 0x8000),
+    ("CLONE_THREAD", 0x10000),
+    ("CLONE_SETTLS", 0x80000),
+    ("CLONE_PARENT_SETTID", 0x100000),
+    ("CLONE_CHILD_CLEARTID", 0x200000),
+    ("CLONE_CHILD_SETTID", 0x1000000),
+];
+
+/// Memory protection flags of `mmap`.
+pub const PROT_FLAGS: &[(&str, usize)] = &[
+    ("PROT_READ", 0x1),
+    ("PROT_WRITE", 0x2),
+    ("PROT_EXEC", 0x4),
+];
+
+/// Flags of `mmap`.
+pub const MAP_FLAGS: &[(&str, usize)] = &[
+    ("MAP_SHARED", 0x1),
+    ("MAP_PRIVATE", 0x2),
+    ("MAP_FIXED", 0x10),
+    ("MAP_ANONYMOUS", 0x20),
+    ("MAP_NORESERVE", 0x4000),
+    ("MAP_STACK", 0x20000),
+];
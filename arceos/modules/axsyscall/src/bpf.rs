@@ -0,0 +1,229 @@
+//! Classic BPF programs that filter syscalls, as seccomp runs them.
+//!
+//! A program is checked once by [`check_filter`] when it is installed, and
+//! then run by [`run_filter`] on the [`SeccompData`] of each syscall to
+//! decide an action (`SECCOMP_RET_*`). Only the instructions that seccomp
+//! allows are accepted, and loads are limited to aligned words of
+//! `struct seccomp_data`.
+
+use axerrno::{LinuxError, LinuxResult};
+
+/// The maximum number of instructions of a filter.
+pub const BPF_MAXINSNS: usize = 4096;
+/// The number of scratch memory words of a BPF program.
+pub const BPF_MEMWORDS: usize = 16;
+
+// Classic BPF instruction encoding.
+pub const BPF_LD: u16 = 0x00;
+pub const BPF_LDX: u16 = 0x01;
+pub const BPF_ST: u16 = 0x02;
+pub const BPF_STX: u16 = 0x03;
+pub const BPF_ALU: u16 = 0x04;
+pub const BPF_JMP: u16 = 0x05;
+pub const BPF_RET: u16 = 0x06;
+pub const BPF_MISC: u16 = 0x07;
+
+pub const BPF_W: u16 = 0x00;
+pub const BPF_IMM: u16 = 0x00;
+pub const BPF_ABS: u16 = 0x20;
+pub const BPF_MEM: u16 = 0x60;
+pub const BPF_LEN: u16 = 0x80;
+
+pub const BPF_ADD: u16 = 0x00;
+pub const BPF_SUB: u16 = 0x10;
+pub const BPF_MUL: u16 = 0x20;
+pub const BPF_DIV: u16 = 0x30;
+pub const BPF_OR: u16 = 0x40;
+pub const BPF_AND: u16 = 0x50;
+pub const BPF_LSH: u16 = 0x60;
+pub const BPF_RSH: u16 = 0x70;
+pub const BPF_NEG: u16 = 0x80;
+pub const BPF_MOD: u16 = 0x90;
+pub const BPF_XOR: u16 = 0xa0;
+
+pub const BPF_JA: u16 = 0x00;
+pub const BPF_JEQ: u16 = 0x10;
+pub const BPF_JGT: u16 = 0x20;
+pub const BPF_JGE: u16 = 0x30;
+pub const BPF_JSET: u16 = 0x40;
+
+pub const BPF_K: u16 = 0x00;
+pub const BPF_X: u16 = 0x08;
+/// The return value of `BPF_RET` is the accumulator.
+pub const BPF_A: u16 = 0x10;
+
+pub const BPF_TAX: u16 = 0x00;
+pub const BPF_TXA: u16 = 0x80;
+
+/// `struct sock_filter`, a classic BPF instruction.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SockFilter {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+impl SockFilter {
+    /// An instruction that does not jump (`BPF_STMT`).
+    pub const fn stmt(code: u16, k: u32) -> Self {
+        Self {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    /// A conditional jump (`BPF_JUMP`) to `jt` or `jf` instructions ahead.
+    pub const fn jump(code: u16, k: u32, jt: u8, jf: u8) -> Self {
+        Self { code, jt, jf, k }
+    }
+}
+
+/// `struct seccomp_data`, the input of a filter.
+#[repr(C)]
+pub struct SeccompData {
+    /// The syscall number.
+    pub nr: i32,
+    /// The `AUDIT_ARCH_*` value of the architecture.
+    pub arch: u32,
+    pub instruction_pointer: u64,
+    pub args: [u64; 6],
+}
+
+/// Reads the 32-bit word of `data` at `offset`, which has been checked to be
+/// aligned and in range. 64-bit fields are little-endian.
+fn load_word(data: &SeccompData, offset: usize) -> u32 {
+    let half = |val: u64| (val >> (offset % 8 * 8)) as u32;
+    match offset {
+        0 => data.nr as u32,
+        4 => data.arch,
+        8 | 12 => half(data.instruction_pointer),
+        _ => half(data.args[(offset - 16) / 8]),
+    }
+}
+
+/// Runs a filter checked by [`check_filter`] on `data`, and returns the
+/// action.
+pub fn run_filter(prog: &[SockFilter], data: &SeccompData) -> u32 {
+    let (mut a, mut x) = (0u32, 0u32);
+    let mut mem = [0u32; BPF_MEMWORDS];
+    let mut pc = 0;
+    loop {
+        let insn = prog[pc];
+        let k = insn.k;
+        pc += 1;
+        match insn.code & 0x07 {
+            BPF_LD => {
+                a = match insn.code & 0xe0 {
+                    BPF_ABS => load_word(data, k as usize),
+                    BPF_LEN => core::mem::size_of::<SeccompData>() as u32,
+                    BPF_MEM => mem[k as usize],
+                    _ => k,
+                }
+            }
+            BPF_LDX => {
+                x = match insn.code & 0xe0 {
+                    BPF_LEN => core::mem::size_of::<SeccompData>() as u32,
+                    BPF_MEM => mem[k as usize],
+                    _ => k,
+                }
+            }
+            BPF_ST => mem[k as usize] = a,
+            BPF_STX => mem[k as usize] = x,
+            BPF_ALU => {
+                let src = if insn.code & BPF_X != 0 { x } else { k };
+                a = match insn.code & 0xf0 {
+                    BPF_ADD => a.wrapping_add(src),
+                    BPF_SUB => a.wrapping_sub(src),
+                    BPF_MUL => a.wrapping_mul(src),
+                    // Division by a zero `X` ends the program with 0.
+                    BPF_DIV if src == 0 => return 0,
+                    BPF_DIV => a / src,
+                    BPF_MOD if src == 0 => return 0,
+                    BPF_MOD => a % src,
+                    BPF_OR => a | src,
+                    BPF_AND => a & src,
+                    BPF_XOR => a ^ src,
+                    BPF_LSH => a.checked_shl(src).unwrap_or(0),
+                    BPF_RSH => a.checked_shr(src).unwrap_or(0),
+                    _ => a.wrapping_neg(), // BPF_NEG
+                }
+            }
+            BPF_JMP => {
+                let src = if insn.code & BPF_X != 0 { x } else { k };
+                let cond = match insn.code & 0xf0 {
+                    BPF_JA => {
+                        pc += k as usize;
+                        continue;
+                    }
+                    BPF_JEQ => a == src,
+                    BPF_JGT => a > src,
+                    BPF_JGE => a >= src,
+                    _ => a & src != 0, // BPF_JSET
+                };
+                pc += if cond { insn.jt } else { insn.jf } as usize;
+            }
+            BPF_RET => return if insn.code & BPF_A != 0 { a } else { k },
+            _ => {
+                if insn.code & 0xf8 == BPF_TXA {
+                    a = x;
+                } else {
+                    x = a;
+                }
+            }
+        }
+    }
+}
+
+/// Checks that `prog` only uses the instructions that seccomp allows, only
+/// loads aligned words of `seccomp_data`, never jumps out of the program,
+/// and ends with a return.
+pub fn check_filter(prog: &[SockFilter]) -> LinuxResult {
+    let data_len = core::mem::size_of::<SeccompData>() as u32;
+    for (pc, insn) in prog.iter().enumerate() {
+        let remaining = prog.len() - pc - 1;
+        let code = insn.code;
+        let k = insn.k;
+        let valid = match code {
+            c if c == BPF_LD | BPF_W | BPF_ABS => k % 4 == 0 && k < data_len,
+            c if c == BPF_LD | BPF_W | BPF_LEN || c == BPF_LDX | BPF_W | BPF_LEN => true,
+            c if c == BPF_LD | BPF_IMM || c == BPF_LDX | BPF_IMM => true,
+            c if c == BPF_LD | BPF_MEM || c == BPF_LDX | BPF_MEM => (k as usize) < BPF_MEMWORDS,
+            BPF_ST | BPF_STX => (k as usize) < BPF_MEMWORDS,
+            c if c & 0x07 == BPF_ALU => match c & !0x07 {
+                op if op == BPF_DIV | BPF_K || op == BPF_MOD | BPF_K => k != 0,
+                op if op == BPF_NEG => true,
+                op => {
+                    let alu = op & 0xf0;
+                    op & !(0xf0 | BPF_X) == 0
+                        && [
+                            BPF_ADD, BPF_SUB, BPF_MUL, BPF_DIV, BPF_MOD, BPF_OR, BPF_AND, BPF_XOR,
+                            BPF_LSH, BPF_RSH,
+                        ]
+                        .contains(&alu)
+                }
+            },
+            c if c == BPF_JMP | BPF_JA => (k as usize) < remaining,
+            c if c & 0x07 == BPF_JMP => {
+                let op = c & !0x07;
+                op & !(0xf0 | BPF_X) == 0
+                    && [BPF_JEQ, BPF_JGT, BPF_JGE, BPF_JSET].contains(&(op & 0xf0))
+                    && (insn.jt as usize) < remaining
+                    && (insn.jf as usize) < remaining
+            }
+            c if c == BPF_RET | BPF_K || c == BPF_RET | BPF_A => true,
+            c if c == BPF_MISC | BPF_TAX || c == BPF_MISC | BPF_TXA => true,
+            _ => false,
+        };
+        if !valid {
+            return Err(LinuxError::EINVAL);
+        }
+    }
+    match prog.last() {
+        Some(insn) if insn.code & 0x07 == BPF_RET => Ok(()),
+        _ => Err(LinuxError::EINVAL),
+    }
+}
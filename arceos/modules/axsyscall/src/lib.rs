@@ -0,0 +1,77 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) syscall layer for monolithic
+//! kernels.
+//!
+//! A kernel registers each of its syscalls once into a [`SyscallTable`], then
+//! forwards every `SYSCALL` trap to [`dispatch`]. Handlers decode their typed
+//! arguments with [`SyscallArgs::arg`].
+//!
+//! When tracing is enabled by [`set_tracing`], every syscall is printed in the
+//! style of `strace`, with its decoded arguments and its return value or
+//! errno.
+//!
+//! Handlers access user memory through [`UserPtr`] and [`UserSlice`], which
+//! return `EFAULT` on bad pointers instead of crashing the kernel.
+
+#![cfg_attr(not(test), no_std)]
+
+#[macro_use]
+extern crate axlog;
+extern crate alloc;
+
+mod args;
+mod table;
+mod trace;
+mod uaccess;
+
+#[cfg(test)]
+mod tests;
+
+pub mod bpf;
+pub mod sysno;
+
+pub use self::args::{ArgFmt, FromArg, SyscallArgs};
+pub use self::args::{AT_FLAGS, CLONE_FLAGS, MAP_FLAGS, OPEN_FLAGS, PROT_FLAGS};
+pub use self::table::{Syscall, SyscallHandler, SyscallTable};
+pub use self::trace::{is_tracing, set_tracing};
+pub use self::uaccess::{copy_from_user, copy_to_user, set_current_aspace_fn, UserPtr, UserSlice};
+
+use axhal::arch::TrapFrame;
+use lazyinit::LazyInit;
+
+static SYSCALL_TABLE: LazyInit<SyscallTable> = LazyInit::new();
+
+/// Installs the syscall table used by [`dispatch`].
+///
+/// It must be called once before the first user task starts.
+pub fn init(table: SyscallTable) {
+    SYSCALL_TABLE.init_once(table);
+}
+
+/// Handles the syscall `sysno` trapped with `tf`, and returns its result.
+///
+/// Unknown syscalls return `-ENOSYS`.
+pub fn dispatch(tf: &TrapFrame, sysno: usize) -> isize {
+    SYSCALL_TABLE.dispatch(tf, sysno)
+}
+
+/// Macro to generate syscall body
+///
+/// It will receive a function which return Result<_, LinuxError> and convert it to
+/// the type which is specified by the caller.
+#[macro_export]
+macro_rules! syscall_body {
+    ($fn: ident, $($stmt: tt)*) => {{
+        #[allow(clippy::redundant_closure_call)]
+        let res = (|| -> axerrno::LinuxResult<_> { $($stmt)* })();
+        match res {
+            Ok(_) | Err(axerrno::LinuxError::EAGAIN) => debug!(concat!(stringify!($fn), " => {:?}"),  res),
+            Err(_) => info!(concat!(stringify!($fn), " => {:?}"), res),
+        }
+        match res {
+            Ok(v) => v as _,
+            Err(e) => {
+                -e.code() as _
+            }
+        }
+    }};
+}
@@ -0,0 +1,15 @@
+//! Linux syscall numbers of the target architecture.
+//!
+//! riscv64 and aarch64 share the generic table. x86_64 has its own table,
+//! which also includes legacy syscalls (e.g., `open` and `stat`) that the
+//! generic table only provides as their `*at` variants.
+
+#[cfg(any(target_arch = "riscv64", target_arch = "aarch64"))]
+mod generic;
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+
+#[cfg(any(target_arch = "riscv64", target_arch = "aarch64"))]
+pub use self::generic::*;
+#[cfg(target_arch = "x86_64")]
+pub use self::x86_64::*;
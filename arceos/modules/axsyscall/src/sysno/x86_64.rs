@@ -0,0 +1,92 @@
+//! Syscall numbers of x86_64.
+//!
+//! See <https://github.com/torvalds/linux/blob/master/arch/x86/entry/syscalls/syscall_64.tbl>
+
+pub const SYS_READ: usize = 0;
+pub const SYS_WRITE: usize = 1;
+pub const SYS_OPEN: usize = 2;
+pub const SYS_CLOSE: usize = 3;
+pub const SYS_STAT: usize = 4;
+pub const SYS_FSTAT: usize = 5;
+pub const SYS_LSTAT: usize = 6;
+pub const SYS_MMAP: usize = 9;
+pub const SYS_MPROTECT: usize = 10;
+pub const SYS_MUNMAP: usize = 11;
+pub const SYS_BRK: usize = 12;
+pub const SYS_RT_SIGACTION: usize = 13;
+pub const SYS_RT_SIGPROCMASK: usize = 14;
+pub const SYS_RT_SIGRETURN: usize = 15;
+pub const SYS_IOCTL: usize = 16;
+pub const SYS_WRITEV: usize = 20;
+pub const SYS_ACCESS: usize = 21;
+pub const SYS_MREMAP: usize = 25;
+pub const SYS_MSYNC: usize = 26;
+pub const SYS_SHMGET: usize = 29;
+pub const SYS_SHMAT: usize = 30;
+pub const SYS_SHMCTL: usize = 31;
+pub const SYS_DUP: usize = 32;
+pub const SYS_GETPID: usize = 39;
+pub const SYS_CLONE: usize = 56;
+pub const SYS_EXECVE: usize = 59;
+pub const SYS_EXIT: usize = 60;
+pub const SYS_WAIT4: usize = 61;
+pub const SYS_KILL: usize = 62;
+pub const SYS_SHMDT: usize = 67;
+pub const SYS_FCNTL: usize = 72;
+pub const SYS_FTRUNCATE: usize = 77;
+pub const SYS_RENAME: usize = 82;
+pub const SYS_MKDIR: usize = 83;
+pub const SYS_RMDIR: usize = 84;
+pub const SYS_UNLINK: usize = 87;
+pub const SYS_READLINK: usize = 89;
+pub const SYS_CHMOD: usize = 90;
+pub const SYS_FCHMOD: usize = 91;
+pub const SYS_CHOWN: usize = 92;
+pub const SYS_FCHOWN: usize = 93;
+pub const SYS_LCHOWN: usize = 94;
+pub const SYS_GETTIMEOFDAY: usize = 96;
+pub const SYS_GETRLIMIT: usize = 97;
+pub const SYS_GETUID: usize = 102;
+pub const SYS_GETGID: usize = 104;
+pub const SYS_SETUID: usize = 105;
+pub const SYS_SETGID: usize = 106;
+pub const SYS_GETEUID: usize = 107;
+pub const SYS_GETEGID: usize = 108;
+pub const SYS_SETPGID: usize = 109;
+pub const SYS_GETPPID: usize = 110;
+pub const SYS_SETREUID: usize = 113;
+pub const SYS_SETREGID: usize = 114;
+pub const SYS_GETGROUPS: usize = 115;
+pub const SYS_SETGROUPS: usize = 116;
+pub const SYS_SETRESUID: usize = 117;
+pub const SYS_GETRESUID: usize = 118;
+pub const SYS_SETRESGID: usize = 119;
+pub const SYS_GETRESGID: usize = 120;
+pub const SYS_GETPGID: usize = 121;
+pub const SYS_RT_SIGPENDING: usize = 127;
+pub const SYS_PRCTL: usize = 157;
+pub const SYS_ARCH_PRCTL: usize = 158;
+pub const SYS_SETRLIMIT: usize = 160;
+pub const SYS_GETTID: usize = 186;
+pub const SYS_TKILL: usize = 200;
+pub const SYS_FUTEX: usize = 202;
+pub const SYS_GETDENTS64: usize = 217;
+pub const SYS_SET_TID_ADDRESS: usize = 218;
+pub const SYS_CLOCK_GETTIME: usize = 228;
+pub const SYS_EXIT_GROUP: usize = 231;
+pub const SYS_TGKILL: usize = 234;
+pub const SYS_OPENAT: usize = 257;
+pub const SYS_MKDIRAT: usize = 258;
+pub const SYS_FCHOWNAT: usize = 260;
+pub const SYS_NEWFSTATAT: usize = 262;
+pub const SYS_UNLINKAT: usize = 263;
+pub const SYS_RENAMEAT: usize = 264;
+pub const SYS_READLINKAT: usize = 267;
+pub const SYS_FCHMODAT: usize = 268;
+pub const SYS_FACCESSAT: usize = 269;
+pub const SYS_DUP3: usize = 292;
+pub const SYS_PRLIMIT64: usize = 302;
+pub const SYS_GETCPU: usize = 309;
+pub const SYS_RENAMEAT2: usize = 316;
+pub const SYS_SECCOMP: usize = 317;
+pub const SYS_MEMFD_CREATE: usize = 319;
@@ -0,0 +1,112 @@
+use alloc::collections::BTreeMap;
+
+use axerrno::LinuxError;
+use axhal::arch::TrapFrame;
+
+use crate::args::{ArgFmt, SyscallArgs};
+use crate::trace;
+
+/// A syscall handler, which decodes its own arguments.
+pub type SyscallHandler = for<'a> fn(&SyscallArgs<'a>) -> isize;
+
+/// A syscall registered in a [`SyscallTable`].
+pub struct Syscall {
+    name: &'static str,
+    args: &'static [ArgFmt],
+    handler: SyscallHandler,
+    noreturn: bool,
+}
+
+impl Syscall {
+    /// Creates a syscall named `name`, whose arguments are printed with
+    /// `args` when tracing.
+    pub const fn new(name: &'static str, args: &'static [ArgFmt], handler: SyscallHandler) -> Self {
+        Self {
+            name,
+            args,
+            handler,
+            noreturn: false,
+        }
+    }
+
+    /// Marks the syscall as never returning on success (e.g., `exit`), so the
+    /// tracer prints it before it runs.
+    pub const fn noreturn(mut self) -> Self {
+        self.noreturn = true;
+        self
+    }
+
+    /// The name of the syscall.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// How the arguments are printed when tracing.
+    pub const fn args(&self) -> &'static [ArgFmt] {
+        self.args
+    }
+
+    /// Whether the syscall returns on success.
+    pub const fn is_noreturn(&self) -> bool {
+        self.noreturn
+    }
+}
+
+/// Syscalls keyed by their syscall numbers.
+pub struct SyscallTable {
+    syscalls: BTreeMap<usize, Syscall>,
+}
+
+impl SyscallTable {
+    /// Creates an empty syscall table.
+    pub const fn new() -> Self {
+        Self {
+            syscalls: BTreeMap::new(),
+        }
+    }
+
+    /// Registers `syscall` with the syscall number `sysno`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sysno` is already registered.
+    pub fn register(&mut self, sysno: usize, syscall: Syscall) -> &mut Self {
+        if let Some(old) = self.syscalls.insert(sysno, syscall) {
+            panic!("syscall {} ({}) registered twice", sysno, old.name);
+        }
+        self
+    }
+
+    /// Returns the syscall with the syscall number `sysno`.
+    pub fn get(&self, sysno: usize) -> Option<&Syscall> {
+        self.syscalls.get(&sysno)
+    }
+
+    /// Handles the syscall `sysno` trapped with `tf`, and returns its result.
+    ///
+    /// Unknown syscalls return `-ENOSYS`.
+    pub fn dispatch(&self, tf: &TrapFrame, sysno: usize) -> isize {
+        let args = SyscallArgs::new(tf);
+        let Some(syscall) = self.get(sysno) else {
+            warn!("Unimplemented syscall: {}", sysno);
+            return -LinuxError::ENOSYS.code() as _;
+        };
+        if !trace::is_tracing() {
+            return (syscall.handler)(&args);
+        }
+
+        let call = trace::format_call(syscall, &args);
+        if syscall.noreturn {
+            trace::print_noreturn(&call);
+        }
+        let ret = (syscall.handler)(&args);
+        trace::print_ret(&call, ret);
+        ret
+    }
+}
+
+impl Default for SyscallTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,289 @@
+use alloc::string::String;
+
+use axerrno::LinuxError;
+
+use crate::trace::format_arg;
+use crate::{ArgFmt, FromArg, Syscall, SyscallTable, UserPtr};
+use crate::{CLONE_FLAGS, MAP_FLAGS, OPEN_FLAGS, PROT_FLAGS};
+
+fn format(fmt: ArgFmt, raw: usize) -> String {
+    let mut s = String::new();
+    format_arg(&mut s, fmt, raw).unwrap();
+    s
+}
+
+#[test]
+fn test_from_arg() {
+    assert_eq!(i32::from_arg(usize::MAX), -1);
+    assert_eq!(isize::from_arg(-100isize as usize), -100);
+    assert_eq!(u32::from_arg(0x1_0000_0005), 5);
+    assert_eq!(u8::from_arg(0x1ff), 0xff);
+    assert_eq!(<*const u8>::from_arg(0x1000) as usize, 0x1000);
+}
+
+#[test]
+fn test_format_int() {
+    assert_eq!(format(ArgFmt::Int, -100isize as usize), "-100");
+    assert_eq!(format(ArgFmt::Uint, usize::MAX), usize::MAX.to_string());
+    assert_eq!(format(ArgFmt::Hex, 0x1000), "0x1000");
+    assert_eq!(format(ArgFmt::Oct, 0o644), "0o644");
+    assert_eq!(format(ArgFmt::Ptr, 0), "NULL");
+    assert_eq!(format(ArgFmt::Ptr, 0xdead_b000), "0xdeadb000");
+    assert_eq!(format(ArgFmt::Str, 0), "NULL");
+}
+
+#[test]
+fn test_format_flags() {
+    let open = ArgFmt::Flags(OPEN_FLAGS);
+    assert_eq!(format(open, 0o1101), "O_WRONLY|O_CREAT|O_TRUNC");
+    assert_eq!(format(open, 0o2000002), "O_RDWR|O_CLOEXEC");
+    #[cfg(not(target_arch = "aarch64"))]
+    assert_eq!(format(open, 0o600000), "O_DIRECTORY|O_NOFOLLOW");
+    #[cfg(target_arch = "aarch64")]
+    assert_eq!(format(open, 0o140000), "O_DIRECTORY|O_NOFOLLOW");
+    // `O_RDONLY` has no bit.
+    assert_eq!(format(open, 0), "0x0");
+
+    let prot = ArgFmt::Flags(PROT_FLAGS);
+    assert_eq!(format(prot, 0x7), "PROT_READ|PROT_WRITE|PROT_EXEC");
+
+    // Unknown bits are printed in hexadecimal after the names.
+    let map = ArgFmt::Flags(MAP_FLAGS);
+    assert_eq!(format(map, 0x22), "MAP_PRIVATE|MAP_ANONYMOUS");
+    assert_eq!(format(map, 0x2 | 0x40), "MAP_PRIVATE|0x40");
+
+    // The exit signal of `clone` is in the low byte.
+    let clone = ArgFmt::Flags(CLONE_FLAGS);
+    assert_eq!(format(clone, 0x100 | 17), "CLONE_VM|0x11");
+}
+
+#[test]
+fn test_table() {
+    let mut table = SyscallTable::new();
+    table
+        .register(
+            1,
+            Syscall::new("write", &[ArgFmt::Int, ArgFmt::Ptr, ArgFmt::Uint], |_| 0),
+        )
+        .register(60, Syscall::new("exit", &[ArgFmt::Int], |_| 0).noreturn());
+    let write = table.get(1).unwrap();
+    assert_eq!(write.name(), "write");
+    assert_eq!(write.args().len(), 3);
+    assert!(!write.is_noreturn());
+    assert!(table.get(60).unwrap().is_noreturn());
+    assert!(table.get(2).is_none());
+}
+
+#[test]
+#[should_panic(expected = "registered twice")]
+fn test_table_duplicate() {
+    let mut table = SyscallTable::new();
+    table
+        .register(1, Syscall::new("write", &[], |_| 0))
+        .register(1, Syscall::new("read", &[], |_| 0));
+}
+
+#[test]
+fn test_user_ptr_add() {
+    let ptr = UserPtr::<u32>::new(0x1000);
+    assert_eq!(ptr.add(3).unwrap().addr(), 0x100c);
+    assert_eq!(ptr.add(0).unwrap().addr(), 0x1000);
+
+    let end = UserPtr::<u64>::new(usize::MAX - 7);
+    assert_eq!(end.add(0).unwrap().addr(), usize::MAX - 7);
+    assert_eq!(end.add(1).err(), Some(LinuxError::EFAULT));
+    assert_eq!(ptr.add(usize::MAX).err(), Some(LinuxError::EFAULT));
+    assert_eq!(ptr.add(usize::MAX / 2).err(), Some(LinuxError::EFAULT));
+}
+
+mod bpf {
+    use axerrno::LinuxError;
+
+    use crate::bpf::*;
+
+    const ARCH: u32 = 0xc000_003e;
+    const RET_KILL_PROCESS: u32 = 0x8000_0000;
+    const RET_ERRNO: u32 = 0x0005_0000;
+    const RET_ALLOW: u32 = 0x7fff_0000;
+
+    fn data(nr: i32, args: [u64; 6]) -> SeccompData {
+        SeccompData {
+            nr,
+            arch: ARCH,
+            instruction_pointer: 0x40_1000,
+            args,
+        }
+    }
+
+    /// Runs `prog` on `data` after checking it.
+    fn run(prog: &[SockFilter], data: &SeccompData) -> u32 {
+        check_filter(prog).unwrap();
+        run_filter(prog, data)
+    }
+
+    #[test]
+    fn test_allowlist() {
+        // Allow `getpid`, fail the others with `EPERM`, and kill on a
+        // foreign architecture.
+        let prog = [
+            SockFilter::stmt(BPF_LD | BPF_W | BPF_ABS, 4),
+            SockFilter::jump(BPF_JMP | BPF_JEQ | BPF_K, ARCH, 0, 4),
+            SockFilter::stmt(BPF_LD | BPF_W | BPF_ABS, 0),
+            SockFilter::jump(BPF_JMP | BPF_JEQ | BPF_K, 39, 0, 1),
+            SockFilter::stmt(BPF_RET | BPF_K, RET_ALLOW),
+            SockFilter::stmt(BPF_RET | BPF_K, RET_ERRNO | 1),
+            SockFilter::stmt(BPF_RET | BPF_K, RET_KILL_PROCESS),
+        ];
+        assert_eq!(run(&prog, &data(39, [0; 6])), RET_ALLOW);
+        assert_eq!(run(&prog, &data(1, [0; 6])), RET_ERRNO | 1);
+        let foreign = SeccompData {
+            arch: 0x4000_0003,
+            ..data(39, [0; 6])
+        };
+        assert_eq!(run(&prog, &foreign), RET_KILL_PROCESS);
+    }
+
+    #[test]
+    fn test_load_args() {
+        let args = [0x1234_5678_9abc_def0, 0, 0, 0, 0, 0xffff_0000_0000_0001];
+        let load = |offset| {
+            let prog = [
+                SockFilter::stmt(BPF_LD | BPF_W | BPF_ABS, offset),
+                SockFilter::stmt(BPF_RET | BPF_A, 0),
+            ];
+            run(&prog, &data(1, args))
+        };
+        // 64-bit fields are little-endian.
+        assert_eq!(load(16), 0x9abc_def0);
+        assert_eq!(load(20), 0x1234_5678);
+        assert_eq!(load(56), 1);
+        assert_eq!(load(60), 0xffff_0000);
+        assert_eq!(load(8), 0x40_1000);
+        assert_eq!(load(12), 0);
+
+        let prog = [
+            SockFilter::stmt(BPF_LD | BPF_W | BPF_LEN, 0),
+            SockFilter::stmt(BPF_RET | BPF_A, 0),
+        ];
+        assert_eq!(run(&prog, &data(1, args)), 64);
+    }
+
+    #[test]
+    fn test_alu_and_memory() {
+        let prog = [
+            SockFilter::stmt(BPF_LD | BPF_IMM, 6),
+            SockFilter::stmt(BPF_MISC | BPF_TAX, 0),
+            SockFilter::stmt(BPF_LD | BPF_IMM, 7),
+            SockFilter::stmt(BPF_ALU | BPF_MUL | BPF_X, 0),
+            SockFilter::stmt(BPF_ST, 3),
+            SockFilter::stmt(BPF_LD | BPF_IMM, 0),
+            SockFilter::stmt(BPF_LD | BPF_MEM, 3),
+            SockFilter::stmt(BPF_ALU | BPF_ADD | BPF_K, 1),
+            SockFilter::stmt(BPF_RET | BPF_A, 0),
+        ];
+        assert_eq!(run(&prog, &data(0, [0; 6])), 43);
+
+        // Shifting by 32 bits or more gives 0.
+        let prog = [
+            SockFilter::stmt(BPF_LD | BPF_IMM, 1),
+            SockFilter::stmt(BPF_ALU | BPF_LSH | BPF_K, 40),
+            SockFilter::stmt(BPF_RET | BPF_A, 0),
+        ];
+        assert_eq!(run(&prog, &data(0, [0; 6])), 0);
+
+        // Division by a zero `X` ends the program with 0.
+        let prog = [
+            SockFilter::stmt(BPF_LD | BPF_IMM, 1),
+            SockFilter::stmt(BPF_LDX | BPF_IMM, 0),
+            SockFilter::stmt(BPF_ALU | BPF_DIV | BPF_X, 0),
+            SockFilter::stmt(BPF_RET | BPF_K, RET_ALLOW),
+        ];
+        assert_eq!(run(&prog, &data(0, [0; 6])), 0);
+    }
+
+    #[test]
+    fn test_jumps() {
+        // Returns 1 if the first argument has bit 2 set, 2 if it is above
+        // 100, and 3 otherwise.
+        let prog = [
+            SockFilter::stmt(BPF_LD | BPF_W | BPF_ABS, 16),
+            SockFilter::jump(BPF_JMP | BPF_JSET | BPF_K, 4, 2, 0),
+            SockFilter::jump(BPF_JMP | BPF_JGT | BPF_K, 100, 2, 0),
+            SockFilter::stmt(BPF_JMP | BPF_JA, 2),
+            SockFilter::stmt(BPF_RET | BPF_K, 1),
+            SockFilter::stmt(BPF_RET | BPF_K, 2),
+            SockFilter::stmt(BPF_RET | BPF_K, 3),
+        ];
+        let arg = |a| data(0, [a, 0, 0, 0, 0, 0]);
+        assert_eq!(run(&prog, &arg(4)), 1);
+        assert_eq!(run(&prog, &arg(104)), 2);
+        assert_eq!(run(&prog, &arg(8)), 3);
+    }
+
+    #[test]
+    fn test_check_filter() {
+        let ret = SockFilter::stmt(BPF_RET | BPF_K, RET_ALLOW);
+        let check = |insn: SockFilter| check_filter(&[insn, ret]);
+        let einval = Err(LinuxError::EINVAL);
+
+        assert_eq!(check_filter(&[ret]), Ok(()));
+        assert_eq!(check_filter(&[]), einval);
+        // The program must end with a return.
+        assert_eq!(
+            check_filter(&[ret, SockFilter::stmt(BPF_LD | BPF_IMM, 0)]),
+            einval
+        );
+
+        // Only aligned words of `seccomp_data` can be loaded.
+        assert_eq!(
+            check(SockFilter::stmt(BPF_LD | BPF_W | BPF_ABS, 60)),
+            Ok(())
+        );
+        assert_eq!(check(SockFilter::stmt(BPF_LD | BPF_W | BPF_ABS, 2)), einval);
+        assert_eq!(
+            check(SockFilter::stmt(BPF_LD | BPF_W | BPF_ABS, 64)),
+            einval
+        );
+        // `BPF_IND`, and loads of bytes, are not allowed.
+        assert_eq!(check(SockFilter::stmt(BPF_LD | BPF_W | 0x40, 0)), einval);
+        assert_eq!(check(SockFilter::stmt(BPF_LD | 0x10 | BPF_ABS, 0)), einval);
+
+        // Scratch memory has 16 words.
+        assert_eq!(check(SockFilter::stmt(BPF_ST, 15)), Ok(()));
+        assert_eq!(check(SockFilter::stmt(BPF_ST, 16)), einval);
+        assert_eq!(check(SockFilter::stmt(BPF_LDX | BPF_MEM, 16)), einval);
+
+        // Division by a constant zero.
+        assert_eq!(
+            check(SockFilter::stmt(BPF_ALU | BPF_DIV | BPF_K, 0)),
+            einval
+        );
+        assert_eq!(
+            check(SockFilter::stmt(BPF_ALU | BPF_MOD | BPF_K, 0)),
+            einval
+        );
+        assert_eq!(
+            check(SockFilter::stmt(BPF_ALU | BPF_DIV | BPF_X, 0)),
+            Ok(())
+        );
+        assert_eq!(check(SockFilter::stmt(BPF_ALU | BPF_NEG, 0)), Ok(()));
+        assert_eq!(check(SockFilter::stmt(BPF_ALU | 0xb0, 0)), einval);
+
+        // Jumps must stay in the program.
+        assert_eq!(
+            check(SockFilter::jump(BPF_JMP | BPF_JEQ | BPF_K, 0, 0, 0)),
+            Ok(())
+        );
+        assert_eq!(
+            check(SockFilter::jump(BPF_JMP | BPF_JEQ | BPF_K, 0, 1, 0)),
+            einval
+        );
+        assert_eq!(
+            check(SockFilter::jump(BPF_JMP | BPF_JGE | BPF_X, 0, 0, 1)),
+            einval
+        );
+        assert_eq!(check(SockFilter::stmt(BPF_JMP | BPF_JA, 0)), Ok(()));
+        assert_eq!(check(SockFilter::stmt(BPF_JMP | BPF_JA, 1)), einval);
+        assert_eq!(check(SockFilter::stmt(BPF_JMP | 0x50, 0)), einval);
+    }
+}
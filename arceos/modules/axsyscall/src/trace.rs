@@ -0,0 +1,102 @@
+use alloc::string::String;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::LinuxError;
+
+use crate::args::{ArgFmt, SyscallArgs};
+use crate::table::Syscall;
+
+/// Maximum number of bytes of a string argument to print.
+const MAX_STR_LEN: usize = 64;
+/// Return values in `-MAX_ERRNO..0` are errors.
+const MAX_ERRNO: isize = 4095;
+
+static TRACING: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables syscall tracing.
+pub fn set_tracing(enabled: bool) {
+    TRACING.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether syscall tracing is enabled.
+pub fn is_tracing() -> bool {
+    TRACING.load(Ordering::Relaxed)
+}
+
+/// Formats the call as `name(arg0, arg1, ...)`.
+pub(crate) fn format_call(syscall: &Syscall, args: &SyscallArgs) -> String {
+    let mut s = String::new();
+    let _ = write!(s, "{}(", syscall.name());
+    for (i, fmt) in syscall.args().iter().enumerate() {
+        if i > 0 {
+            s.push_str(", ");
+        }
+        let _ = format_arg(&mut s, *fmt, args.raw(i));
+    }
+    s.push(')');
+    s
+}
+
+pub(crate) fn format_arg(s: &mut String, fmt: ArgFmt, raw: usize) -> core::fmt::Result {
+    match fmt {
+        ArgFmt::Int => write!(s, "{}", raw as isize),
+        ArgFmt::Uint => write!(s, "{}", raw),
+        ArgFmt::Hex => write!(s, "{:#x}", raw),
+        ArgFmt::Oct => write!(s, "{:#o}", raw),
+        ArgFmt::Ptr if raw == 0 => write!(s, "NULL"),
+        ArgFmt::Ptr => write!(s, "{:#x}", raw),
+        ArgFmt::Str if raw == 0 => write!(s, "NULL"),
+        ArgFmt::Str => match crate::uaccess::read_cstr_bytes(raw, MAX_STR_LEN) {
+            Ok((bytes, complete)) => {
+                write!(s, "{:?}", String::from_utf8_lossy(&bytes))?;
+                if !complete {
+                    s.push_str("...");
+                }
+                Ok(())
+            }
+            Err(_) => write!(s, "{:#x}", raw),
+        },
+        ArgFmt::Flags(names) => {
+            let mut rest = raw;
+            let mut first = true;
+            for &(name, bit) in names {
+                if rest & bit == bit && bit != 0 {
+                    if !first {
+                        s.push('|');
+                    }
+                    s.push_str(name);
+                    rest &= !bit;
+                    first = false;
+                }
+            }
+            if rest != 0 || first {
+                if !first {
+                    s.push('|');
+                }
+                write!(s, "{:#x}", rest)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Prints a call that may not return, before it runs.
+pub(crate) fn print_noreturn(call: &str) {
+    ax_println!("[{}] {} ...", axtask::current().id().as_u64(), call);
+}
+
+/// Prints a call with its return value or errno.
+pub(crate) fn print_ret(call: &str, ret: isize) {
+    let tid = axtask::current().id().as_u64();
+    if (-MAX_ERRNO..0).contains(&ret) {
+        match LinuxError::try_from(-ret as i32) {
+            Ok(e) => ax_println!("[{}] {} = -1 {:?} ({})", tid, call, e, e.as_str()),
+            Err(_) => ax_println!("[{}] {} = {}", tid, call, ret),
+        }
+    } else if ret < 0 {
+        ax_println!("[{}] {} = {:#x}", tid, call, ret as usize);
+    } else {
+        ax_println!("[{}] {} = {}", tid, call, ret);
+    }
+}
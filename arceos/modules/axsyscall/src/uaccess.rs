@@ -0,0 +1,231 @@
+//! Fault-safe access to the memory of the current user process.
+//!
+//! Every access checks the range against the address space of the current
+//! task, which is provided by the kernel through [`set_current_aspace_fn`],
+//! and faults the pages in (e.g., lazily allocated or copy-on-write pages)
+//! before copying. The copy itself goes through [`axhal::arch::copy_user`],
+//! so that a page unmapped meanwhile results in `EFAULT` instead of a kernel
+//! panic.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ffi::c_char;
+use core::marker::PhantomData;
+use core::mem::{size_of, MaybeUninit};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::{VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::MappingFlags;
+use axmm::AddrSpace;
+use axsync::Mutex;
+use lazyinit::LazyInit;
+
+use crate::args::FromArg;
+
+static CURRENT_ASPACE_FN: LazyInit<fn() -> Arc<Mutex<AddrSpace>>> = LazyInit::new();
+
+/// Sets the function that returns the address space of the current user
+/// task.
+///
+/// It must be called once before any user memory is accessed.
+pub fn set_current_aspace_fn(f: fn() -> Arc<Mutex<AddrSpace>>) {
+    CURRENT_ASPACE_FN.init_once(f);
+}
+
+/// Faults in the user pages in `[addr, addr + len)` for `access`.
+fn prepare(addr: usize, len: usize, access: MappingFlags) -> LinuxResult {
+    if len == 0 {
+        return Ok(());
+    }
+    if addr.checked_add(len).is_none() {
+        return Err(LinuxError::EFAULT);
+    }
+    let aspace = CURRENT_ASPACE_FN.get().ok_or(LinuxError::EFAULT)?();
+    let mut aspace = aspace.lock();
+    aspace
+        .prefault(VirtAddr::from(addr), len, access | MappingFlags::USER)
+        .map_err(|_| LinuxError::EFAULT)
+}
+
+/// Copies bytes at the user address `src` to `dst`.
+pub fn copy_from_user(dst: &mut [u8], src: usize) -> LinuxResult {
+    prepare(src, dst.len(), MappingFlags::READ)?;
+    match unsafe { axhal::arch::copy_user(dst.as_mut_ptr(), src as *const u8, dst.len()) } {
+        0 => Ok(()),
+        _ => Err(LinuxError::EFAULT),
+    }
+}
+
+/// Copies `src` to the user address `dst`.
+pub fn copy_to_user(dst: usize, src: &[u8]) -> LinuxResult {
+    prepare(dst, src.len(), MappingFlags::WRITE)?;
+    match unsafe { axhal::arch::copy_user(dst as *mut u8, src.as_ptr(), src.len()) } {
+        0 => Ok(()),
+        _ => Err(LinuxError::EFAULT),
+    }
+}
+
+/// Reads a NUL-terminated string at the user address `addr`, one page at a
+/// time.
+///
+/// Returns the bytes without the NUL, and whether the NUL is found within
+/// `max_len` bytes.
+pub(crate) fn read_cstr_bytes(addr: usize, max_len: usize) -> LinuxResult<(Vec<u8>, bool)> {
+    let mut bytes = Vec::new();
+    let mut ptr = addr;
+    while bytes.len() < max_len {
+        let chunk = (PAGE_SIZE_4K - ptr % PAGE_SIZE_4K).min(max_len - bytes.len());
+        let start = bytes.len();
+        bytes.resize(start + chunk, 0);
+        copy_from_user(&mut bytes[start..], ptr)?;
+        if let Some(pos) = bytes[start..].iter().position(|&b| b == 0) {
+            bytes.truncate(start + pos);
+            return Ok((bytes, true));
+        }
+        ptr += chunk;
+    }
+    Ok((bytes, false))
+}
+
+/// A pointer to a value of type `T` in user space.
+///
+/// It can be decoded from a syscall argument directly.
+pub struct UserPtr<T> {
+    addr: usize,
+    _phantom: PhantomData<*mut T>,
+}
+
+impl<T> Clone for UserPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UserPtr<T> {}
+
+impl<T> UserPtr<T> {
+    /// Creates a pointer to the user address `addr`.
+    pub const fn new(addr: usize) -> Self {
+        Self {
+            addr,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The user address.
+    pub const fn addr(&self) -> usize {
+        self.addr
+    }
+
+    pub const fn is_null(&self) -> bool {
+        self.addr == 0
+    }
+
+    /// Returns a pointer to the `count`-th element after this one, or
+    /// `EFAULT` if it is beyond the address space.
+    pub fn add(self, count: usize) -> LinuxResult<Self> {
+        count
+            .checked_mul(size_of::<T>())
+            .and_then(|offset| self.addr.checked_add(offset))
+            .map(Self::new)
+            .ok_or(LinuxError::EFAULT)
+    }
+}
+
+impl<T: Copy> UserPtr<T> {
+    /// Reads the value, or returns `EFAULT` if it is not readable.
+    pub fn read(&self) -> LinuxResult<T> {
+        let mut val = MaybeUninit::<T>::uninit();
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(val.as_mut_ptr() as *mut u8, size_of::<T>())
+        };
+        copy_from_user(buf, self.addr)?;
+        Ok(unsafe { val.assume_init() })
+    }
+
+    /// Writes the value, or returns `EFAULT` if it is not writable.
+    pub fn write(&self, val: T) -> LinuxResult {
+        let buf =
+            unsafe { core::slice::from_raw_parts(&val as *const T as *const u8, size_of::<T>()) };
+        copy_to_user(self.addr, buf)
+    }
+
+    /// Reads `len` consecutive values.
+    pub fn read_array(&self, len: usize) -> LinuxResult<Vec<T>> {
+        (0..len).map(|i| self.add(i)?.read()).collect()
+    }
+}
+
+impl UserPtr<c_char> {
+    /// Reads a NUL-terminated string of at most `max_len` bytes (excluding
+    /// the NUL), or returns `ENAMETOOLONG` if it is longer.
+    ///
+    /// Linux allows any bytes but NUL in a path, while the file system only
+    /// has UTF-8 names, so a string that is not UTF-8 names no file: it
+    /// fails with `ENOENT`.
+    pub fn read_str(&self, max_len: usize) -> LinuxResult<String> {
+        if self.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        match read_cstr_bytes(self.addr, max_len + 1)? {
+            (bytes, true) => String::from_utf8(bytes).map_err(|_| LinuxError::ENOENT),
+            (_, false) => Err(LinuxError::ENAMETOOLONG),
+        }
+    }
+}
+
+impl<T> From<*const T> for UserPtr<T> {
+    fn from(ptr: *const T) -> Self {
+        Self::new(ptr as usize)
+    }
+}
+
+impl<T> From<*mut T> for UserPtr<T> {
+    fn from(ptr: *mut T) -> Self {
+        Self::new(ptr as usize)
+    }
+}
+
+impl<T> FromArg for UserPtr<T> {
+    fn from_arg(raw: usize) -> Self {
+        Self::new(raw)
+    }
+}
+
+/// A byte buffer in user space.
+#[derive(Clone, Copy)]
+pub struct UserSlice {
+    addr: usize,
+    len: usize,
+}
+
+impl UserSlice {
+    /// Creates a buffer of `len` bytes at the user address `addr`.
+    pub const fn new(addr: usize, len: usize) -> Self {
+        Self { addr, len }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copies the whole buffer into kernel memory.
+    pub fn read_to_vec(&self) -> LinuxResult<Vec<u8>> {
+        let mut buf = alloc::vec![0; self.len];
+        copy_from_user(&mut buf, self.addr)?;
+        Ok(buf)
+    }
+
+    /// Copies `buf` to the start of the buffer, which must be large enough.
+    pub fn write(&self, buf: &[u8]) -> LinuxResult {
+        if buf.len() > self.len {
+            return Err(LinuxError::EFAULT);
+        }
+        copy_to_user(self.addr, buf)
+    }
+}
@@ -229,8 +229,9 @@ impl TaskInner {
         matches!(self.state(), TaskState::Ready)
     }
 
+    /// Whether the task is waiting for something, e.g., in a wait queue.
     #[inline]
-    pub(crate) fn is_blocked(&self) -> bool {
+    pub fn is_blocked(&self) -> bool {
         matches!(self.state(), TaskState::Blocked)
     }
 
@@ -39,6 +39,8 @@ fn main() {
     let ustack_top = init_user_stack(&mut uspace, true).unwrap();
     ax_println!("New user address space: {:#x?}", uspace);
 
+    syscall::init_syscalls();
+
     // Let's kick off the user process.
     let user_task = task::spawn_user_task(
         Arc::new(Mutex::new(uspace)),
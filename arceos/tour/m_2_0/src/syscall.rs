@@ -2,22 +2,25 @@
 
 use axhal::arch::TrapFrame;
 use axhal::trap::{register_trap_handler, SYSCALL};
-use axerrno::LinuxError;
+use axsyscall::sysno::*;
+use axsyscall::{ArgFmt, Syscall, SyscallTable};
 
-const SYS_EXIT: usize = 93;
+/// Registers the syscalls of this kernel.
+pub fn init_syscalls() {
+    let mut table = SyscallTable::new();
+    table.register(
+        SYS_EXIT,
+        Syscall::new("exit", &[ArgFmt::Int], |args| sys_exit(args.arg(0))).noreturn(),
+    );
+    axsyscall::init(table);
+}
 
 #[register_trap_handler(SYSCALL)]
 fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
     ax_println!("handle_syscall ...");
-    let ret = match syscall_num {
-        SYS_EXIT => {
-            ax_println!("[SYS_EXIT]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
-            -LinuxError::ENOSYS.code() as _
-        }
-    };
-    ret
+    axsyscall::dispatch(tf, syscall_num)
+}
+
+fn sys_exit(exit_code: i32) -> ! {
+    axtask::exit(exit_code)
 }
@@ -1,53 +1,96 @@
 #![allow(dead_code)]
 
 use core::ffi::c_void;
+use axerrno::LinuxError;
 use axhal::arch::TrapFrame;
 use axhal::trap::{register_trap_handler, SYSCALL};
-use axerrno::LinuxError;
+use axsyscall::sysno::*;
+use axsyscall::{syscall_body, ArgFmt, Syscall, SyscallTable, UserPtr, UserSlice};
 use axtask::current;
 use axtask::TaskExtRef;
 use arceos_posix_api as api;
 
-const SYS_IOCTL: usize = 29;
-const SYS_WRITEV: usize = 66;
-const SYS_EXIT: usize = 93;
-const SYS_EXIT_GROUP: usize = 94;
-const SYS_SET_TID_ADDRESS: usize = 96;
+/// The maximum number of buffers of `writev`.
+const IOV_MAX: i32 = 1024;
+
+/// Registers the syscalls of this kernel.
+pub fn init_syscalls() {
+    use ArgFmt::*;
+    let mut table = SyscallTable::new();
+    table
+        .register(
+            SYS_IOCTL,
+            Syscall::new("ioctl", &[Int, Hex, Ptr], |args| {
+                sys_ioctl(args.arg(0), args.arg(1), args.arg(2)) as _
+            }),
+        )
+        .register(
+            SYS_SET_TID_ADDRESS,
+            Syscall::new("set_tid_address", &[Ptr], |args| {
+                sys_set_tid_address(args.arg(0))
+            }),
+        )
+        .register(
+            SYS_WRITEV,
+            Syscall::new("writev", &[Int, Ptr, Int], |args| {
+                sys_writev(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_EXIT_GROUP,
+            Syscall::new("exit_group", &[Int], |args| sys_exit_group(args.arg(0))).noreturn(),
+        )
+        .register(
+            SYS_EXIT,
+            Syscall::new("exit", &[Int], |args| sys_exit(args.arg(0))).noreturn(),
+        );
+    axsyscall::init(table);
+}
 
 #[register_trap_handler(SYSCALL)]
 fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
     ax_println!("handle_syscall [{}] ...", syscall_num);
-    let ret = match syscall_num {
-         SYS_IOCTL => sys_ioctl(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _) as _,
-        SYS_SET_TID_ADDRESS => sys_set_tid_address(tf.arg0() as _),
-        SYS_WRITEV => sys_writev(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_EXIT_GROUP => {
-            ax_println!("[SYS_EXIT_GROUP]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        SYS_EXIT => {
-            ax_println!("[SYS_EXIT]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
-            -LinuxError::ENOSYS.code() as _
-        }
-    };
-    ret
+    axsyscall::dispatch(tf, syscall_num)
 }
 
-fn sys_writev(fd: i32, iov: *const api::ctypes::iovec, iocnt: i32) -> isize {
-    unsafe { api::sys_writev(fd, iov, iocnt) }
+fn sys_exit_group(exit_code: i32) -> ! {
+    axtask::exit(exit_code)
+}
+
+fn sys_exit(exit_code: i32) -> ! {
+    axtask::exit(exit_code)
+}
+
+/// Writes the user buffers described by `iov` in order, stopping at the first
+/// short write.
+fn sys_writev(fd: i32, iov: UserPtr<api::ctypes::iovec>, iocnt: i32) -> isize {
+    syscall_body!(sys_writev, {
+        if !(0..=IOV_MAX).contains(&iocnt) {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut total = 0;
+        for iov in iov.read_array(iocnt as usize)? {
+            let kbuf = UserSlice::new(iov.iov_base as usize, iov.iov_len).read_to_vec()?;
+            let n = api::sys_write(fd, kbuf.as_ptr() as _, kbuf.len());
+            if n < 0 {
+                return if total == 0 { Ok(n) } else { Ok(total) };
+            }
+            total += n;
+            if (n as usize) < kbuf.len() {
+                break;
+            }
+        }
+        Ok(total)
+    })
 }
 
-pub(crate) fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
+fn sys_set_tid_address(tid_ptd: UserPtr<i32>) -> isize {
     let curr = current();
-    curr.task_ext().set_clear_child_tid(tid_ptd as _);
+    curr.task_ext().set_clear_child_tid(tid_ptd.addr() as _);
     curr.id().as_u64() as isize
 }
 
-fn sys_ioctl(_fd: i32, _op: usize, _argp: *mut c_void) -> i32 {
+fn sys_ioctl(_fd: i32, _op: usize, _argp: UserPtr<c_void>) -> i32 {
     ax_println!("Unimplemented syscall: SYS_IOCTL");
     0
 }
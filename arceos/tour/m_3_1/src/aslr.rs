@@ -0,0 +1,39 @@
+//! Address space layout randomization.
+//!
+//! When it is enabled, the loader shifts the load bias of position-independent
+//! executables, the base of the dynamic linker, the stack top, the mmap base
+//! and the start of the heap by random numbers of pages, drawn from
+//! [`axhal::misc::random`] for every `execve`.
+//!
+//! It is off unless the kernel command line has `aslr` (or `aslr=on`), so
+//! that layouts are reproducible for debugging by default.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axhal::mem::PAGE_SIZE_4K;
+
+use crate::cmdline::Cmdline;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables ASLR according to the kernel command line.
+pub fn init(cmdline: &Cmdline) {
+    let enabled = cmdline.enabled("aslr");
+    ENABLED.store(enabled, Ordering::Release);
+    info!("ASLR {}", if enabled { "enabled" } else { "disabled" });
+}
+
+/// Whether the layout of new address spaces is randomized.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Returns a random multiple of the page size below `range`, or 0 if ASLR is
+/// disabled.
+pub fn random_offset(range: usize) -> usize {
+    let pages = range / PAGE_SIZE_4K;
+    if !is_enabled() || pages == 0 {
+        return 0;
+    }
+    (axhal::misc::random() % pages as u128) as usize * PAGE_SIZE_4K
+}
@@ -0,0 +1,52 @@
+//! The kernel command line.
+//!
+//! The boot loader does not pass one, so it is read at boot from
+//! [`CMDLINE_PATH`] on the root filesystem instead. It is a list of
+//! whitespace-separated options, each either `name` or `name=value`, e.g.
+//! `strace aslr=on`. A missing file is an empty command line.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Where the command line is stored.
+const CMDLINE_PATH: &str = "/boot/cmdline";
+
+/// The options of the kernel command line.
+pub struct Cmdline {
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Cmdline {
+    /// Reads the command line from [`CMDLINE_PATH`].
+    pub fn load() -> Self {
+        let text = std::fs::read_to_string(CMDLINE_PATH).unwrap_or_default();
+        info!("Kernel command line: {}", text.trim());
+        Self::parse(&text)
+    }
+
+    fn parse(text: &str) -> Self {
+        let options = text
+            .split_whitespace()
+            .map(|option| match option.split_once('=') {
+                Some((name, value)) => (String::from(name), Some(String::from(value))),
+                None => (String::from(option), None),
+            })
+            .collect();
+        Self { options }
+    }
+
+    /// The value of the last option `name`, or `Some("")` if it has none.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_deref().unwrap_or(""))
+    }
+
+    /// Whether the option `name` is given as `name`, `name=on`, `name=y` or
+    /// `name=1`. Any other value turns it off.
+    pub fn enabled(&self, name: &str) -> bool {
+        matches!(self.get(name), Some("" | "on" | "y" | "1"))
+    }
+}
@@ -0,0 +1,341 @@
+//! ELF core dumps of crashing user processes.
+//!
+//! When a signal whose default action is to dump core kills a process, its
+//! memory and the registers of the faulting thread are written to
+//! `core.<pid>` in the current directory. The file is an `ET_CORE` ELF file as
+//! `gdb` and `readelf` expect: a `PT_NOTE` segment with the `NT_PRSTATUS`,
+//! `NT_PRPSINFO` and `NT_AUXV` notes, followed by a `PT_LOAD` segment for each
+//! area of the address space.
+//!
+//! The other threads of the process are not stopped, and their registers are
+//! not saved.
+
+use core::mem::size_of;
+use std::fs::File;
+use std::io::{self, Write};
+
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use axhal::arch::TrapFrame;
+use axhal::mem::{VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::MappingFlags;
+use axtask::{current, TaskExtRef};
+use elf::abi::{
+    ELFCLASS64, ELFDATA2LSB, ELFMAGIC, ELFOSABI_NONE, EI_CLASS, EI_DATA, EI_NIDENT, EI_OSABI,
+    EI_VERSION, ET_CORE, EV_CURRENT, NT_AUXV, NT_PRPSINFO, NT_PRSTATUS, PF_R, PF_W, PF_X,
+    PT_LOAD, PT_NOTE,
+};
+use elf::file::Elf64_Ehdr;
+use elf::segment::Elf64_Phdr;
+
+use crate::signal::SigInfo;
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) const ELF_MACHINE: u16 = elf::abi::EM_X86_64;
+#[cfg(target_arch = "aarch64")]
+pub(crate) const ELF_MACHINE: u16 = elf::abi::EM_AARCH64;
+#[cfg(target_arch = "riscv64")]
+pub(crate) const ELF_MACHINE: u16 = elf::abi::EM_RISCV;
+
+/// The number of registers in `NT_PRSTATUS` (`ELF_NGREG`).
+#[cfg(target_arch = "x86_64")]
+const NGREG: usize = 27;
+#[cfg(target_arch = "aarch64")]
+const NGREG: usize = 34;
+#[cfg(target_arch = "riscv64")]
+const NGREG: usize = 32;
+
+/// The owner name of the notes.
+const NOTE_NAME: &[u8] = b"CORE\0";
+
+/// `struct elf_prstatus` of Linux, the `NT_PRSTATUS` note.
+#[repr(C)]
+struct PrStatus {
+    /// `si_signo`, `si_code` and `si_errno` of the signal.
+    info: [i32; 3],
+    cursig: u16,
+    _pad0: u16,
+    sigpend: u64,
+    sighold: u64,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    /// The user, system and children's CPU times, as `struct timeval`s.
+    times: [[i64; 2]; 4],
+    reg: [u64; NGREG],
+    fpvalid: i32,
+    _pad1: i32,
+}
+
+/// `struct elf_prpsinfo` of Linux, the `NT_PRPSINFO` note.
+#[repr(C)]
+struct PrPsInfo {
+    state: u8,
+    sname: u8,
+    zomb: u8,
+    nice: i8,
+    _pad: u32,
+    flag: u64,
+    uid: u32,
+    gid: u32,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    /// The file name of the executable.
+    fname: [u8; 16],
+    /// The command line.
+    psargs: [u8; 80],
+}
+
+/// The registers of `tf` in the order of `struct user_regs_struct`.
+#[cfg(target_arch = "x86_64")]
+fn user_regs(tf: &TrapFrame) -> [u64; NGREG] {
+    let fs_base = axhal::arch::read_thread_pointer() as u64;
+    // `orig_rax` is -1 as the thread is not in a syscall. `gs_base` and the
+    // data segments are 0.
+    #[rustfmt::skip]
+    let regs = [
+        tf.r15, tf.r14, tf.r13, tf.r12, tf.rbp, tf.rbx, tf.r11, tf.r10, tf.r9,
+        tf.r8, tf.rax, tf.rcx, tf.rdx, tf.rsi, tf.rdi, u64::MAX, tf.rip, tf.cs,
+        tf.rflags, tf.rsp, tf.ss, fs_base, 0, 0, 0, 0, 0,
+    ];
+    regs
+}
+
+/// The registers of `tf` in the order of `struct user_pt_regs`.
+#[cfg(target_arch = "aarch64")]
+fn user_regs(tf: &TrapFrame) -> [u64; NGREG] {
+    let mut regs = [0; NGREG];
+    regs[..31].copy_from_slice(&tf.r);
+    regs[31] = tf.usp;
+    regs[32] = tf.elr;
+    regs[33] = tf.spsr;
+    regs
+}
+
+/// The registers of `tf` in the order of `struct user_regs_struct`, i.e.,
+/// `pc` followed by `x1` to `x31`.
+#[cfg(target_arch = "riscv64")]
+fn user_regs(tf: &TrapFrame) -> [u64; NGREG] {
+    let gprs =
+        unsafe { core::mem::transmute::<axhal::arch::GeneralRegisters, [usize; 31]>(tf.regs) };
+    let mut regs = [0; NGREG];
+    regs[0] = tf.sepc as u64;
+    for (reg, gpr) in regs[1..].iter_mut().zip(gprs) {
+        *reg = gpr as u64;
+    }
+    regs
+}
+
+/// Views `value` as bytes. `T` must be `repr(C)` without implicit padding.
+pub(crate) fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+/// The `e_ident` of 64-bit little-endian ELF files of this kernel.
+pub(crate) fn elf_ident() -> [u8; EI_NIDENT] {
+    let mut ident = [0; EI_NIDENT];
+    ident[..4].copy_from_slice(&ELFMAGIC);
+    ident[EI_CLASS] = ELFCLASS64;
+    ident[EI_DATA] = ELFDATA2LSB;
+    ident[EI_VERSION] = EV_CURRENT;
+    ident[EI_OSABI] = ELFOSABI_NONE;
+    ident
+}
+
+/// Appends a note of type `ty` owned by `CORE` to `buf`. The name and the
+/// descriptor are padded to 4 bytes.
+fn push_note(buf: &mut Vec<u8>, ty: u64, desc: &[u8]) {
+    buf.extend((NOTE_NAME.len() as u32).to_ne_bytes());
+    buf.extend((desc.len() as u32).to_ne_bytes());
+    buf.extend((ty as u32).to_ne_bytes());
+    buf.extend(NOTE_NAME);
+    buf.resize(buf.len().next_multiple_of(4), 0);
+    buf.extend(desc);
+    buf.resize(buf.len().next_multiple_of(4), 0);
+}
+
+/// Copies `s` into `buf`, truncated so that it stays NUL-terminated.
+fn copy_cstr(buf: &mut [u8], s: &[u8]) {
+    let len = s.len().min(buf.len() - 1);
+    buf[..len].copy_from_slice(&s[..len]);
+}
+
+/// Converts user mapping flags to the `p_flags` of a segment.
+fn segment_flags(flags: MappingFlags) -> u32 {
+    let mut p_flags = 0;
+    if flags.contains(MappingFlags::READ) {
+        p_flags |= PF_R;
+    }
+    if flags.contains(MappingFlags::WRITE) {
+        p_flags |= PF_W;
+    }
+    if flags.contains(MappingFlags::EXECUTE) {
+        p_flags |= PF_X;
+    }
+    p_flags
+}
+
+/// Builds the notes of the current process, which is killed by `info` in
+/// the context `tf`.
+fn build_notes(tf: &TrapFrame, info: &SigInfo) -> Vec<u8> {
+    let curr = current();
+    let ext = curr.task_ext();
+    let process = &ext.process;
+    let exec_info = process.exec_info();
+
+    let prstatus = PrStatus {
+        info: [info.signo, info.code, info.errno],
+        cursig: info.signo as u16,
+        _pad0: 0,
+        sigpend: ext.signals.pending().bits(),
+        sighold: ext.signals.blocked().bits(),
+        // The thread ID, by which `gdb` names the thread.
+        pid: curr.id().as_u64() as i32,
+        ppid: process.ppid() as i32,
+        pgrp: process.pgid() as i32,
+        sid: 0,
+        times: [[0; 2]; 4],
+        reg: user_regs(tf),
+        fpvalid: 0,
+        _pad1: 0,
+    };
+
+    let cred = process.cred();
+    let mut prpsinfo = PrPsInfo {
+        state: 0,
+        sname: b'R',
+        zomb: 0,
+        nice: 0,
+        _pad: 0,
+        flag: 0,
+        uid: cred.uid,
+        gid: cred.gid,
+        pid: process.pid() as i32,
+        ppid: process.ppid() as i32,
+        pgrp: process.pgid() as i32,
+        sid: 0,
+        fname: [0; 16],
+        psargs: [0; 80],
+    };
+    let fname = exec_info.path.rsplit('/').next().unwrap_or_default();
+    copy_cstr(&mut prpsinfo.fname, fname.as_bytes());
+    copy_cstr(&mut prpsinfo.psargs, exec_info.args.join(" ").as_bytes());
+
+    let auxv: Vec<u8> = exec_info
+        .auxv
+        .iter()
+        .flat_map(|&(key, value)| [key, value])
+        .flat_map(usize::to_ne_bytes)
+        .collect();
+
+    let mut notes = Vec::new();
+    push_note(&mut notes, NT_PRSTATUS, as_bytes(&prstatus));
+    push_note(&mut notes, NT_PRPSINFO, as_bytes(&prpsinfo));
+    push_note(&mut notes, NT_AUXV, &auxv);
+    notes
+}
+
+/// Writes the core file of the current process to `path`.
+fn write_core(path: &str, tf: &TrapFrame, info: &SigInfo) -> io::Result<()> {
+    let notes = build_notes(tf, info);
+    let curr = current();
+    let aspace = curr.task_ext().aspace.lock();
+    let areas: Vec<_> = aspace.areas().collect();
+    let readable = |flags: MappingFlags| flags.contains(MappingFlags::READ);
+
+    let phnum = areas.len() + 1;
+    let notes_offset = size_of::<Elf64_Ehdr>() + phnum * size_of::<Elf64_Phdr>();
+    let headers_end = notes_offset + notes.len();
+    let mut phdrs = Vec::with_capacity(phnum);
+    phdrs.push(Elf64_Phdr {
+        p_type: PT_NOTE,
+        p_flags: 0,
+        p_offset: notes_offset as u64,
+        p_vaddr: 0,
+        p_paddr: 0,
+        p_filesz: notes.len() as u64,
+        p_memsz: 0,
+        p_align: 4,
+    });
+    // Memory contents start at a page boundary. Areas that are not readable
+    // take no room in the file.
+    let mut offset = headers_end.next_multiple_of(PAGE_SIZE_4K);
+    for &(start, size, flags) in &areas {
+        let filesz = if readable(flags) { size } else { 0 };
+        phdrs.push(Elf64_Phdr {
+            p_type: PT_LOAD,
+            p_flags: segment_flags(flags),
+            p_offset: offset as u64,
+            p_vaddr: start.as_usize() as u64,
+            p_paddr: 0,
+            p_filesz: filesz as u64,
+            p_memsz: size as u64,
+            p_align: PAGE_SIZE_4K as u64,
+        });
+        offset += filesz;
+    }
+
+    let ehdr = Elf64_Ehdr {
+        e_ident: elf_ident(),
+        e_type: ET_CORE,
+        e_machine: ELF_MACHINE,
+        e_version: EV_CURRENT as u32,
+        e_entry: 0,
+        e_phoff: size_of::<Elf64_Ehdr>() as u64,
+        e_shoff: 0,
+        e_flags: 0,
+        e_ehsize: size_of::<Elf64_Ehdr>() as u16,
+        e_phentsize: size_of::<Elf64_Phdr>() as u16,
+        e_phnum: phnum as u16,
+        e_shentsize: 0,
+        e_shnum: 0,
+        e_shstrndx: 0,
+    };
+
+    let mut file = File::create(path)?;
+    file.write_all(as_bytes(&ehdr))?;
+    for phdr in &phdrs {
+        file.write_all(as_bytes(phdr))?;
+    }
+    file.write_all(&notes)?;
+    file.write_all(&vec![0; headers_end.next_multiple_of(PAGE_SIZE_4K) - headers_end])?;
+    let mut page = [0u8; PAGE_SIZE_4K];
+    for &(start, size, _) in areas.iter().filter(|&&(_, _, flags)| readable(flags)) {
+        for vaddr in (start.as_usize()..start.as_usize() + size).step_by(PAGE_SIZE_4K) {
+            // Pages that have never been touched are read from their file,
+            // e.g. the code of the program.
+            if aspace.read_page(VirtAddr::from(vaddr), &mut page).is_err() {
+                page.fill(0);
+            }
+            file.write_all(&page)?;
+        }
+    }
+    Ok(())
+}
+
+/// Dumps the core of the current process, which is killed by `info` in the
+/// context `tf`. Returns whether the core file has been written.
+///
+/// Nothing is dumped if another thread is terminating the process already.
+pub fn dump_core(tf: &TrapFrame, info: &SigInfo) -> bool {
+    let curr = current();
+    let process = &curr.task_ext().process;
+    if process.is_group_exiting() {
+        return false;
+    }
+    let path = format!("core.{}", process.pid());
+    match write_core(&path, tf, info) {
+        Ok(()) => {
+            info!("dumped core of process {} to {}", process.pid(), path);
+            true
+        }
+        Err(e) => {
+            warn!("failed to dump core to {}: {:?}", path, e);
+            false
+        }
+    }
+}
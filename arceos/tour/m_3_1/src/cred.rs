@@ -0,0 +1,224 @@
+//! User and group IDs (`getuid`, `setuid`, `setresuid`, `setgroups` and the
+//! like).
+//!
+//! Every process has its own credentials, which a child inherits from its
+//! parent and which file permissions are checked against. The init process
+//! runs as root. Only root can take on arbitrary IDs; other processes can
+//! only switch among their real, effective and saved IDs.
+
+use alloc::sync::Arc;
+
+use axerrno::{LinuxError, LinuxResult};
+use axfs::cred::Cred;
+use axsyscall::{syscall_body, UserPtr};
+use axtask::{current, TaskExtRef};
+
+/// An ID argument that keeps the current one.
+const KEEP_ID: u32 = u32::MAX;
+/// The maximum number of supplementary groups.
+const NGROUPS_MAX: usize = 65536;
+
+/// Whether a syscall works on the user IDs or the group IDs.
+#[derive(Clone, Copy)]
+enum IdKind {
+    User,
+    Group,
+}
+
+/// The real, effective and saved IDs of `kind`.
+fn get_ids(cred: &Cred, kind: IdKind) -> [u32; 3] {
+    match kind {
+        IdKind::User => [cred.uid, cred.euid, cred.suid],
+        IdKind::Group => [cred.gid, cred.egid, cred.sgid],
+    }
+}
+
+fn set_ids(cred: &mut Cred, kind: IdKind, [real, effective, saved]: [u32; 3]) {
+    match kind {
+        IdKind::User => (cred.uid, cred.euid, cred.suid) = (real, effective, saved),
+        IdKind::Group => (cred.gid, cred.egid, cred.sgid) = (real, effective, saved),
+    }
+}
+
+fn current_cred() -> Arc<Cred> {
+    current().task_ext().process.cred()
+}
+
+/// Updates the credentials of the calling process with `f`, which sees the
+/// real, effective and saved IDs of `kind` and whether the process is
+/// privileged, and returns the new IDs.
+fn update_ids(
+    kind: IdKind,
+    f: impl FnOnce([u32; 3], bool) -> LinuxResult<[u32; 3]>,
+) -> LinuxResult<isize> {
+    current().task_ext().process.update_cred(|cred| {
+        let ids = f(get_ids(cred, kind), cred.is_privileged())?;
+        set_ids(cred, kind, ids);
+        Ok(0)
+    })
+}
+
+/// `setuid` and `setgid`: root sets all three IDs, others only the effective
+/// one, to the real or saved ID.
+fn do_setid(kind: IdKind, id: u32) -> LinuxResult<isize> {
+    if id == KEEP_ID {
+        return Err(LinuxError::EINVAL);
+    }
+    update_ids(kind, |[real, effective, saved], privileged| {
+        if privileged {
+            Ok([id, id, id])
+        } else if id == real || id == saved {
+            Ok([real, id, saved])
+        } else {
+            Err(LinuxError::EPERM)
+        }
+    })
+}
+
+/// `setreuid` and `setregid`.
+///
+/// Others than root can set the real ID to the real or effective one, and
+/// the effective ID to any of the three. The saved ID follows the new
+/// effective one if the real ID is set, or the effective ID is set to
+/// something other than the old real one.
+fn do_setreid(kind: IdKind, new_real: u32, new_effective: u32) -> LinuxResult<isize> {
+    update_ids(kind, |[real, effective, saved], privileged| {
+        let r = if new_real == KEEP_ID { real } else { new_real };
+        let e = if new_effective == KEEP_ID {
+            effective
+        } else {
+            new_effective
+        };
+        if !privileged
+            && ((r != real && r != effective) || (e != real && e != effective && e != saved))
+        {
+            return Err(LinuxError::EPERM);
+        }
+        let s = if new_real != KEEP_ID || (new_effective != KEEP_ID && e != real) {
+            e
+        } else {
+            saved
+        };
+        Ok([r, e, s])
+    })
+}
+
+/// `setresuid` and `setresgid`: others than root can only set each ID to one
+/// of the current three.
+fn do_setresid(kind: IdKind, new: [u32; 3]) -> LinuxResult<isize> {
+    update_ids(kind, |old, privileged| {
+        let mut ids = old;
+        for (id, new) in ids.iter_mut().zip(new) {
+            if new != KEEP_ID {
+                if !privileged && !old.contains(&new) {
+                    return Err(LinuxError::EPERM);
+                }
+                *id = new;
+            }
+        }
+        Ok(ids)
+    })
+}
+
+/// `getresuid` and `getresgid`.
+fn do_getresid(kind: IdKind, ptrs: [UserPtr<u32>; 3]) -> LinuxResult<isize> {
+    for (ptr, id) in ptrs.into_iter().zip(get_ids(&current_cred(), kind)) {
+        ptr.write(id)?;
+    }
+    Ok(0)
+}
+
+pub fn sys_getuid() -> isize {
+    syscall_body!(sys_getuid, { Ok(current_cred().uid) })
+}
+
+pub fn sys_geteuid() -> isize {
+    syscall_body!(sys_geteuid, { Ok(current_cred().euid) })
+}
+
+pub fn sys_getgid() -> isize {
+    syscall_body!(sys_getgid, { Ok(current_cred().gid) })
+}
+
+pub fn sys_getegid() -> isize {
+    syscall_body!(sys_getegid, { Ok(current_cred().egid) })
+}
+
+pub fn sys_setuid(uid: u32) -> isize {
+    syscall_body!(sys_setuid, { do_setid(IdKind::User, uid) })
+}
+
+pub fn sys_setgid(gid: u32) -> isize {
+    syscall_body!(sys_setgid, { do_setid(IdKind::Group, gid) })
+}
+
+pub fn sys_setreuid(ruid: u32, euid: u32) -> isize {
+    syscall_body!(sys_setreuid, { do_setreid(IdKind::User, ruid, euid) })
+}
+
+pub fn sys_setregid(rgid: u32, egid: u32) -> isize {
+    syscall_body!(sys_setregid, { do_setreid(IdKind::Group, rgid, egid) })
+}
+
+pub fn sys_setresuid(ruid: u32, euid: u32, suid: u32) -> isize {
+    syscall_body!(sys_setresuid, {
+        do_setresid(IdKind::User, [ruid, euid, suid])
+    })
+}
+
+pub fn sys_setresgid(rgid: u32, egid: u32, sgid: u32) -> isize {
+    syscall_body!(sys_setresgid, {
+        do_setresid(IdKind::Group, [rgid, egid, sgid])
+    })
+}
+
+pub fn sys_getresuid(ruid: UserPtr<u32>, euid: UserPtr<u32>, suid: UserPtr<u32>) -> isize {
+    syscall_body!(sys_getresuid, {
+        do_getresid(IdKind::User, [ruid, euid, suid])
+    })
+}
+
+pub fn sys_getresgid(rgid: UserPtr<u32>, egid: UserPtr<u32>, sgid: UserPtr<u32>) -> isize {
+    syscall_body!(sys_getresgid, {
+        do_getresid(IdKind::Group, [rgid, egid, sgid])
+    })
+}
+
+/// Stores the supplementary group IDs at `list`, which has room for `size`
+/// of them, and returns their number. Only the number is returned if `size`
+/// is 0.
+pub fn sys_getgroups(size: i32, list: UserPtr<u32>) -> isize {
+    syscall_body!(sys_getgroups, {
+        let cred = current_cred();
+        let count = cred.groups.len();
+        match size {
+            0 => {}
+            size if size < 0 || (size as usize) < count => return Err(LinuxError::EINVAL),
+            _ => {
+                for (i, &gid) in cred.groups.iter().enumerate() {
+                    list.add(i)?.write(gid)?;
+                }
+            }
+        }
+        Ok(count)
+    })
+}
+
+/// Replaces the supplementary group IDs with the `size` ones at `list`. Only
+/// root can do it.
+pub fn sys_setgroups(size: usize, list: UserPtr<u32>) -> isize {
+    syscall_body!(sys_setgroups, {
+        if size > NGROUPS_MAX {
+            return Err(LinuxError::EINVAL);
+        }
+        if !current_cred().is_privileged() {
+            return Err(LinuxError::EPERM);
+        }
+        let groups = list.read_array(size)?;
+        current()
+            .task_ext()
+            .process
+            .update_cred(|cred| cred.groups = groups);
+        Ok(0)
+    })
+}
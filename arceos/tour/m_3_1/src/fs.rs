@@ -0,0 +1,241 @@
+//! Syscalls on paths and directories.
+//!
+//! Relative paths are resolved against a directory file descriptor (the
+//! `*at` syscalls), or the current directory if it is `AT_FDCWD`.
+
+use alloc::ffi::CString;
+use alloc::vec;
+use core::ffi::{c_char, c_int};
+
+use arceos_posix_api::{self as api, ctypes};
+use axerrno::{LinuxError, LinuxResult};
+use axsyscall::{syscall_body, UserPtr, UserSlice};
+
+/// `dirfd` of the `*at` syscalls that stands for the current directory.
+pub const AT_FDCWD: c_int = -100;
+/// Do not follow symbolic links, for the legacy `lstat` of x86_64.
+#[cfg(target_arch = "x86_64")]
+pub const AT_SYMLINK_NOFOLLOW: c_int = 0x100;
+/// `unlinkat`: remove a directory instead of a file, for the legacy `rmdir`
+/// of x86_64.
+#[cfg(target_arch = "x86_64")]
+pub const AT_REMOVEDIR: c_int = 0x200;
+/// Operate on `dirfd` itself if the path is empty.
+const AT_EMPTY_PATH: c_int = 0x1000;
+
+/// The maximum length of a path.
+pub const PATH_MAX: usize = 4096;
+/// Directory entries are read into a kernel buffer of at most this size.
+const MAX_DENTS_CHUNK: usize = 0x10000;
+
+/// `struct stat` of Linux on x86_64.
+#[cfg(target_arch = "x86_64")]
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stat {
+    dev: u64,
+    ino: u64,
+    nlink: u64,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    _pad0: u32,
+    rdev: u64,
+    size: i64,
+    blksize: i64,
+    blocks: i64,
+    /// `tv_sec` and `tv_nsec` of the timestamps.
+    atime: [i64; 2],
+    mtime: [i64; 2],
+    ctime: [i64; 2],
+    _unused: [i64; 3],
+}
+
+/// `struct stat` of the generic Linux ABI, used by riscv64 and aarch64.
+#[cfg(not(target_arch = "x86_64"))]
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stat {
+    dev: u64,
+    ino: u64,
+    mode: u32,
+    nlink: u32,
+    uid: u32,
+    gid: u32,
+    rdev: u64,
+    _pad1: u64,
+    size: i64,
+    blksize: i32,
+    _pad2: i32,
+    blocks: i64,
+    /// `tv_sec` and `tv_nsec` of the timestamps.
+    atime: [i64; 2],
+    mtime: [i64; 2],
+    ctime: [i64; 2],
+    _unused: [u32; 2],
+}
+
+impl From<ctypes::stat> for Stat {
+    fn from(st: ctypes::stat) -> Self {
+        let time = |ts: ctypes::timespec| [ts.tv_sec as i64, ts.tv_nsec as i64];
+        Self {
+            dev: st.st_dev as _,
+            ino: st.st_ino as _,
+            nlink: st.st_nlink as _,
+            mode: st.st_mode as _,
+            uid: st.st_uid as _,
+            gid: st.st_gid as _,
+            rdev: st.st_rdev as _,
+            size: st.st_size as _,
+            blksize: st.st_blksize as _,
+            blocks: st.st_blocks as _,
+            atime: time(st.st_atime),
+            mtime: time(st.st_mtime),
+            ctime: time(st.st_ctime),
+            ..Default::default()
+        }
+    }
+}
+
+/// Copies a path from user space.
+pub fn read_path(path: UserPtr<c_char>) -> LinuxResult<CString> {
+    CString::new(path.read_str(PATH_MAX)?).map_err(|_| LinuxError::EINVAL)
+}
+
+pub fn sys_openat(
+    dirfd: c_int,
+    path: UserPtr<c_char>,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> isize {
+    syscall_body!(sys_openat, {
+        let path = read_path(path)?;
+        Ok(api::sys_openat(dirfd, path.as_ptr(), flags, mode))
+    })
+}
+
+pub fn sys_mkdirat(dirfd: c_int, path: UserPtr<c_char>, mode: ctypes::mode_t) -> isize {
+    syscall_body!(sys_mkdirat, {
+        let path = read_path(path)?;
+        Ok(api::sys_mkdirat(dirfd, path.as_ptr(), mode))
+    })
+}
+
+pub fn sys_unlinkat(dirfd: c_int, path: UserPtr<c_char>, flags: c_int) -> isize {
+    syscall_body!(sys_unlinkat, {
+        let path = read_path(path)?;
+        Ok(api::sys_unlinkat(dirfd, path.as_ptr(), flags))
+    })
+}
+
+pub fn sys_renameat2(
+    olddirfd: c_int,
+    old: UserPtr<c_char>,
+    newdirfd: c_int,
+    new: UserPtr<c_char>,
+    flags: c_int,
+) -> isize {
+    syscall_body!(sys_renameat2, {
+        let old = read_path(old)?;
+        let new = read_path(new)?;
+        Ok(api::sys_renameat2(olddirfd, old.as_ptr(), newdirfd, new.as_ptr(), flags))
+    })
+}
+
+/// Gets the status of the file at `path` into the user buffer `statbuf`.
+pub fn sys_fstatat(
+    dirfd: c_int,
+    path: UserPtr<c_char>,
+    statbuf: UserPtr<Stat>,
+    flags: c_int,
+) -> isize {
+    syscall_body!(sys_fstatat, {
+        let path = read_path(path)?;
+        let mut st = unsafe { core::mem::zeroed() };
+        let ret = unsafe { api::sys_fstatat(dirfd, path.as_ptr(), &mut st, flags) };
+        if ret < 0 {
+            return Ok(ret as isize);
+        }
+        statbuf.write(Stat::from(st))?;
+        Ok(0)
+    })
+}
+
+/// Gets the status of the file `fd` into the user buffer `statbuf`.
+pub fn sys_fstat(fd: c_int, statbuf: UserPtr<Stat>) -> isize {
+    syscall_body!(sys_fstat, {
+        let mut st = unsafe { core::mem::zeroed() };
+        let ret = unsafe { api::sys_fstatat(fd, c"".as_ptr(), &mut st, AT_EMPTY_PATH) };
+        if ret < 0 {
+            return Ok(ret as isize);
+        }
+        statbuf.write(Stat::from(st))?;
+        Ok(0)
+    })
+}
+
+pub fn sys_faccessat(dirfd: c_int, path: UserPtr<c_char>, mode: c_int) -> isize {
+    syscall_body!(sys_faccessat, {
+        let path = read_path(path)?;
+        Ok(api::sys_faccessat(dirfd, path.as_ptr(), mode))
+    })
+}
+
+pub fn sys_fchmodat(dirfd: c_int, path: UserPtr<c_char>, mode: ctypes::mode_t) -> isize {
+    syscall_body!(sys_fchmodat, {
+        let path = read_path(path)?;
+        Ok(api::sys_fchmodat(dirfd, path.as_ptr(), mode))
+    })
+}
+
+pub fn sys_fchownat(
+    dirfd: c_int,
+    path: UserPtr<c_char>,
+    uid: u32,
+    gid: u32,
+    flags: c_int,
+) -> isize {
+    syscall_body!(sys_fchownat, {
+        let path = read_path(path)?;
+        Ok(api::sys_fchownat(dirfd, path.as_ptr(), uid, gid, flags))
+    })
+}
+
+pub fn sys_fchmod(fd: c_int, mode: ctypes::mode_t) -> isize {
+    api::sys_fchmod(fd, mode) as isize
+}
+
+pub fn sys_fchown(fd: c_int, uid: u32, gid: u32) -> isize {
+    api::sys_fchown(fd, uid, gid) as isize
+}
+
+pub fn sys_readlinkat(
+    dirfd: c_int,
+    path: UserPtr<c_char>,
+    buf: UserPtr<u8>,
+    bufsize: usize,
+) -> isize {
+    syscall_body!(sys_readlinkat, {
+        let path = read_path(path)?;
+        let mut kbuf = vec![0u8; bufsize.min(PATH_MAX)];
+        let n = api::sys_readlinkat(dirfd, path.as_ptr(), kbuf.as_mut_ptr() as _, kbuf.len());
+        if n < 0 {
+            return Ok(n as isize);
+        }
+        UserSlice::new(buf.addr(), bufsize).write(&kbuf[..n as usize])?;
+        Ok(n as isize)
+    })
+}
+
+/// Reads the entries of the directory `fd` into the user buffer `dirp`.
+pub fn sys_getdents64(fd: c_int, dirp: UserPtr<u8>, count: usize) -> isize {
+    syscall_body!(sys_getdents64, {
+        let mut kbuf = vec![0u8; count.min(MAX_DENTS_CHUNK)];
+        let n = unsafe { api::sys_getdents64(fd, kbuf.as_mut_ptr(), kbuf.len()) };
+        if n < 0 {
+            return Ok(n as isize);
+        }
+        UserSlice::new(dirp.addr(), count).write(&kbuf[..n as usize])?;
+        Ok(n as isize)
+    })
+}
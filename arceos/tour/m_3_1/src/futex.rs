@@ -0,0 +1,296 @@
+//! Fast user-space locking (`futex`).
+//!
+//! A futex is identified by its address space and user address. Waiters are
+//! kept in a fixed number of hashed buckets, each with a list of waiters and a
+//! wait queue. A waiter always sleeps on the wait queue of the bucket it
+//! started waiting in, even if it is requeued to another futex later, so it
+//! can be woken up by setting its flag and notifying that wait queue.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use arceos_posix_api::ctypes;
+use axerrno::{LinuxError, LinuxResult};
+use axmm::AddrSpace;
+use axsync::{Mutex, MutexGuard};
+use axsyscall::{syscall_body, UserPtr};
+use axtask::{current, TaskExtRef, WaitQueue};
+
+const FUTEX_WAIT: u32 = 0;
+const FUTEX_WAKE: u32 = 1;
+const FUTEX_REQUEUE: u32 = 3;
+const FUTEX_CMP_REQUEUE: u32 = 4;
+
+/// The futex is only shared by threads of a process. Since futexes are
+/// always keyed by the address space, it makes no difference.
+const FUTEX_PRIVATE_FLAG: u32 = 128;
+/// The timeout is measured against `CLOCK_REALTIME`, which only matters for
+/// absolute timeouts.
+const FUTEX_CLOCK_REALTIME: u32 = 256;
+
+/// The number of buckets that futexes are hashed into.
+const FUTEX_BUCKETS: usize = 64;
+
+/// Identifies a futex.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FutexKey {
+    /// The address of the address space.
+    aspace: usize,
+    /// The user address of the futex word.
+    uaddr: usize,
+}
+
+impl FutexKey {
+    /// The futex at `uaddr` in the address space of the current process.
+    fn current(uaddr: usize) -> LinuxResult<Self> {
+        if uaddr % 4 != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(Self::new(&current().task_ext().aspace, uaddr))
+    }
+
+    fn new(aspace: &Arc<Mutex<AddrSpace>>, uaddr: usize) -> Self {
+        Self {
+            aspace: Arc::as_ptr(aspace) as usize,
+            uaddr,
+        }
+    }
+
+    fn bucket(&self) -> usize {
+        (self.aspace ^ (self.uaddr >> 2)) % FUTEX_BUCKETS
+    }
+}
+
+/// A thread waiting on a futex.
+struct FutexWaiter {
+    /// The bucket whose wait queue the thread sleeps on.
+    sleep_bucket: usize,
+    /// The bucket whose waiter list the waiter is in, which changes when it
+    /// is requeued.
+    list_bucket: AtomicUsize,
+    woken: AtomicBool,
+}
+
+impl FutexWaiter {
+    /// Wakes up the thread. It must have been removed from the waiter list.
+    fn wake(&self) {
+        self.woken.store(true, Ordering::Release);
+        FUTEX_TABLE[self.sleep_bucket].wq.notify_all(false);
+    }
+}
+
+type WaiterList = VecDeque<(FutexKey, Arc<FutexWaiter>)>;
+
+struct FutexBucket {
+    waiters: Mutex<WaiterList>,
+    wq: WaitQueue,
+}
+
+impl FutexBucket {
+    const fn new() -> Self {
+        Self {
+            waiters: Mutex::new(VecDeque::new()),
+            wq: WaitQueue::new(),
+        }
+    }
+
+    /// Wakes up at most `count` waiters of `key`. Returns the number of woken
+    /// waiters.
+    fn wake(waiters: &mut WaiterList, key: FutexKey, count: usize) -> usize {
+        let mut woken = 0;
+        waiters.retain(|(k, waiter)| {
+            if woken < count && *k == key {
+                waiter.wake();
+                woken += 1;
+                false
+            } else {
+                true
+            }
+        });
+        woken
+    }
+}
+
+static FUTEX_TABLE: [FutexBucket; FUTEX_BUCKETS] = [const { FutexBucket::new() }; FUTEX_BUCKETS];
+
+/// Locks the waiter lists of two buckets in a fixed order. The second guard
+/// is `None` if both are the same bucket.
+fn lock_two(
+    a: usize,
+    b: usize,
+) -> (
+    MutexGuard<'static, WaiterList>,
+    Option<MutexGuard<'static, WaiterList>>,
+) {
+    if a == b {
+        (FUTEX_TABLE[a].waiters.lock(), None)
+    } else if a < b {
+        let first = FUTEX_TABLE[a].waiters.lock();
+        (first, Some(FUTEX_TABLE[b].waiters.lock()))
+    } else {
+        let second = FUTEX_TABLE[b].waiters.lock();
+        (FUTEX_TABLE[a].waiters.lock(), Some(second))
+    }
+}
+
+/// Waits on the futex at `uaddr` if it still contains `val`, until it is
+/// woken up, `timeout` has elapsed, or a signal arrives.
+fn futex_wait(uaddr: usize, val: u32, timeout: Option<Duration>) -> LinuxResult<isize> {
+    let key = FutexKey::current(uaddr)?;
+    let bucket = key.bucket();
+    let waiter = Arc::new(FutexWaiter {
+        sleep_bucket: bucket,
+        list_bucket: AtomicUsize::new(bucket),
+        woken: AtomicBool::new(false),
+    });
+    {
+        // Check the value with the waiter list locked, so that a wake-up
+        // after changing the value cannot be missed.
+        let mut waiters = FUTEX_TABLE[bucket].waiters.lock();
+        if UserPtr::<u32>::new(uaddr).read()? != val {
+            return Err(LinuxError::EAGAIN);
+        }
+        waiters.push_back((key, waiter.clone()));
+    }
+
+    let curr = current();
+    // The condition is checked with the run queue locked, so it must not take
+    // any sleeping lock.
+    let condition =
+        || waiter.woken.load(Ordering::Acquire) || curr.task_ext().signals.has_deliverable();
+    let wq = &FUTEX_TABLE[bucket].wq;
+    let timed_out = match timeout {
+        Some(dur) => wq.wait_timeout_until(dur, condition),
+        None => {
+            wq.wait_until(condition);
+            false
+        }
+    };
+
+    // Remove the waiter if nobody has done it, as it may have been requeued
+    // to another bucket meanwhile.
+    loop {
+        let list_bucket = waiter.list_bucket.load(Ordering::Acquire);
+        let mut waiters = FUTEX_TABLE[list_bucket].waiters.lock();
+        if waiter.list_bucket.load(Ordering::Acquire) != list_bucket {
+            continue;
+        }
+        waiters.retain(|(_, w)| !Arc::ptr_eq(w, &waiter));
+        break;
+    }
+    if waiter.woken.load(Ordering::Acquire) {
+        Ok(0)
+    } else if timed_out {
+        Err(LinuxError::ETIMEDOUT)
+    } else {
+        Err(LinuxError::EINTR)
+    }
+}
+
+/// Wakes up at most `count` waiters of the futex at `uaddr`.
+fn futex_wake(uaddr: usize, count: usize) -> LinuxResult<isize> {
+    let key = FutexKey::current(uaddr)?;
+    Ok(wake_key(key, count) as isize)
+}
+
+fn wake_key(key: FutexKey, count: usize) -> usize {
+    let mut waiters = FUTEX_TABLE[key.bucket()].waiters.lock();
+    FutexBucket::wake(&mut waiters, key, count)
+}
+
+/// Wakes up at most `wake_count` waiters of the futex at `uaddr`, and moves
+/// at most `requeue_count` of the remaining ones to the futex at `uaddr2`.
+///
+/// If `cmp_val` is given, the futex at `uaddr` must still contain it.
+fn futex_requeue(
+    uaddr: usize,
+    wake_count: usize,
+    uaddr2: usize,
+    requeue_count: usize,
+    cmp_val: Option<u32>,
+) -> LinuxResult<isize> {
+    let key = FutexKey::current(uaddr)?;
+    let key2 = FutexKey::current(uaddr2)?;
+    let (bucket, bucket2) = (key.bucket(), key2.bucket());
+    let (mut waiters, mut waiters2) = lock_two(bucket, bucket2);
+    if let Some(cmp_val) = cmp_val {
+        if UserPtr::<u32>::new(uaddr).read()? != cmp_val {
+            return Err(LinuxError::EAGAIN);
+        }
+    }
+
+    let woken = FutexBucket::wake(&mut waiters, key, wake_count);
+    let mut requeued = 0;
+    let mut i = 0;
+    while i < waiters.len() && requeued < requeue_count {
+        if waiters[i].0 != key {
+            i += 1;
+            continue;
+        }
+        let (_, waiter) = waiters.remove(i).unwrap();
+        waiter.list_bucket.store(bucket2, Ordering::Release);
+        match waiters2.as_mut() {
+            Some(waiters2) => waiters2.push_back((key2, waiter)),
+            None => waiters.push_back((key2, waiter)),
+        }
+        requeued += 1;
+    }
+    if cmp_val.is_some() {
+        Ok((woken + requeued) as isize)
+    } else {
+        Ok(woken as isize)
+    }
+}
+
+/// Wakes up all threads waiting on futexes, so that they can handle newly
+/// arrived signals.
+pub fn interrupt_wait() {
+    for bucket in FUTEX_TABLE.iter() {
+        bucket.wq.notify_all(false);
+    }
+}
+
+/// Clears the word at `clear_child_tid` and wakes up a waiter on it, when a
+/// thread that called `set_tid_address` exits.
+pub fn clear_child_tid(aspace: &Arc<Mutex<AddrSpace>>, clear_child_tid: usize) {
+    if UserPtr::<u32>::new(clear_child_tid).write(0).is_ok() {
+        wake_key(FutexKey::new(aspace, clear_child_tid), 1);
+    }
+}
+
+/// The `futex` syscall. `FUTEX_WAIT`, `FUTEX_WAKE`, `FUTEX_REQUEUE` and
+/// `FUTEX_CMP_REQUEUE` are supported.
+///
+/// For `FUTEX_WAIT`, `timeout` is a relative `struct timespec`; for the
+/// requeue operations, it is the maximum number of waiters to requeue.
+pub fn sys_futex(
+    uaddr: usize,
+    futex_op: u32,
+    val: u32,
+    timeout: usize,
+    uaddr2: usize,
+    val3: u32,
+) -> isize {
+    syscall_body!(sys_futex, {
+        match futex_op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME) {
+            FUTEX_WAIT => {
+                let timeout = if timeout == 0 {
+                    None
+                } else {
+                    let ts = UserPtr::<ctypes::timespec>::new(timeout).read()?;
+                    if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+                        return Err(LinuxError::EINVAL);
+                    }
+                    Some(Duration::from(ts))
+                };
+                futex_wait(uaddr, val, timeout)
+            }
+            FUTEX_WAKE => futex_wake(uaddr, val as usize),
+            FUTEX_REQUEUE => futex_requeue(uaddr, val as usize, uaddr2, timeout, None),
+            FUTEX_CMP_REQUEUE => futex_requeue(uaddr, val as usize, uaddr2, timeout, Some(val3)),
+            _ => Err(LinuxError::ENOSYS),
+        }
+    })
+}
@@ -1,15 +1,22 @@
-use std::io::{self, Read};
-use std::io::SeekFrom;
-use std::io::Seek;
-use std::fs::File;
+use std::io;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::vec::Vec;
 use alloc::vec;
 use axhal::paging::MappingFlags;
-use axhal::mem::{PAGE_SIZE_4K, VirtAddr, MemoryAddr};
+use axhal::mem::{PAGE_SIZE_4K, VirtAddr};
+use axerrno::{LinuxError, LinuxResult};
+use axfs::cred::Cred;
+use axfs::fops::{File, OpenOptions};
 use axmm::AddrSpace;
 
-use elf::abi::{PT_INTERP, PT_LOAD};
+use crate::aslr;
+use crate::coredump::ELF_MACHINE;
+use crate::fs::PATH_MAX;
+
+use elf::abi::{ET_DYN, ET_EXEC, PF_R, PF_W, PF_X, PT_GNU_STACK, PT_INTERP, PT_LOAD, PT_PHDR};
 use elf::endian::AnyEndian;
+use elf::file::{Class, FileHeader};
 use elf::parse::ParseAt;
 use elf::segment::ProgramHeader;
 use elf::segment::SegmentTable;
@@ -17,60 +24,580 @@ use elf::ElfBytes;
 
 const ELF_HEAD_BUF_SIZE: usize = 256;
 
-pub fn load_user_app(fname: &str, uspace: &mut AddrSpace) -> io::Result<usize> {
-    let mut file = File::open(fname)?;
-    let (phdrs, entry, _, _) = load_elf_phdrs(&mut file)?;
+/// The initial size of the user stack, which grows on demand.
+const USER_STACK_SIZE: usize = 0x10000;
+
+/// The base address of position-independent executables (`ET_DYN`).
+const ELF_ET_DYN_BASE: usize = 0x1_0000_0000;
+/// The base address of the dynamic linker named by `PT_INTERP`.
+const ELF_INTERP_BASE: usize = 0x20_0000_0000;
+/// Where the search for a free area for `mmap` starts, well above the
+/// program and its heap.
+const MMAP_BASE: usize = 0x10_0000_0000;
+
+// The ranges that ASLR shifts the layout within, see `crate::aslr`.
+/// The range of the load bias of `ET_DYN` executables.
+const ET_DYN_RANDOM_RANGE: usize = 0x4000_0000; // 1 GiB
+/// The range of the base of the dynamic linker.
+const INTERP_RANDOM_RANGE: usize = 0x4000_0000; // 1 GiB
+/// The range of the mmap base.
+const MMAP_RANDOM_RANGE: usize = 0x1_0000_0000; // 4 GiB
+/// The range that the stack top is moved down within. It is small, as the
+/// vDSO sits not far below the top of the address space.
+const STACK_RANDOM_RANGE: usize = 0x400_0000; // 64 MiB
+/// The range of the gap between the program and its heap.
+const BRK_RANDOM_RANGE: usize = 0x200_0000; // 32 MiB
+
+// Auxiliary vector entry types, see `<elf.h>`.
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PHENT: usize = 4;
+const AT_PHNUM: usize = 5;
+const AT_PAGESZ: usize = 6;
+const AT_BASE: usize = 7;
+const AT_FLAGS: usize = 8;
+const AT_ENTRY: usize = 9;
+const AT_UID: usize = 11;
+const AT_EUID: usize = 12;
+const AT_GID: usize = 13;
+const AT_EGID: usize = 14;
+const AT_PLATFORM: usize = 15;
+const AT_HWCAP: usize = 16;
+const AT_CLKTCK: usize = 17;
+const AT_SECURE: usize = 23;
+const AT_RANDOM: usize = 25;
+const AT_EXECFN: usize = 31;
+const AT_SYSINFO_EHDR: usize = 33;
+
+/// The frequency of the clock ticks reported to user space (`USER_HZ`), e.g.,
+/// for `times`.
+const USER_HZ: usize = 100;
+
+/// The hardware capabilities (`AT_HWCAP`) of the CPU.
+#[cfg(target_arch = "riscv64")]
+fn hwcap() -> usize {
+    // One bit per single-letter ISA extension. The kernel targets RV64GC.
+    b"imafdc".iter().fold(0, |cap, ext| cap | 1 << (ext - b'a'))
+}
+
+/// The hardware capabilities (`AT_HWCAP`) of the CPU.
+#[cfg(target_arch = "x86_64")]
+fn hwcap() -> usize {
+    // The feature flags in `EDX` of CPUID leaf 1.
+    unsafe { core::arch::x86_64::__cpuid(1).edx as usize }
+}
+
+/// The hardware capabilities (`AT_HWCAP`) of the CPU.
+#[cfg(target_arch = "aarch64")]
+fn hwcap() -> usize {
+    // HWCAP_FP | HWCAP_ASIMD, which every ARMv8-A CPU that Linux runs on has.
+    0b11
+}
+
+/// The platform string (`AT_PLATFORM`), which RISC-V does not have.
+#[cfg(target_arch = "x86_64")]
+const PLATFORM: Option<&str> = Some("x86_64");
+#[cfg(target_arch = "aarch64")]
+const PLATFORM: Option<&str> = Some("aarch64");
+#[cfg(target_arch = "riscv64")]
+const PLATFORM: Option<&str> = None;
+
+/// Information about a loaded ELF image, needed to build the auxiliary vector.
+struct ElfImage {
+    /// The load bias, i.e. the offset added to all `p_vaddr`s. It is zero
+    /// for `ET_EXEC` objects.
+    bias: usize,
+    entry: usize,
+    /// The user address of the program header table.
+    phdr: usize,
+    phent: usize,
+    phnum: usize,
+    /// Whether `PT_GNU_STACK` asks for an executable stack.
+    stack_exec: bool,
+    /// The end of the highest loaded segment.
+    end: usize,
+}
+
+/// Converts the `p_flags` of a segment to user mapping flags.
+fn segment_flags(p_flags: u32) -> MappingFlags {
+    let mut flags = MappingFlags::USER;
+    if p_flags & PF_R != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if p_flags & PF_W != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if p_flags & PF_X != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
+
+/// A page-aligned area of an ELF image to map.
+struct ImageArea {
+    start: usize,
+    end: usize,
+    flags: MappingFlags,
+    /// The file offset that `start` is mapped from, or `None` if the area is
+    /// allocated and the file content is copied into it.
+    offset: Option<u64>,
+}
 
-    for phdr in &phdrs {
-        ax_println!(
-            "phdr: offset: {:#X}=>{:#X} size: {:#X}=>{:#X}",
-            phdr.p_offset, phdr.p_vaddr, phdr.p_filesz, phdr.p_memsz
-        );
+/// Computes the areas to map for the loadable segments `loads`, relocated by
+/// `bias`.
+///
+/// A page that only holds the file content of one segment is mapped from the
+/// file, and read in on demand. The other pages are allocated: the ones with
+/// `.bss`, which must be zero past the file content, and the ones shared by
+/// adjacent segments (e.g. the end of `.text` and the start of `.data`),
+/// which get the union of their permissions.
+fn image_areas(loads: &[&ProgramHeader], bias: usize) -> Vec<ImageArea> {
+    // The flags of each page, and the file offset it may be mapped from.
+    let mut pages: BTreeMap<usize, (MappingFlags, Option<u64>)> = BTreeMap::new();
+    for phdr in loads {
+        let vaddr = phdr.p_vaddr as usize + bias;
+        let start = vaddr & !(PAGE_SIZE_4K - 1);
+        let end = (vaddr + phdr.p_memsz as usize).div_ceil(PAGE_SIZE_4K) * PAGE_SIZE_4K;
+        let flags = segment_flags(phdr.p_flags);
+        for page in (start..end).step_by(PAGE_SIZE_4K) {
+            let offset = if page + PAGE_SIZE_4K <= vaddr + phdr.p_filesz as usize {
+                (phdr.p_offset + page as u64)
+                    .checked_sub(vaddr as u64)
+                    .filter(|offset| offset % PAGE_SIZE_4K as u64 == 0)
+            } else {
+                None
+            };
+            pages
+                .entry(page)
+                .and_modify(|(page_flags, page_offset)| {
+                    *page_flags |= flags;
+                    *page_offset = None;
+                })
+                .or_insert((flags, offset));
+        }
+    }
+
+    let mut areas: Vec<ImageArea> = Vec::new();
+    for (page, (flags, offset)) in pages {
+        match areas.last_mut() {
+            Some(area)
+                if area.end == page
+                    && area.flags == flags
+                    && area.offset.map(|start| start + (page - area.start) as u64) == offset =>
+            {
+                area.end += PAGE_SIZE_4K;
+            }
+            _ => areas.push(ImageArea {
+                start: page,
+                end: page + PAGE_SIZE_4K,
+                flags,
+                offset,
+            }),
+        }
+    }
+    areas
+}
 
-        let vaddr = VirtAddr::from(phdr.p_vaddr as usize).align_down_4k();
-        let vaddr_end = VirtAddr::from((phdr.p_vaddr+phdr.p_memsz) as usize)
-            .align_up_4k();
+/// Reads exactly `buf.len()` bytes of `file` at `offset`.
+fn read_exact_at(file: &File, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read_at(offset + read as u64, &mut buf[read..])? {
+            0 => return Err(io::Error::UnexpectedEof),
+            n => read += n,
+        }
+    }
+    Ok(())
+}
 
-        ax_println!("{:#x} - {:#x}", vaddr, vaddr_end);
-        uspace.map_alloc(vaddr, vaddr_end-vaddr, MappingFlags::READ|MappingFlags::WRITE|MappingFlags::EXECUTE|MappingFlags::USER, true)?;
+/// An ELF file whose headers have been read and checked.
+struct ElfFile {
+    file: File,
+    ehdr: FileHeader<AnyEndian>,
+    phdrs: Vec<ProgramHeader>,
+    /// The path of the dynamic linker named by `PT_INTERP`, if any.
+    interp: Option<String>,
+}
 
-        let mut data = vec![0u8; phdr.p_memsz as usize];
-        file.seek(SeekFrom::Start(phdr.p_offset))?;
+impl ElfFile {
+    /// Opens the ELF file `path` and checks its headers.
+    ///
+    /// Fails with `ENOEXEC` if it is not an executable or a shared object for
+    /// this machine, its program headers are malformed, or its entry point is not
+    /// in a loadable segment.
+    fn open(path: &str) -> LinuxResult<Self> {
+        let mut opts = OpenOptions::new();
+        opts.read(true);
+        let file = File::open(path, &opts)?;
+        let (ehdr, phdrs) = load_elf_phdrs(&file)?;
+        let interp = match phdrs.iter().find(|phdr| phdr.p_type == PT_INTERP) {
+            Some(phdr) => {
+                if phdr.p_filesz as usize > PATH_MAX {
+                    return Err(LinuxError::ENOEXEC);
+                }
+                let mut path = vec![0u8; phdr.p_filesz as usize];
+                read_exact_at(&file, phdr.p_offset, &mut path).map_err(|_| LinuxError::ENOEXEC)?;
+                // Strip the trailing NUL(s).
+                while path.last() == Some(&0) {
+                    path.pop();
+                }
+                Some(String::from_utf8(path).map_err(|_| LinuxError::ENOEXEC)?)
+            }
+            None => None,
+        };
+        Ok(Self {
+            file,
+            ehdr,
+            phdrs,
+            interp,
+        })
+    }
 
-        let filesz = phdr.p_filesz as usize;
-        let mut index = 0;
-        while index < filesz {
-            let n = file.read(&mut data[index..filesz])?;
-            index += n;
+    /// The offset that the segments are loaded at when relocated to
+    /// `dyn_base`, or `None` if the segments are linked above `dyn_base`.
+    fn bias(&self, dyn_base: usize) -> Option<usize> {
+        if self.ehdr.e_type != ET_DYN {
+            return Some(0);
         }
-        assert_eq!(index, filesz);
-        uspace.write(VirtAddr::from(phdr.p_vaddr as usize), &data)?;
+        let min_vaddr = self
+            .phdrs
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_LOAD)
+            .map(|phdr| phdr.p_vaddr as usize)
+            .min()
+            .unwrap_or(0);
+        dyn_base.checked_sub(min_vaddr & !(PAGE_SIZE_4K - 1))
     }
+}
 
-    Ok(entry)
+/// A user app that is ready to be loaded: the program, and its dynamic linker
+/// if it is dynamically linked.
+pub struct UserApp {
+    path: String,
+    program: ElfFile,
+    interp: Option<ElfFile>,
 }
 
-fn load_elf_phdrs(file: &mut File) -> io::Result<(Vec<ProgramHeader>, usize, usize, usize)> {
+impl UserApp {
+    /// Opens the program at `path` and its dynamic linker, and checks their
+    /// headers, so that a bad file is reported before the address space is
+    /// touched. Fails with `ENOEXEC` if either is not a valid ELF file.
+    pub fn open(path: &str) -> LinuxResult<Self> {
+        let program = ElfFile::open(path)?;
+        let interp = program.interp.as_deref().map(ElfFile::open).transpose()?;
+        // The random offsets only move the bases up.
+        if program.bias(ELF_ET_DYN_BASE).is_none()
+            || interp
+                .as_ref()
+                .is_some_and(|interp| interp.bias(ELF_INTERP_BASE).is_none())
+        {
+            return Err(LinuxError::ENOEXEC);
+        }
+        Ok(Self {
+            path: String::from(path),
+            program,
+            interp,
+        })
+    }
+}
+
+/// Loads the user app `app` into `uspace`, and sets up its user stack with
+/// the arguments `args`, the environment variables `envs` and the auxiliary
+/// vector.
+///
+/// If the app is dynamically linked, its dynamic linker is loaded as well,
+/// and the returned entry point is the one of the dynamic linker. The signal
+/// return trampoline and the vDSO are mapped too, and the program break is
+/// set up right after the app.
+///
+/// With ASLR enabled, the base of a position-independent app, the base of
+/// the dynamic linker, the heap, the mmap base and the stack are shifted by
+/// random offsets.
+///
+/// The auxiliary vector tells the app its user and group IDs from `cred`.
+///
+/// The user stack may grow up to `stack_limit` bytes.
+///
+/// `uspace` is expected to have no user mappings. Returns the entry point,
+/// the initial user stack pointer and the complete auxiliary vector.
+pub fn load_user_app(
+    app: &UserApp,
+    args: &[String],
+    envs: &[String],
+    cred: &Cred,
+    stack_limit: usize,
+    uspace: &mut AddrSpace,
+) -> io::Result<(usize, VirtAddr, Vec<(usize, usize)>)> {
+    let dyn_base = ELF_ET_DYN_BASE + aslr::random_offset(ET_DYN_RANDOM_RANGE);
+    let image = load_elf(&app.program, dyn_base, uspace)?;
+    // The heap starts right after the program, not the dynamic linker.
+    uspace.init_heap((image.end + aslr::random_offset(BRK_RANDOM_RANGE)).into());
+    uspace.set_mmap_base((MMAP_BASE + aslr::random_offset(MMAP_RANDOM_RANGE)).into());
+    let (entry, interp_base) = match &app.interp {
+        Some(interp) => {
+            let interp_base = ELF_INTERP_BASE + aslr::random_offset(INTERP_RANDOM_RANGE);
+            let interp = load_elf(interp, interp_base, uspace)?;
+            (interp.entry, interp.bias)
+        }
+        None => (image.entry, 0),
+    };
+    let vdso_base = crate::vdso::map_vdso(uspace)?;
+    // The dynamic linker ignores `LD_*` variables in secure mode.
+    let secure = cred.uid != cred.euid || cred.gid != cred.egid;
+    let auxv = [
+        (AT_HWCAP, hwcap()),
+        (AT_PAGESZ, PAGE_SIZE_4K),
+        (AT_CLKTCK, USER_HZ),
+        (AT_PHDR, image.phdr),
+        (AT_PHENT, image.phent),
+        (AT_PHNUM, image.phnum),
+        (AT_BASE, interp_base),
+        (AT_FLAGS, 0),
+        (AT_ENTRY, image.entry),
+        (AT_UID, cred.uid as usize),
+        (AT_EUID, cred.euid as usize),
+        (AT_GID, cred.gid as usize),
+        (AT_EGID, cred.egid as usize),
+        (AT_SECURE, secure as usize),
+        (AT_SYSINFO_EHDR, vdso_base),
+    ];
+    let (ustack_pointer, auxv) =
+        init_user_stack(uspace, &app.path, args, envs, &auxv, stack_limit, image.stack_exec)?;
+    crate::signal::map_sigreturn_trampoline(uspace)?;
+    Ok((entry, ustack_pointer, auxv))
+}
+
+/// Maps the loadable segments of the ELF file `elf` into `uspace`.
+///
+/// `ET_DYN` objects are relocated to `dyn_base`, while `ET_EXEC` objects are
+/// loaded at their linked addresses.
+fn load_elf(elf: &ElfFile, dyn_base: usize, uspace: &mut AddrSpace) -> io::Result<ElfImage> {
+    let ElfFile {
+        file, ehdr, phdrs, ..
+    } = elf;
+    let phoff = ehdr.e_phoff as usize;
+
+    let bias = elf.bias(dyn_base).ok_or(io::Error::InvalidData)?;
+
+    // The program header table is either described by `PT_PHDR`, or found
+    // in the loadable segment that covers it in the file.
+    let phdr = phdrs
+        .iter()
+        .find(|phdr| phdr.p_type == PT_PHDR)
+        .map(|phdr| phdr.p_vaddr as usize)
+        .or_else(|| {
+            phdrs
+                .iter()
+                .filter(|phdr| phdr.p_type == PT_LOAD)
+                .find(|phdr| {
+                    let offset = phoff as u64;
+                    phdr.p_offset <= offset && offset < phdr.p_offset + phdr.p_filesz
+                })
+                .map(|phdr| (phdr.p_vaddr + phoff as u64 - phdr.p_offset) as usize)
+        })
+        .map_or(0, |phdr| phdr + bias);
+
+    let loads: Vec<&ProgramHeader> = phdrs.iter().filter(|phdr| phdr.p_type == PT_LOAD).collect();
+    let node = file.mmap_node(false)?;
+    let mut image_end = 0;
+    for area in image_areas(&loads, bias) {
+        debug!("{:#x} - {:#x} {:?}", area.start, area.end, area.flags);
+        let size = area.end - area.start;
+        image_end = image_end.max(area.end);
+        if let Some(offset) = area.offset {
+            uspace.map_file(
+                area.start.into(),
+                size,
+                area.flags,
+                node.clone(),
+                offset,
+                false,
+            )?;
+            continue;
+        }
+
+        // Only the file content is copied: frames are zeroed on allocation,
+        // which covers `.bss`.
+        uspace.map_alloc(area.start.into(), size, area.flags, true)?;
+        for phdr in &loads {
+            let vaddr = phdr.p_vaddr as usize + bias;
+            let start = vaddr.max(area.start);
+            let end = (vaddr + phdr.p_filesz as usize).min(area.end);
+            if start < end {
+                let mut data = vec![0u8; end - start];
+                read_exact_at(file, phdr.p_offset + (start - vaddr) as u64, &mut data)?;
+                uspace.write(VirtAddr::from(start), &data)?;
+            }
+        }
+    }
+
+    // Returning to a kernel or non-canonical address would fault in the
+    // kernel instead of the app.
+    let entry = ehdr.e_entry as usize + bias;
+    if !uspace.contains_range(entry.into(), 1) {
+        return Err(io::Error::InvalidData);
+    }
+
+    let stack_exec = phdrs
+        .iter()
+        .find(|phdr| phdr.p_type == PT_GNU_STACK)
+        .is_some_and(|phdr| phdr.p_flags & PF_X != 0);
+
+    Ok(ElfImage {
+        bias,
+        entry,
+        phdr,
+        phent: ehdr.e_phentsize as usize,
+        phnum: ehdr.e_phnum as usize,
+        stack_exec,
+        end: image_end,
+    })
+}
+
+/// Maps the user stack at the top of `uspace` (lowered by a random offset
+/// with ASLR), which may grow up to `limit` bytes (executable only if `exec`
+/// is set), and pushes the initial process state onto it, as the System V ABI
+/// describes:
+///
+/// ```text
+/// +------------------------+ <- stack top
+/// | execfn                 | <- AT_EXECFN
+/// | argument and env strs  |
+/// | platform string        | <- AT_PLATFORM
+/// | 16 random bytes        | <- AT_RANDOM
+/// +------------------------+ (aligned to 16 bytes)
+/// | auxv (terminated by    |
+/// |       AT_NULL)         |
+/// | envp[] (NULL ended)    |
+/// | argv[] (NULL ended)    |
+/// | argc                   | <- returned stack pointer
+/// +------------------------+
+/// ```
+///
+/// The entries of `auxv` come first, followed by the ones that point into
+/// the stack. Returns the stack pointer and all entries of the auxiliary
+/// vector.
+fn init_user_stack(
+    uspace: &mut AddrSpace,
+    execfn: &str,
+    args: &[String],
+    envs: &[String],
+    auxv: &[(usize, usize)],
+    limit: usize,
+    exec: bool,
+) -> io::Result<(VirtAddr, Vec<(usize, usize)>)> {
+    let ustack_top = uspace.end() - aslr::random_offset(STACK_RANDOM_RANGE);
+    let ustack_vaddr = ustack_top - USER_STACK_SIZE;
+    ax_println!(
+        "Mapping user stack: {:#x?} -> {:#x?}",
+        ustack_vaddr, ustack_top
+    );
+    let mut flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    if exec {
+        flags |= MappingFlags::EXECUTE;
+    }
+    uspace.init_stack(ustack_top, USER_STACK_SIZE, limit, flags)?;
+
+    let mut sp = ustack_top.as_usize();
+    let mut push = |data: &[u8]| -> io::Result<usize> {
+        sp -= data.len();
+        if sp < ustack_vaddr.as_usize() {
+            return Err(io::Error::NoMemory);
+        }
+        uspace.write(VirtAddr::from(sp), data)?;
+        Ok(sp)
+    };
+    let mut push_str = |s: &str| -> io::Result<usize> {
+        push(&[0])?;
+        push(s.as_bytes())
+    };
+    let execfn = push_str(execfn)?;
+    let envp = envs
+        .iter()
+        .map(|env| push_str(env))
+        .collect::<io::Result<Vec<_>>>()?;
+    let argv = args
+        .iter()
+        .map(|arg| push_str(arg))
+        .collect::<io::Result<Vec<_>>>()?;
+    let platform = PLATFORM.map(&mut push_str).transpose()?;
+    let random = push(&axhal::misc::random().to_le_bytes())?;
+
+    let mut auxv = auxv.to_vec();
+    auxv.extend(platform.map(|platform| (AT_PLATFORM, platform)));
+    auxv.extend([(AT_RANDOM, random), (AT_EXECFN, execfn), (AT_NULL, 0)]);
+
+    let mut words = Vec::with_capacity(argv.len() + envp.len() + auxv.len() * 2 + 3);
+    words.push(argv.len());
+    words.extend(argv);
+    words.push(0);
+    words.extend(envp);
+    words.push(0);
+    for &(key, value) in &auxv {
+        words.push(key);
+        words.push(value);
+    }
+    let words: Vec<u8> = words.iter().flat_map(|w| w.to_ne_bytes()).collect();
+
+    // `sp` must be 16-byte aligned after pushing `argc`.
+    let sp = (random - words.len()) & !0xf;
+    if sp < ustack_vaddr.as_usize() {
+        return Err(io::Error::NoMemory);
+    }
+    uspace.write(VirtAddr::from(sp), &words)?;
+    Ok((sp.into(), auxv))
+}
+
+/// Reads the ELF header and the program headers of `file`, keeping the ones
+/// the loader uses.
+///
+/// Fails with `ENOEXEC` if it is not an executable or a shared object for
+/// this machine, or the headers are malformed.
+fn load_elf_phdrs(file: &File) -> LinuxResult<(FileHeader<AnyEndian>, Vec<ProgramHeader>)> {
     let mut buf: [u8; ELF_HEAD_BUF_SIZE] = [0; ELF_HEAD_BUF_SIZE];
-    file.read(&mut buf)?;
+    let len = file.read_at(0, &mut buf)?;
 
-    let ehdr = ElfBytes::<AnyEndian>::parse_elf_header(&buf[..]).unwrap();
+    let ehdr =
+        ElfBytes::<AnyEndian>::parse_elf_header(&buf[..len]).map_err(|_| LinuxError::ENOEXEC)?;
+    if ehdr.class != Class::ELF64
+        || ehdr.e_machine != ELF_MACHINE
+        || !matches!(ehdr.e_type, ET_EXEC | ET_DYN)
+    {
+        return Err(LinuxError::ENOEXEC);
+    }
     info!("e_entry: {:#X}", ehdr.e_entry);
 
     let phnum = ehdr.e_phnum as usize;
     // Validate phentsize before trying to read the table so that we can error early for corrupted files
-    let entsize = ProgramHeader::validate_entsize(ehdr.class, ehdr.e_phentsize as usize).unwrap();
-    let size = entsize.checked_mul(phnum).unwrap();
-    assert!(size > 0 && size <= PAGE_SIZE_4K);
-    let phoff = ehdr.e_phoff;
+    let entsize = ProgramHeader::validate_entsize(ehdr.class, ehdr.e_phentsize as usize)
+        .map_err(|_| LinuxError::ENOEXEC)?;
+    let size = entsize
+        .checked_mul(phnum)
+        .filter(|&size| size > 0 && size <= PAGE_SIZE_4K)
+        .ok_or(LinuxError::ENOEXEC)?;
     let mut buf = alloc::vec![0u8; size];
-    let _ = file.seek(SeekFrom::Start(phoff));
-    file.read(&mut buf)?;
+    read_exact_at(file, ehdr.e_phoff, &mut buf).map_err(|_| LinuxError::ENOEXEC)?;
     let phdrs = SegmentTable::new(ehdr.endianness, ehdr.class, &buf[..]);
 
     let phdrs: Vec<ProgramHeader> = phdrs
         .iter()
-        .filter(|phdr| phdr.p_type == PT_LOAD || phdr.p_type == PT_INTERP)
+        .filter(|phdr| matches!(phdr.p_type, PT_LOAD | PT_INTERP | PT_PHDR | PT_GNU_STACK))
         .collect();
-    Ok((phdrs, ehdr.e_entry as usize, ehdr.e_phoff as usize, ehdr.e_phnum as usize))
+    let loads_valid = phdrs
+        .iter()
+        .filter(|phdr| phdr.p_type == PT_LOAD)
+        .all(|phdr| {
+            phdr.p_filesz <= phdr.p_memsz
+                && phdr.p_vaddr.checked_add(phdr.p_memsz).is_some()
+                && phdr.p_offset.checked_add(phdr.p_filesz).is_some()
+        });
+    // The entry point must be in a loadable segment.
+    let entry_valid = phdrs
+        .iter()
+        .filter(|phdr| phdr.p_type == PT_LOAD)
+        .any(|phdr| phdr.p_vaddr <= ehdr.e_entry && ehdr.e_entry < phdr.p_vaddr + phdr.p_memsz);
+    if !loads_valid || !entry_valid {
+        return Err(LinuxError::ENOEXEC);
+    }
+    Ok((ehdr, phdrs))
 }
@@ -11,72 +11,137 @@ extern crate axlog;
 mod task;
 mod syscall;
 mod loader;
+mod mm;
+mod signal;
+mod process;
+mod futex;
+mod fs;
+mod coredump;
+mod vdso;
+mod time;
+mod rlimit;
+mod shm;
+mod aslr;
+mod seccomp;
+mod cred;
+mod procfs;
+mod cmdline;
 
-use axstd::io;
 use axhal::paging::MappingFlags;
-use axhal::arch::UspaceContext;
+use axhal::arch::{TrapFrame, UspaceContext};
 use axhal::mem::VirtAddr;
 use axsync::Mutex;
+use axtask::TaskExtRef;
+use axhal::trap::{register_trap_handler, UserException, PAGE_FAULT, USER_EXCEPTION};
 use alloc::sync::Arc;
 use alloc::string::String;
-use alloc::collections::BTreeMap;
-use axmm::AddrSpace;
-use loader::load_user_app;
+use axfs::cred::Cred;
+use loader::{load_user_app, UserApp};
+use arceos_posix_api::{self as api, FdTable};
 
-const USER_STACK_SIZE: usize = 0x10000;
+const APP_PATH: &str = "/sbin/fileops";
 const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
 
 #[cfg_attr(feature = "axstd", no_mangle)]
 fn main() {
+    let cmdline = cmdline::Cmdline::load();
+    vdso::init();
+    aslr::init(&cmdline);
+
     // A new address space for user app.
     let mut uspace = axmm::new_user_aspace().unwrap();
 
-    // Load user app binary file into address space.
-    let entry = match load_user_app("/sbin/fileops", &mut uspace) {
-        Ok(e) => e,
-        Err(err) => panic!("Cannot load app! {:?}", err),
+    // Load user app binary file into address space, and init user stack.
+    let args = [String::from(APP_PATH)];
+    let stack_limit = rlimit::default_stack_limit();
+    let app = match UserApp::open(APP_PATH) {
+        Ok(app) => app,
+        Err(err) => panic!("Cannot open app! {:?}", err),
     };
+    // The init process runs as root.
+    let (entry, ustack_top, auxv) =
+        match load_user_app(&app, &args, &[], &Cred::root(), stack_limit, &mut uspace) {
+            Ok(e) => e,
+            Err(err) => panic!("Cannot load app! {:?}", err),
+        };
     ax_println!("entry: {:#x}", entry);
-
-    // Init user stack.
-    let ustack_top = init_user_stack(&mut uspace, true).unwrap();
     ax_println!("New user address space: {:#x?}", uspace);
 
-    // Let's kick off the user process.
-    let user_task = task::spawn_user_task(
-        Arc::new(Mutex::new(uspace)),
-        UspaceContext::new(entry, ustack_top),
-    );
+    syscall::init_syscalls();
+    axsyscall::set_tracing(cmdline.enabled("strace"));
+    // Each user process owns its file descriptor table.
+    api::set_current_fd_table_fn(task::current_fd_table);
+    axsyscall::set_current_aspace_fn(task::current_aspace);
+    axfs::cred::set_current_cred_fn(task::current_cred);
+    axfs::procfs::set_process_source(procfs::source());
+
+    // Let's kick off the user process, which is the init process.
+    let user_task = task::spawn_user_task(task::new_user_task(
+        |tid| {
+            let init = process::Process::new(tid, None);
+            init.set_exec_info(process::ExecInfo {
+                path: String::from(APP_PATH),
+                args: args.to_vec(),
+                auxv,
+            });
+            task::TaskExt::new(
+                init,
+                UspaceContext::new(entry, ustack_top),
+                Arc::new(Mutex::new(uspace)),
+                Arc::new(FdTable::with_stdio()),
+                Arc::new(Mutex::new(signal::SignalActions::new())),
+                signal::SignalSet::empty(),
+                seccomp::Seccomp::default(),
+            )
+        },
+        None,
+    ));
+    let init = user_task.task_ext().process.clone();
 
-    // Wait for user process to exit ...
-    let exit_code = user_task.join();
+    // Wait for user process to exit, i.e., its last thread ...
+    let mut exit_code = user_task.join();
+    while let Some(task) = init.threads().into_iter().next() {
+        exit_code = task.join();
+    }
     ax_println!("monolithic kernel exit [{:?}] normally!", exit_code);
 }
 
-fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtAddr> {
-    let ustack_top = uspace.end();
-    let ustack_vaddr = ustack_top - crate::USER_STACK_SIZE;
-    ax_println!(
-        "Mapping user stack: {:#x?} -> {:#x?}",
-        ustack_vaddr, ustack_top
-    );
-    uspace.map_alloc(
-        ustack_vaddr,
-        crate::USER_STACK_SIZE,
-        MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
-        populating,
-    ).unwrap();
-
-    let app_name = "hello";
-    let av = BTreeMap::new();
-    let (stack_data, ustack_pointer) = kernel_elf_parser::get_app_stack_region(
-        &[String::from(app_name)],
-        &[],
-        &av,
-        ustack_vaddr,
-        crate::USER_STACK_SIZE,
-    );
-    uspace.write(VirtAddr::from_usize(ustack_pointer), stack_data.as_slice())?;
+#[register_trap_handler(PAGE_FAULT)]
+fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
+    if is_user {
+        let curr = axtask::current();
+        let mut aspace = curr.task_ext().aspace.lock();
+        if !aspace.handle_page_fault(vaddr, access_flags) {
+            // SEGV_ACCERR if the page is mapped, but not with the permission
+            // needed, and SEGV_MAPERR otherwise.
+            let mapped = aspace
+                .areas()
+                .any(|(start, size, _)| (start..start + size).contains(&vaddr));
+            drop(aspace);
+            let code = if mapped { 2 } else { 1 };
+            signal::force_signal(signal::SigInfo::from_fault(
+                signal::SIGSEGV,
+                code,
+                vaddr.as_usize(),
+            ));
+        }
+        true
+    } else {
+        false
+    }
+}
 
-    Ok(ustack_pointer.into())
+#[register_trap_handler(USER_EXCEPTION)]
+fn handle_user_exception(tf: &TrapFrame, exception: UserException) -> bool {
+    // `si_code`s are ILL_ILLOPC, BUS_ADRALN, SEGV_ACCERR and FPE_INTDIV
+    // respectively.
+    let (sig, code) = match exception {
+        UserException::IllegalInstruction => (signal::SIGILL, 1),
+        UserException::Misaligned => (signal::SIGBUS, 1),
+        UserException::AccessFault => (signal::SIGSEGV, 2),
+        UserException::Arithmetic => (signal::SIGFPE, 1),
+    };
+    let pc = UspaceContext::from(tf).get_ip();
+    signal::force_signal(signal::SigInfo::from_fault(sig, code, pc));
+    true
 }
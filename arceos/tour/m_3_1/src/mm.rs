@@ -0,0 +1,304 @@
+//! Syscalls that manage the address space of the current user process.
+
+use arceos_posix_api as api;
+use axerrno::{AxError, AxResult, LinuxError, LinuxResult};
+use axhal::mem::{VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::MappingFlags;
+use axmm::{AddrSpace, SharedPages};
+use axsyscall::syscall_body;
+use axtask::{current, TaskExtRef};
+use memory_addr::VirtAddrRange;
+
+use crate::shm::MemFd;
+
+const PROT_READ: u32 = 1;
+const PROT_WRITE: u32 = 2;
+const PROT_EXEC: u32 = 4;
+
+/// `flags` of `mmap`: the mapping is shared with other mappings of the same
+/// memory, including the ones inherited by children.
+const MAP_SHARED: u32 = 0x1;
+/// `flags` of `mmap`: the mapping is private and copied on write.
+const MAP_PRIVATE: u32 = 0x2;
+/// `flags` of `mmap`: like `MAP_SHARED`, but unknown flags are rejected.
+const MAP_SHARED_VALIDATE: u32 = 0x3;
+/// `flags` of `mmap`: the mapping is placed at `addr` exactly.
+const MAP_FIXED: u32 = 0x10;
+/// `flags` of `mmap`: the mapping is not backed by a file.
+const MAP_ANONYMOUS: u32 = 0x20;
+/// The `mmap` flags that are understood. The others are hints, and ignored.
+const MAP_KNOWN_FLAGS: u32 = MAP_SHARED_VALIDATE | MAP_FIXED | MAP_ANONYMOUS | 0x4000 | 0x20000;
+
+/// `flags` of `msync`: schedule the write-back and return.
+const MS_ASYNC: u32 = 1;
+/// `flags` of `msync`: invalidate other mappings of the same file.
+const MS_INVALIDATE: u32 = 2;
+/// `flags` of `msync`: write back and wait for it to complete.
+const MS_SYNC: u32 = 4;
+
+/// `flags` of `mremap`: the mapping may be moved.
+const MREMAP_MAYMOVE: u32 = 1;
+/// `flags` of `mremap`: the mapping is moved to `new_addr`.
+const MREMAP_FIXED: u32 = 2;
+
+/// Moves the program break of the current process to `addr`, with the heap
+/// populated lazily.
+///
+/// Returns the new program break, or the current one if `addr` is 0 or the
+/// break cannot be moved, as the Linux syscall does (`sbrk` is implemented
+/// on top of it by the C library).
+pub fn sys_brk(addr: usize) -> isize {
+    let curr = current();
+    let mut aspace = curr.task_ext().aspace.lock();
+    if addr != 0 {
+        let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+        if let Err(e) = aspace.set_brk(VirtAddr::from(addr), flags) {
+            debug!("sys_brk: cannot move the break to {:#x}: {:?}", addr, e);
+        }
+    }
+    aspace.brk().as_usize() as isize
+}
+
+/// Converts the `prot` argument of `mmap` and `mprotect` to user mapping
+/// flags.
+fn prot_to_flags(prot: u32) -> LinuxResult<MappingFlags> {
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let mut flags = MappingFlags::USER;
+    if prot & PROT_READ != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if prot & PROT_WRITE != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if prot & PROT_EXEC != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    Ok(flags)
+}
+
+/// Finds a free area of `size` bytes for a new mapping, at `hint` if
+/// possible, or anywhere above the mmap base of `aspace` (or below it as a
+/// last resort) if `hint` is 0 or taken.
+pub fn find_mmap_area(aspace: &AddrSpace, hint: usize, size: usize) -> LinuxResult<VirtAddr> {
+    let range = VirtAddrRange::new(aspace.base(), aspace.end());
+    let hint = if hint == 0 {
+        aspace.mmap_base()
+    } else {
+        VirtAddr::from(hint.next_multiple_of(PAGE_SIZE_4K))
+    };
+    aspace
+        .find_free_area(hint, size, range)
+        .or_else(|| aspace.find_free_area(aspace.mmap_base(), size, range))
+        .or_else(|| aspace.find_free_area(VirtAddr::from(PAGE_SIZE_4K), size, range))
+        .ok_or(LinuxError::ENOMEM)
+}
+
+/// Copies the content of `pages` at `offset` into the `size` bytes populated
+/// at `start`, a page at a time, so that a large mapping does not need a
+/// buffer of its size in the kernel heap.
+fn copy_pages(
+    aspace: &mut AddrSpace,
+    start: VirtAddr,
+    size: usize,
+    pages: &SharedPages,
+    offset: usize,
+) -> AxResult {
+    let mut buf = [0; PAGE_SIZE_4K];
+    for page in (0..size).step_by(PAGE_SIZE_4K) {
+        let len = pages.read_at(offset.saturating_add(page), &mut buf);
+        if len == 0 {
+            break;
+        }
+        aspace.write(start + page, &buf[..len])?;
+    }
+    Ok(())
+}
+
+/// Maps `length` bytes at `addr` (exactly with `MAP_FIXED`, or as a hint),
+/// and returns the start of the mapping.
+///
+/// Anonymous mappings are populated lazily. `MAP_SHARED` ones are backed by
+/// shared pages, which a child created by `fork` keeps sharing. A shared
+/// mapping of a memfd refers to the pages of the memfd, while a private one
+/// gets a copy of their content. Regular files are read in page by page on
+/// demand, and the pages of a shared mapping are written back by `msync` or
+/// `munmap`.
+pub fn sys_mmap(
+    addr: usize,
+    length: usize,
+    prot: u32,
+    flags: u32,
+    fd: i32,
+    offset: usize,
+) -> isize {
+    syscall_body!(sys_mmap, {
+        let shared = match flags & MAP_SHARED_VALIDATE {
+            MAP_SHARED => true,
+            MAP_PRIVATE => false,
+            MAP_SHARED_VALIDATE if flags & !MAP_KNOWN_FLAGS != 0 => {
+                return Err(LinuxError::EOPNOTSUPP)
+            }
+            MAP_SHARED_VALIDATE => true,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        if length == 0 || offset % PAGE_SIZE_4K != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let map_flags = prot_to_flags(prot)?;
+        let size = length.div_ceil(PAGE_SIZE_4K) * PAGE_SIZE_4K;
+        let (memfd, node) = if flags & MAP_ANONYMOUS != 0 {
+            (None, None)
+        } else {
+            match api::get_file_like(fd)?.into_any().downcast::<MemFd>() {
+                Ok(memfd) => (Some(memfd), None),
+                Err(_) => {
+                    let writable = shared && prot & PROT_WRITE != 0;
+                    (None, Some(api::file_mmap_node(fd, writable)?))
+                }
+            }
+        };
+
+        let curr = current();
+        let mut aspace = curr.task_ext().aspace.lock();
+        let start = if flags & MAP_FIXED != 0 {
+            if addr % PAGE_SIZE_4K != 0 || !aspace.contains_range(VirtAddr::from(addr), size) {
+                return Err(LinuxError::EINVAL);
+            }
+            aspace
+                .unmap(VirtAddr::from(addr), size)
+                .map_err(|_| LinuxError::EINVAL)?;
+            VirtAddr::from(addr)
+        } else {
+            find_mmap_area(&aspace, addr, size)?
+        };
+        let result = match (memfd, node, shared) {
+            (_, Some(node), _) => {
+                aspace.map_file(start, size, map_flags, node, offset as u64, shared)
+            }
+            (None, None, false) => aspace.map_alloc(start, size, map_flags, false),
+            (None, None, true) => {
+                aspace.map_shared(start, size, map_flags, SharedPages::new(size), 0)
+            }
+            (Some(memfd), None, true) => {
+                aspace.map_shared(start, size, map_flags, memfd.pages().clone(), offset)
+            }
+            (Some(memfd), None, false) => aspace
+                .map_alloc(start, size, map_flags, true)
+                .and_then(|_| copy_pages(&mut aspace, start, size, memfd.pages(), offset)),
+        };
+        result.map_err(|e| match e {
+            AxError::NoMemory => LinuxError::ENOMEM,
+            _ => LinuxError::EINVAL,
+        })?;
+        Ok(start.as_usize())
+    })
+}
+
+/// Removes the mappings in `[addr, addr + length)`. Areas partially covered
+/// by the range are shrunk or split.
+pub fn sys_munmap(addr: usize, length: usize) -> isize {
+    syscall_body!(sys_munmap, {
+        if addr % PAGE_SIZE_4K != 0 || length == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let curr = current();
+        curr.task_ext()
+            .aspace
+            .lock()
+            .unmap(VirtAddr::from(addr), length.div_ceil(PAGE_SIZE_4K) * PAGE_SIZE_4K)
+            .map_err(|_| LinuxError::EINVAL)?;
+        Ok(0)
+    })
+}
+
+/// Writes the modified pages of shared file mappings in `[addr, addr +
+/// length)` back to their files.
+///
+/// The write-back is always done before returning, and other mappings of
+/// the files always see the same pages, so `MS_ASYNC` and `MS_INVALIDATE`
+/// have nothing more to do.
+pub fn sys_msync(addr: usize, length: usize, flags: u32) -> isize {
+    syscall_body!(sys_msync, {
+        if addr % PAGE_SIZE_4K != 0
+            || flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0
+            || flags & (MS_ASYNC | MS_SYNC) == MS_ASYNC | MS_SYNC
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let curr = current();
+        curr.task_ext()
+            .aspace
+            .lock()
+            .msync(VirtAddr::from(addr), length.div_ceil(PAGE_SIZE_4K) * PAGE_SIZE_4K)
+            .map_err(|_| LinuxError::ENOMEM)?;
+        Ok(0)
+    })
+}
+
+/// Changes the protection of the pages in `[addr, addr + length)`, which must
+/// all be mapped. Areas partially covered by the range are split.
+pub fn sys_mprotect(addr: usize, length: usize, prot: u32) -> isize {
+    syscall_body!(sys_mprotect, {
+        if addr % PAGE_SIZE_4K != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let flags = prot_to_flags(prot)?;
+        // The vDSO data page is shared by all processes.
+        if flags.contains(MappingFlags::WRITE) && crate::vdso::overlaps_vvar(addr, length) {
+            return Err(LinuxError::EACCES);
+        }
+        let curr = current();
+        curr.task_ext()
+            .aspace
+            .lock()
+            .protect(VirtAddr::from(addr), length.div_ceil(PAGE_SIZE_4K) * PAGE_SIZE_4K, flags)
+            .map_err(|e| match e {
+                AxError::NoMemory => LinuxError::ENOMEM,
+                _ => LinuxError::EINVAL,
+            })?;
+        Ok(0)
+    })
+}
+
+/// Resizes the anonymous mapping at `old_addr`, possibly moving it if
+/// `MREMAP_MAYMOVE` is given, or to `new_addr` with `MREMAP_FIXED`.
+///
+/// Returns the new address of the mapping.
+pub fn sys_mremap(
+    old_addr: usize,
+    old_size: usize,
+    new_size: usize,
+    flags: u32,
+    new_addr: usize,
+) -> isize {
+    syscall_body!(sys_mremap, {
+        if flags & !(MREMAP_MAYMOVE | MREMAP_FIXED) != 0
+            || (flags & MREMAP_FIXED != 0 && flags & MREMAP_MAYMOVE == 0)
+            || old_addr % PAGE_SIZE_4K != 0
+            || new_size == 0
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let new_addr = (flags & MREMAP_FIXED != 0).then(|| VirtAddr::from(new_addr));
+        let curr = current();
+        let new_addr = curr
+            .task_ext()
+            .aspace
+            .lock()
+            .resize(
+                VirtAddr::from(old_addr),
+                old_size.div_ceil(PAGE_SIZE_4K) * PAGE_SIZE_4K,
+                new_size.div_ceil(PAGE_SIZE_4K) * PAGE_SIZE_4K,
+                flags & MREMAP_MAYMOVE != 0,
+                new_addr,
+            )
+            .map_err(|e| match e {
+                AxError::BadAddress => LinuxError::EFAULT,
+                AxError::NoMemory => LinuxError::ENOMEM,
+                _ => LinuxError::EINVAL,
+            })?;
+        Ok(new_addr.as_usize())
+    })
+}
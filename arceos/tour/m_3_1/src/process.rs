@@ -0,0 +1,538 @@
+//! User processes and the process tree.
+//!
+//! Every user process has a [`Process`] in the global process table, which
+//! links it to its parent and children. When the last thread of a process
+//! exits, the process becomes a zombie that keeps its wait status until the
+//! parent reaps it with `wait4`. Orphans are adopted by the init process.
+//!
+//! A thread can exit alone with `exit`, while `exit_group` and fatal signals
+//! terminate the whole thread group: the other threads are sent `SIGKILL`,
+//! and exit when they next return to user space. `execve` kills the other
+//! threads the same way, but the process lives on in the calling thread.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use arceos_posix_api::ctypes::rlimit;
+use axerrno::{LinuxError, LinuxResult};
+use axfs::cred::Cred;
+use axsync::Mutex;
+use axsyscall::{syscall_body, UserPtr};
+use axtask::{current, AxTaskRef, TaskExtRef, WaitQueue};
+
+use crate::futex;
+use crate::rlimit::{default_rlimits, RLIM_NLIMITS};
+use crate::signal::{self, SigInfo};
+
+/// The process ID of the init process, which adopts orphans.
+static INIT_PID: AtomicUsize = AtomicUsize::new(0);
+
+/// `options` of `wait4`: return immediately if no child has exited.
+const WNOHANG: u32 = 1;
+
+/// Set in the wait status if the process dumped core.
+const WCOREFLAG: i32 = 0x80;
+
+/// `si_code` of `SIGCHLD`: the child has exited.
+const CLD_EXITED: i32 = 1;
+/// `si_code` of `SIGCHLD`: the child was killed by a signal.
+const CLD_KILLED: i32 = 2;
+/// `si_code` of `SIGCHLD`: the child was killed by a signal and dumped core.
+const CLD_DUMPED: i32 = 3;
+
+/// The size of `struct rusage`.
+const RUSAGE_SIZE: usize = 144;
+
+/// The program that a process runs, recorded when it is loaded.
+#[derive(Debug, Default, Clone)]
+pub struct ExecInfo {
+    /// The path of the executable.
+    pub path: String,
+    pub args: Vec<String>,
+    /// The auxiliary vector passed to the program, ending with `AT_NULL`.
+    pub auxv: Vec<(usize, usize)>,
+}
+
+/// A user process.
+pub struct Process {
+    pid: usize,
+    /// The process group ID.
+    pgid: AtomicUsize,
+    parent: Mutex<Weak<Process>>,
+    children: Mutex<Vec<Arc<Process>>>,
+    /// The threads that have not exited.
+    threads: Mutex<Vec<AxTaskRef>>,
+    /// The wait status, which is set when the process becomes a zombie.
+    exit_status: Mutex<Option<i32>>,
+    /// The wait status of the process, which is set once the thread group
+    /// starts to exit.
+    group_exit_status: Mutex<Option<i32>>,
+    /// The program that the process runs.
+    exec_info: Mutex<ExecInfo>,
+    /// The resource limits, indexed by `RLIMIT_*`.
+    rlimits: Mutex<[rlimit; RLIM_NLIMITS]>,
+    /// The user and group IDs, shared by all threads.
+    cred: Mutex<Arc<Cred>>,
+    /// When the process was created, since boot.
+    start_time: Duration,
+    /// Threads waiting for a child to exit.
+    child_exit_wq: WaitQueue,
+    /// Incremented whenever a child becomes a zombie.
+    child_events: AtomicUsize,
+}
+
+/// All processes that have not been reaped, keyed by their process IDs.
+static PROCESS_TABLE: Mutex<BTreeMap<usize, Arc<Process>>> = Mutex::new(BTreeMap::new());
+
+/// Returns the process ID of the init process.
+pub fn init_pid() -> usize {
+    INIT_PID.load(Ordering::Acquire)
+}
+
+impl Process {
+    /// Creates a process with the process ID `pid` and adds it to the process
+    /// table.
+    ///
+    /// As on Linux, `pid` is the thread ID of the main thread, so that
+    /// `getpid` and `gettid` agree in it.
+    ///
+    /// The process is a child of `parent` and in the same process group, or
+    /// the init process, the leader of a new process group, if `parent` is
+    /// `None`.
+    pub fn new(pid: usize, parent: Option<&Arc<Process>>) -> Arc<Self> {
+        if parent.is_none() {
+            INIT_PID.store(pid, Ordering::Release);
+        }
+        let process = Arc::new(Self {
+            pid,
+            pgid: AtomicUsize::new(parent.map_or(pid, |parent| parent.pgid())),
+            parent: Mutex::new(parent.map_or(Weak::new(), Arc::downgrade)),
+            children: Mutex::new(Vec::new()),
+            threads: Mutex::new(Vec::new()),
+            exit_status: Mutex::new(None),
+            group_exit_status: Mutex::new(None),
+            exec_info: Mutex::new(
+                parent.map_or_else(ExecInfo::default, |parent| parent.exec_info()),
+            ),
+            rlimits: Mutex::new(
+                parent.map_or_else(default_rlimits, |parent| *parent.rlimits.lock()),
+            ),
+            cred: Mutex::new(parent.map_or_else(|| Arc::new(Cred::root()), |parent| parent.cred())),
+            start_time: axhal::time::monotonic_time(),
+            child_exit_wq: WaitQueue::new(),
+            child_events: AtomicUsize::new(0),
+        });
+        if let Some(parent) = parent {
+            parent.children.lock().push(process.clone());
+        }
+        PROCESS_TABLE.lock().insert(pid, process.clone());
+        process
+    }
+
+    pub fn pid(&self) -> usize {
+        self.pid
+    }
+
+    pub fn pgid(&self) -> usize {
+        self.pgid.load(Ordering::Acquire)
+    }
+
+    pub fn set_pgid(&self, pgid: usize) {
+        self.pgid.store(pgid, Ordering::Release);
+    }
+
+    pub fn parent(&self) -> Option<Arc<Process>> {
+        self.parent.lock().upgrade()
+    }
+
+    /// The process ID of the parent, or 0 if there is none.
+    pub fn ppid(&self) -> usize {
+        self.parent().map_or(0, |parent| parent.pid)
+    }
+
+    pub fn is_zombie(&self) -> bool {
+        self.exit_status.lock().is_some()
+    }
+
+    /// The program that the process runs, which a child inherits from its
+    /// parent until it calls `execve`.
+    pub fn exec_info(&self) -> ExecInfo {
+        self.exec_info.lock().clone()
+    }
+
+    pub fn set_exec_info(&self, exec_info: ExecInfo) {
+        *self.exec_info.lock() = exec_info;
+    }
+
+    /// The limits of `resource`, which must be below `RLIM_NLIMITS`.
+    pub fn rlimit(&self, resource: u32) -> rlimit {
+        self.rlimits.lock()[resource as usize]
+    }
+
+    pub fn set_rlimit(&self, resource: u32, limits: rlimit) {
+        self.rlimits.lock()[resource as usize] = limits;
+    }
+
+    /// The credentials, which a child inherits from its parent. The init
+    /// process runs as root.
+    pub fn cred(&self) -> Arc<Cred> {
+        self.cred.lock().clone()
+    }
+
+    /// Updates the credentials with `f`. Credentials already handed out by
+    /// [`Process::cred`] are not affected.
+    pub fn update_cred<R>(&self, f: impl FnOnce(&mut Cred) -> R) -> R {
+        f(Arc::make_mut(&mut self.cred.lock()))
+    }
+
+    /// When the process was created, since boot.
+    pub fn start_time(&self) -> Duration {
+        self.start_time
+    }
+
+    /// Whether all threads of the process are exiting, after `exit_group` or
+    /// a fatal signal.
+    pub fn is_group_exiting(&self) -> bool {
+        self.group_exit_status.lock().is_some()
+    }
+
+    /// The threads of the process that have not exited.
+    pub fn threads(&self) -> Vec<AxTaskRef> {
+        self.threads.lock().clone()
+    }
+
+    /// Adds a thread to the process. `spawn` is called with the thread list
+    /// locked, so that the thread cannot exit before it is added.
+    pub fn add_thread(&self, spawn: impl FnOnce() -> AxTaskRef) -> AxTaskRef {
+        let mut threads = self.threads.lock();
+        let task = spawn();
+        threads.push(task.clone());
+        task
+    }
+
+    /// Makes all threads of the process exit, and the process has the wait
+    /// status `wait_status`. Threads other than the current one are killed by
+    /// `SIGKILL`.
+    ///
+    /// Nothing is done if the thread group is exiting already, so the first
+    /// wait status wins.
+    fn start_group_exit(&self, wait_status: i32) {
+        {
+            let mut status = self.group_exit_status.lock();
+            if status.is_some() {
+                return;
+            }
+            *status = Some(wait_status);
+        }
+        let curr = current();
+        for task in self.threads().iter().filter(|task| task.id() != curr.id()) {
+            signal::send_signal(
+                task,
+                SigInfo::from_process(signal::SIGKILL, signal::SI_KERNEL, self.pid),
+            );
+        }
+    }
+
+    /// Kills the other threads of the process for `execve`, and waits until
+    /// they have exited, so that none of them runs in or touches the address
+    /// space once it is torn down.
+    ///
+    /// Fails with `EAGAIN` if the thread group is exiting already, as the
+    /// current thread is about to be killed too.
+    pub fn kill_other_threads(&self) -> LinuxResult {
+        {
+            let mut status = self.group_exit_status.lock();
+            if status.is_some() {
+                return Err(LinuxError::EAGAIN);
+            }
+            // The killed threads take it for a group exit, and leave without
+            // taking the process down with them.
+            *status = Some(signal::SIGKILL as i32);
+        }
+        let curr = current();
+        // Threads may still be cloned until the others are gone.
+        loop {
+            let others: Vec<_> = self
+                .threads()
+                .into_iter()
+                .filter(|task| task.id() != curr.id())
+                .collect();
+            if others.is_empty() {
+                break;
+            }
+            for task in &others {
+                signal::send_signal(
+                    task,
+                    SigInfo::from_process(signal::SIGKILL, signal::SI_KERNEL, self.pid),
+                );
+            }
+            for task in others {
+                task.join();
+            }
+        }
+        *self.group_exit_status.lock() = None;
+        Ok(())
+    }
+
+    /// Wakes up the threads waiting for children, so that they can handle
+    /// newly arrived signals.
+    pub fn interrupt_wait(&self) {
+        self.child_exit_wq.notify_all(false);
+    }
+
+    /// Wakes up the threads waiting for children, as a child has exited.
+    fn notify_child_exit(&self) {
+        self.child_events.fetch_add(1, Ordering::AcqRel);
+        self.child_exit_wq.notify_all(false);
+    }
+}
+
+/// Returns the process with the process ID `pid`, which may be a zombie.
+pub fn find_process(pid: usize) -> Option<Arc<Process>> {
+    PROCESS_TABLE.lock().get(&pid).cloned()
+}
+
+/// Returns all processes, including zombies.
+pub fn processes() -> Vec<Arc<Process>> {
+    PROCESS_TABLE.lock().values().cloned().collect()
+}
+
+/// Terminates the current thread with `exit_code`. The other threads of the
+/// process keep running.
+pub fn exit_current(exit_code: i32) -> ! {
+    do_exit((exit_code & 0xff) << 8, exit_code)
+}
+
+/// Terminates all threads of the current process with `exit_code`.
+pub fn exit_group_current(exit_code: i32) -> ! {
+    let wait_status = (exit_code & 0xff) << 8;
+    current().task_ext().process.start_group_exit(wait_status);
+    do_exit(wait_status, exit_code)
+}
+
+/// Terminates the current thread, which is killed by the signal `sig`. The
+/// other threads of the process keep running, and the process is reported
+/// as killed by `sig` if it was the last thread.
+pub fn exit_thread_by_signal(sig: usize) -> ! {
+    do_exit(sig as i32 & 0x7f, 128 + sig as i32)
+}
+
+/// Terminates all threads of the current process, which is killed by the
+/// signal `sig`. `core_dumped` tells the parent whether a core file has been
+/// written.
+pub fn exit_current_by_signal(sig: usize, core_dumped: bool) -> ! {
+    let mut wait_status = sig as i32 & 0x7f;
+    if core_dumped {
+        wait_status |= WCOREFLAG;
+    }
+    current().task_ext().process.start_group_exit(wait_status);
+    do_exit(wait_status, 128 + sig as i32)
+}
+
+/// Removes the current thread from its process, after clearing its
+/// `clear_child_tid` word and waking up a futex waiter on it (as
+/// `pthread_join` does). If it is the last thread,
+/// the process becomes a zombie with `wait_status`, its children are adopted
+/// by init, and the parent is notified with `SIGCHLD`. The wait status of a
+/// group exit takes precedence over `wait_status`.
+fn do_exit(wait_status: i32, exit_code: i32) -> ! {
+    let curr = current();
+    let clear_child_tid = curr.task_ext().clear_child_tid() as usize;
+    if clear_child_tid != 0 {
+        futex::clear_child_tid(&curr.task_ext().aspace, clear_child_tid);
+    }
+    let process = curr.task_ext().process.clone();
+    let is_last = {
+        let mut threads = process.threads.lock();
+        threads.retain(|task| task.id() != curr.id());
+        threads.is_empty()
+    };
+    if is_last {
+        let wait_status = process.group_exit_status.lock().unwrap_or(wait_status);
+        *process.exit_status.lock() = Some(wait_status);
+        reparent_children(&process);
+        notify_parent(&process, wait_status);
+    }
+    axtask::exit(exit_code)
+}
+
+/// Hands the children of the exiting `process` over to init.
+fn reparent_children(process: &Arc<Process>) {
+    let children = core::mem::take(&mut *process.children.lock());
+    if children.is_empty() {
+        return;
+    }
+    let Some(init) = find_process(init_pid()).filter(|init| !Arc::ptr_eq(init, process)) else {
+        // Nobody can reap them any more.
+        let mut table = PROCESS_TABLE.lock();
+        for child in children.iter().filter(|child| child.is_zombie()) {
+            table.remove(&child.pid);
+        }
+        return;
+    };
+    for child in &children {
+        *child.parent.lock() = Arc::downgrade(&init);
+    }
+    let has_zombie = children.iter().any(|child| child.is_zombie());
+    init.children.lock().extend(children);
+    if has_zombie {
+        init.notify_child_exit();
+    }
+}
+
+/// Sends `SIGCHLD` to the parent of the exiting `process`, and wakes it up if
+/// it is waiting. If the parent ignores `SIGCHLD`, the process is reaped
+/// right away.
+fn notify_parent(process: &Arc<Process>, wait_status: i32) {
+    let Some(parent) = process.parent() else {
+        PROCESS_TABLE.lock().remove(&process.pid);
+        return;
+    };
+    let auto_reap = parent
+        .threads()
+        .first()
+        .is_some_and(|task| task.task_ext().sig_actions.lock().ignores_children());
+    if auto_reap {
+        parent
+            .children
+            .lock()
+            .retain(|child| !Arc::ptr_eq(child, process));
+        PROCESS_TABLE.lock().remove(&process.pid);
+    } else {
+        let code = if wait_status & 0x7f == 0 {
+            CLD_EXITED
+        } else if wait_status & WCOREFLAG != 0 {
+            CLD_DUMPED
+        } else {
+            CLD_KILLED
+        };
+        signal::send_process_signal(
+            &parent,
+            SigInfo::from_process(signal::SIGCHLD, code, process.pid),
+        );
+    }
+    parent.notify_child_exit();
+}
+
+/// Whether the child is selected by the `pid` argument of `wait4`.
+fn wait_selects(pid: isize, parent: &Process, child: &Process) -> bool {
+    match pid {
+        -1 => true,
+        0 => child.pgid() == parent.pgid(),
+        pid if pid > 0 => child.pid == pid as usize,
+        pgid => child.pgid() == pgid.unsigned_abs(),
+    }
+}
+
+/// Waits for a child process selected by `pid` to exit, and reaps it.
+///
+/// `pid` selects any child if it is -1, a child in the caller's process group
+/// if it is 0, a child in the process group `-pid` if it is less than -1, or
+/// the child `pid` otherwise. Only `WNOHANG` of `options` is supported.
+///
+/// Returns the process ID of the reaped child, or 0 if `WNOHANG` is given and
+/// no selected child has exited.
+pub fn sys_wait4(
+    pid: isize,
+    wstatus: UserPtr<i32>,
+    options: u32,
+    rusage: UserPtr<[u8; RUSAGE_SIZE]>,
+) -> isize {
+    syscall_body!(sys_wait4, {
+        let curr = current();
+        let process = curr.task_ext().process.clone();
+        loop {
+            // Read it before checking the children, so that no exit is missed.
+            let events = process.child_events.load(Ordering::Acquire);
+            let zombie = {
+                let mut children = process.children.lock();
+                if !children.iter().any(|child| wait_selects(pid, &process, child)) {
+                    return Err(LinuxError::ECHILD);
+                }
+                children
+                    .iter()
+                    .position(|child| wait_selects(pid, &process, child) && child.is_zombie())
+                    .map(|i| children.remove(i))
+            };
+
+            if let Some(child) = zombie {
+                PROCESS_TABLE.lock().remove(&child.pid);
+                let status = child.exit_status.lock().unwrap();
+                if !wstatus.is_null() {
+                    wstatus.write(status)?;
+                }
+                if !rusage.is_null() {
+                    rusage.write([0; RUSAGE_SIZE])?;
+                }
+                return Ok(child.pid as isize);
+            }
+            if options & WNOHANG != 0 {
+                return Ok(0);
+            }
+
+            // The condition is checked with the run queue locked, so it must
+            // not take any sleeping lock.
+            process.child_exit_wq.wait_until(|| {
+                process.child_events.load(Ordering::Acquire) != events
+                    || curr.task_ext().signals.has_deliverable()
+            });
+            if curr.task_ext().signals.has_deliverable() {
+                return Err(LinuxError::EINTR);
+            }
+        }
+    })
+}
+
+/// Returns the process ID of the parent of the calling process.
+pub fn sys_getppid() -> isize {
+    current().task_ext().process.ppid() as isize
+}
+
+/// Returns the process group ID of the process `pid`, or of the calling
+/// process if `pid` is 0.
+pub fn sys_getpgid(pid: usize) -> isize {
+    syscall_body!(sys_getpgid, {
+        let process = if pid == 0 {
+            current().task_ext().process.clone()
+        } else {
+            find_process(pid).ok_or(LinuxError::ESRCH)?
+        };
+        Ok(process.pgid() as isize)
+    })
+}
+
+/// Moves the process `pid` (or the calling process if `pid` is 0) into the
+/// process group `pgid` (or its own process group if `pgid` is 0).
+///
+/// The process must be the caller or one of its children.
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    syscall_body!(sys_setpgid, {
+        let curr = current();
+        let caller = &curr.task_ext().process;
+        let process = if pid == 0 || pid == caller.pid {
+            caller.clone()
+        } else {
+            caller
+                .children
+                .lock()
+                .iter()
+                .find(|child| child.pid == pid)
+                .cloned()
+                .ok_or(LinuxError::ESRCH)?
+        };
+        let pgid = if pgid == 0 { process.pid } else { pgid };
+        if pgid != process.pid
+            && !processes()
+                .iter()
+                .any(|p| p.pgid() == pgid && !p.is_zombie())
+        {
+            return Err(LinuxError::EPERM);
+        }
+        process.set_pgid(pgid);
+        Ok(0)
+    })
+}
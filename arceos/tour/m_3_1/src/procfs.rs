@@ -0,0 +1,114 @@
+//! The processes shown in `/proc`.
+//!
+//! `axfs` generates the files of `/proc/<pid>` from snapshots of the
+//! processes, which are taken here from the process table, the address
+//! spaces and the file descriptor tables whenever a file is read.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use axfs::procfs::{MemArea, ProcessInfo, ProcessSource, ProcessState};
+use axhal::paging::MappingFlags;
+use axmm::AddrSpace;
+use axtask::TaskExtRef;
+
+use crate::process::{self, Process};
+use crate::vdso;
+
+/// The processes of `/proc`, registered with
+/// [`axfs::procfs::set_process_source`].
+pub fn source() -> ProcessSource {
+    ProcessSource {
+        pids,
+        current_pid,
+        info,
+    }
+}
+
+fn pids() -> Vec<usize> {
+    process::processes().iter().map(|p| p.pid()).collect()
+}
+
+fn current_pid() -> Option<usize> {
+    let curr = axtask::current();
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return None;
+    }
+    Some(curr.task_ext().proc_id)
+}
+
+fn info(pid: usize) -> Option<ProcessInfo> {
+    let process = process::find_process(pid)?;
+    let threads = process.threads();
+    let exec_info = process.exec_info();
+    // The threads share the address space and the file descriptor table, so
+    // any one of them will do. A zombie has neither.
+    let (areas, fds) = match threads.first() {
+        Some(task) => {
+            let ext = task.task_ext();
+            let fd_table = &ext.fd_table;
+            let fds = fd_table
+                .fds()
+                .into_iter()
+                .filter_map(|fd| Some((fd as usize, fd_table.get(fd).ok()?.path())))
+                .collect();
+            (mem_areas(&ext.aspace.lock()), fds)
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+    Some(ProcessInfo {
+        pid,
+        ppid: process.ppid(),
+        pgid: process.pgid(),
+        state: process_state(&process),
+        exe: exec_info.path,
+        args: exec_info.args,
+        threads: threads.len(),
+        cred: process.cred(),
+        start_time: process.start_time(),
+        areas,
+        fds,
+    })
+}
+
+fn process_state(process: &Process) -> ProcessState {
+    if process.is_zombie() {
+        ProcessState::Zombie
+    } else if process.threads().iter().all(|task| task.is_blocked()) {
+        ProcessState::Sleeping
+    } else {
+        ProcessState::Running
+    }
+}
+
+/// The mapped areas of `aspace`, named after the heap, the stack and the
+/// vDSO that they belong to.
+fn mem_areas(aspace: &AddrSpace) -> Vec<MemArea> {
+    let heap = aspace.heap_start().as_usize()..aspace.brk().as_usize();
+    let stack = aspace.stack();
+    aspace
+        .areas()
+        .map(|(start, size, flags)| {
+            let name = if heap.contains(&start.as_usize()) {
+                "[heap]"
+            } else if stack.is_some_and(|stack| stack.contains(start)) {
+                "[stack]"
+            } else if start.as_usize() == vdso::VVAR_BASE {
+                "[vvar]"
+            } else if start.as_usize() == vdso::VDSO_BASE {
+                "[vdso]"
+            } else {
+                ""
+            };
+            MemArea {
+                start: start.as_usize(),
+                end: start.as_usize() + size,
+                readable: flags.contains(MappingFlags::READ),
+                writable: flags.contains(MappingFlags::WRITE),
+                executable: flags.contains(MappingFlags::EXECUTE),
+                shared: aspace.shared_pages_at(start).is_some(),
+                name: String::from(name),
+            }
+        })
+        .collect()
+}
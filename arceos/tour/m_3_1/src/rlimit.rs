@@ -0,0 +1,116 @@
+//! Resource limits (`getrlimit`, `setrlimit` and `prlimit64`).
+//!
+//! Every process has its own limits, which a child inherits from its parent.
+//! Only `RLIMIT_STACK` is enforced: it bounds the growth of the user stack,
+//! and the reservation below it that `mmap` keeps away from. The other limits
+//! are just recorded.
+
+use arceos_posix_api::ctypes::{self, rlimit};
+use axerrno::{LinuxError, LinuxResult};
+use axsyscall::{syscall_body, UserPtr};
+use axtask::{current, TaskExtRef};
+
+use crate::process::{find_process, Process};
+
+/// The number of resources.
+pub const RLIM_NLIMITS: usize = ctypes::RLIMIT_NLIMITS as usize;
+/// No limit.
+const RLIM_INFINITY: u64 = u64::MAX;
+
+/// The default soft limit of the stack size, 8 MiB as on Linux.
+const DEFAULT_STACK_LIMIT: u64 = 0x80_0000;
+/// The stack never grows beyond it, whatever `RLIMIT_STACK` is, so that the
+/// reservation does not take up the address space.
+const MAX_STACK_SIZE: usize = 0x4000_0000; // 1 GiB
+
+/// The limits of the init process.
+pub fn default_rlimits() -> [rlimit; RLIM_NLIMITS] {
+    let mut rlimits = [rlimit {
+        rlim_cur: RLIM_INFINITY,
+        rlim_max: RLIM_INFINITY,
+    }; RLIM_NLIMITS];
+    rlimits[ctypes::RLIMIT_STACK as usize].rlim_cur = DEFAULT_STACK_LIMIT;
+    rlimits
+}
+
+/// The maximum size of the user stack of `process`.
+pub fn stack_limit(process: &Process) -> usize {
+    clamp_stack_limit(process.rlimit(ctypes::RLIMIT_STACK))
+}
+
+/// The maximum size of the user stack of the init process.
+pub fn default_stack_limit() -> usize {
+    clamp_stack_limit(default_rlimits()[ctypes::RLIMIT_STACK as usize])
+}
+
+fn clamp_stack_limit(rlim: rlimit) -> usize {
+    rlim.rlim_cur.min(MAX_STACK_SIZE as u64) as usize
+}
+
+/// Stores the limits of `resource` of `process` at `old`, and replaces them
+/// with the ones at `new`. Either may be null.
+fn do_prlimit(
+    process: &Process,
+    resource: u32,
+    new: UserPtr<rlimit>,
+    old: UserPtr<rlimit>,
+) -> LinuxResult<isize> {
+    if resource as usize >= RLIM_NLIMITS {
+        return Err(LinuxError::EINVAL);
+    }
+    let new = if new.is_null() {
+        None
+    } else {
+        let new = new.read()?;
+        if new.rlim_cur > new.rlim_max {
+            return Err(LinuxError::EINVAL);
+        }
+        Some(new)
+    };
+    if !old.is_null() {
+        old.write(process.rlimit(resource))?;
+    }
+    if let Some(new) = new {
+        process.set_rlimit(resource, new);
+        if resource == ctypes::RLIMIT_STACK {
+            // Threads share the address space.
+            if let Some(thread) = process.threads().first() {
+                thread.task_ext().aspace.lock().set_stack_limit(stack_limit(process));
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Stores the limits of `resource` of the calling process at `rlim`.
+pub fn sys_getrlimit(resource: u32, rlim: UserPtr<rlimit>) -> isize {
+    syscall_body!(sys_getrlimit, {
+        do_prlimit(&current().task_ext().process, resource, UserPtr::new(0), rlim)
+    })
+}
+
+/// Sets the limits of `resource` of the calling process to the ones at
+/// `rlim`.
+pub fn sys_setrlimit(resource: u32, rlim: UserPtr<rlimit>) -> isize {
+    syscall_body!(sys_setrlimit, {
+        do_prlimit(&current().task_ext().process, resource, rlim, UserPtr::new(0))
+    })
+}
+
+/// Gets and sets the limits of `resource` of the process `pid`, or the
+/// calling process if `pid` is 0.
+pub fn sys_prlimit64(
+    pid: i32,
+    resource: u32,
+    new: UserPtr<rlimit>,
+    old: UserPtr<rlimit>,
+) -> isize {
+    syscall_body!(sys_prlimit64, {
+        let process = match pid {
+            0 => current().task_ext().process.clone(),
+            pid if pid > 0 => find_process(pid as usize).ok_or(LinuxError::ESRCH)?,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        do_prlimit(&process, resource, new, old)
+    })
+}
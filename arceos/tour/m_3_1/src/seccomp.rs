@@ -0,0 +1,393 @@
+//! Syscall filtering (`seccomp`).
+//!
+//! A thread can restrict the syscalls it may make, either to `read`, `write`,
+//! `exit` and `rt_sigreturn` (strict mode), or with classic BPF filters that
+//! decide an action for each syscall from its number and arguments, as on
+//! Linux. Filters are stacked: every installed filter runs, and the action
+//! with the highest precedence wins. The policy is checked before a syscall
+//! is dispatched, and children created by `clone` inherit it.
+//!
+//! Installing a filter requires `no_new_privs` or root, as on Linux.
+//! `SECCOMP_RET_TRACE` and `SECCOMP_RET_USER_NOTIF` have no tracer or
+//! listener to defer to, so the syscall fails with `ENOSYS`.
+//!
+//! The filters are checked and run by [`axsyscall::bpf`].
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::arch::{TrapFrame, UspaceContext};
+use axsyscall::bpf::{check_filter, run_filter, SeccompData, SockFilter, BPF_MAXINSNS};
+use axsyscall::sysno::*;
+use axsyscall::{syscall_body, UserPtr};
+use axtask::{current, TaskExtRef};
+
+use crate::signal::{self, SigInfo};
+
+/// `op` of `seccomp`: allow only `read`, `write`, `exit` and `rt_sigreturn`.
+const SECCOMP_SET_MODE_STRICT: u32 = 0;
+/// `op` of `seccomp`: install a BPF filter.
+const SECCOMP_SET_MODE_FILTER: u32 = 1;
+/// `op` of `seccomp`: check whether an action is supported.
+const SECCOMP_GET_ACTION_AVAIL: u32 = 2;
+
+/// `flags` of `SECCOMP_SET_MODE_FILTER`: install the filter on all threads of
+/// the process.
+const SECCOMP_FILTER_FLAG_TSYNC: u32 = 1 << 0;
+/// `flags` of `SECCOMP_SET_MODE_FILTER`: log the actions taken, except
+/// `SECCOMP_RET_ALLOW`.
+const SECCOMP_FILTER_FLAG_LOG: u32 = 1 << 1;
+/// `flags` of `SECCOMP_SET_MODE_FILTER`: keep speculative store bypass
+/// mitigation off. Accepted and ignored.
+const SECCOMP_FILTER_FLAG_SPEC_ALLOW: u32 = 1 << 2;
+/// `flags` of `SECCOMP_SET_MODE_FILTER`: with `TSYNC`, fail with `ESRCH`
+/// instead of the ID of the thread that cannot be synchronized.
+const SECCOMP_FILTER_FLAG_TSYNC_ESRCH: u32 = 1 << 4;
+
+/// Actions returned by filters, in the high 16 bits. The lower ones take
+/// precedence (as signed integers).
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_KILL_THREAD: u32 = 0x0000_0000;
+const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+const SECCOMP_RET_TRACE: u32 = 0x7ff0_0000;
+const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_RET_ACTION_FULL: u32 = 0xffff_0000;
+const SECCOMP_RET_DATA: u32 = 0x0000_ffff;
+
+/// The largest errno that `SECCOMP_RET_ERRNO` can return.
+const MAX_ERRNO: u32 = 4095;
+
+/// `si_code` of `SIGSYS`: sent by `SECCOMP_RET_TRAP`.
+const SYS_SECCOMP: i32 = 1;
+
+/// `option` of `prctl`: get the seccomp mode.
+const PR_GET_SECCOMP: u32 = 21;
+/// `option` of `prctl`: set the seccomp mode.
+const PR_SET_SECCOMP: u32 = 22;
+/// `option` of `prctl`: set the `no_new_privs` bit.
+const PR_SET_NO_NEW_PRIVS: u32 = 38;
+/// `option` of `prctl`: get the `no_new_privs` bit.
+const PR_GET_NO_NEW_PRIVS: u32 = 39;
+
+/// The `AUDIT_ARCH_*` value of the architecture, in `seccomp_data`.
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+#[cfg(target_arch = "riscv64")]
+const AUDIT_ARCH: u32 = 0xc000_00f3;
+
+/// The maximum number of instructions of all filters of a thread, counting 4
+/// more for each filter.
+const MAX_INSNS_PER_PATH: usize = 32768;
+
+/// `struct sock_fprog`, a classic BPF program.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SockFprog {
+    len: u16,
+    filter: usize,
+}
+
+/// An installed filter, linked to the ones installed before it.
+struct Filter {
+    prog: Vec<SockFilter>,
+    /// Whether the actions taken are logged (`SECCOMP_FILTER_FLAG_LOG`).
+    log: bool,
+    prev: Option<Arc<Filter>>,
+}
+
+impl Filter {
+    /// The filters from this one back to the first one.
+    fn chain(self: &Arc<Self>) -> impl Iterator<Item = &Arc<Filter>> {
+        core::iter::successors(Some(self), |&filter| filter.prev.as_ref())
+    }
+}
+
+/// The seccomp mode of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Mode {
+    #[default]
+    Disabled = 0,
+    Strict = 1,
+    Filter = 2,
+}
+
+/// The syscall filtering state of a thread, which a child inherits.
+#[derive(Clone, Default)]
+pub struct Seccomp {
+    mode: Mode,
+    /// The last installed filter.
+    filter: Option<Arc<Filter>>,
+    /// Set by `PR_SET_NO_NEW_PRIVS`, and never cleared.
+    no_new_privs: bool,
+}
+
+/// What to do with a syscall.
+enum Verdict {
+    Allow,
+    /// Fail with the errno.
+    Errno(u32),
+    /// Fail with `ENOSYS` and send `SIGSYS`, with the data of the action.
+    Trap(u32),
+    KillThread,
+    KillProcess,
+}
+
+impl Seccomp {
+    /// Decides what to do with the syscall described by `data`.
+    fn check(&self, data: &SeccompData) -> Verdict {
+        match self.mode {
+            Mode::Disabled => Verdict::Allow,
+            Mode::Strict => {
+                let allowed = [SYS_READ, SYS_WRITE, SYS_EXIT, SYS_RT_SIGRETURN];
+                if allowed.contains(&(data.nr as usize)) {
+                    Verdict::Allow
+                } else {
+                    Verdict::KillProcess
+                }
+            }
+            Mode::Filter => {
+                let mut ret = SECCOMP_RET_ALLOW;
+                let mut log = false;
+                for filter in self.filter.iter().flat_map(|f| f.chain()) {
+                    let cur = run_filter(&filter.prog, data);
+                    if ((cur & SECCOMP_RET_ACTION_FULL) as i32)
+                        < ((ret & SECCOMP_RET_ACTION_FULL) as i32)
+                    {
+                        ret = cur;
+                        log = filter.log;
+                    }
+                }
+                let action = ret & SECCOMP_RET_ACTION_FULL;
+                if action == SECCOMP_RET_LOG || (log && action != SECCOMP_RET_ALLOW) {
+                    info!("seccomp: syscall {} => action {:#x}", data.nr, ret);
+                }
+                match action {
+                    SECCOMP_RET_ALLOW | SECCOMP_RET_LOG => Verdict::Allow,
+                    SECCOMP_RET_ERRNO => Verdict::Errno((ret & SECCOMP_RET_DATA).min(MAX_ERRNO)),
+                    SECCOMP_RET_TRACE | SECCOMP_RET_USER_NOTIF => {
+                        Verdict::Errno(LinuxError::ENOSYS.code() as u32)
+                    }
+                    SECCOMP_RET_TRAP => Verdict::Trap(ret & SECCOMP_RET_DATA),
+                    SECCOMP_RET_KILL_THREAD => Verdict::KillThread,
+                    _ => Verdict::KillProcess,
+                }
+            }
+        }
+    }
+
+    /// The number of instructions of all filters, as counted against
+    /// `MAX_INSNS_PER_PATH`.
+    fn path_len(&self) -> usize {
+        self.filter
+            .iter()
+            .flat_map(|f| f.chain())
+            .map(|f| f.prog.len() + 4)
+            .sum()
+    }
+
+    /// Whether the filters of `self` are the same as, or a prefix of, the
+    /// filters of `other`, so that `self` can be replaced by `other`
+    /// without losing any restriction.
+    fn is_ancestor_of(&self, other: &Seccomp) -> bool {
+        match (self.mode, &self.filter) {
+            (Mode::Disabled, _) => true,
+            (Mode::Filter, Some(mine)) => other
+                .filter
+                .iter()
+                .flat_map(|f| f.chain())
+                .any(|f| Arc::ptr_eq(f, mine)),
+            _ => false,
+        }
+    }
+}
+
+/// Applies the seccomp policy of the current thread to the syscall `sysno`
+/// trapped with `tf`, before it is dispatched.
+///
+/// Returns `None` if the syscall is allowed, or the value to return instead
+/// of running it. A kill action does not return.
+pub fn check_syscall(tf: &TrapFrame, sysno: usize) -> Option<isize> {
+    let curr = current();
+    let seccomp = curr.task_ext().seccomp.lock().clone();
+    if seccomp.mode == Mode::Disabled {
+        return None;
+    }
+    let ip = UspaceContext::from(tf).get_ip();
+    let data = SeccompData {
+        nr: sysno as i32,
+        arch: AUDIT_ARCH,
+        instruction_pointer: ip as u64,
+        args: [
+            tf.arg0() as u64,
+            tf.arg1() as u64,
+            tf.arg2() as u64,
+            tf.arg3() as u64,
+            tf.arg4() as u64,
+            tf.arg5() as u64,
+        ],
+    };
+    let sigsys = |errno| {
+        let mut info = SigInfo::from_syscall(signal::SIGSYS, SYS_SECCOMP, ip, sysno, AUDIT_ARCH);
+        info.errno = errno as i32;
+        info
+    };
+    match seccomp.check(&data) {
+        Verdict::Allow => None,
+        Verdict::Errno(errno) => Some(-(errno as isize)),
+        Verdict::Trap(errno) => {
+            signal::force_signal(sigsys(errno));
+            Some(-LinuxError::ENOSYS.code() as isize)
+        }
+        Verdict::KillThread if curr.task_ext().process.threads().len() > 1 => {
+            warn!("{}: killed by seccomp at syscall {}", curr.id_name(), sysno);
+            crate::process::exit_thread_by_signal(signal::SIGSYS)
+        }
+        Verdict::KillThread => signal::kill_current(tf, &sigsys(0)),
+        Verdict::KillProcess if seccomp.mode == Mode::Strict => signal::kill_current(
+            tf,
+            &SigInfo::from_process(signal::SIGKILL, signal::SI_KERNEL, 0),
+        ),
+        Verdict::KillProcess => signal::kill_current(tf, &sigsys(0)),
+    }
+}
+
+/// Copies the filter program described at `fprog` from user space and
+/// checks it.
+fn read_filter(fprog: UserPtr<SockFprog>) -> LinuxResult<Vec<SockFilter>> {
+    let fprog = fprog.read()?;
+    let len = fprog.len as usize;
+    if len == 0 || len > BPF_MAXINSNS {
+        return Err(LinuxError::EINVAL);
+    }
+    let prog = UserPtr::<SockFilter>::new(fprog.filter).read_array(len)?;
+    check_filter(&prog)?;
+    Ok(prog)
+}
+
+/// Sets the seccomp mode of the calling thread (and of the other threads of
+/// its process with `SECCOMP_FILTER_FLAG_TSYNC`).
+fn do_seccomp(op: u32, flags: u32, args: usize) -> LinuxResult<isize> {
+    let curr = current();
+    match op {
+        SECCOMP_SET_MODE_STRICT => {
+            if flags != 0 || args != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            let mut seccomp = curr.task_ext().seccomp.lock();
+            if seccomp.mode == Mode::Filter {
+                return Err(LinuxError::EINVAL);
+            }
+            seccomp.mode = Mode::Strict;
+            Ok(0)
+        }
+        SECCOMP_SET_MODE_FILTER => {
+            let known = SECCOMP_FILTER_FLAG_TSYNC
+                | SECCOMP_FILTER_FLAG_LOG
+                | SECCOMP_FILTER_FLAG_SPEC_ALLOW
+                | SECCOMP_FILTER_FLAG_TSYNC_ESRCH;
+            if flags & !known != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            let prog = read_filter(UserPtr::new(args))?;
+
+            let mut new = curr.task_ext().seccomp.lock().clone();
+            if new.mode == Mode::Strict {
+                return Err(LinuxError::EINVAL);
+            }
+            if !new.no_new_privs && !curr.task_ext().process.cred().is_privileged() {
+                return Err(LinuxError::EACCES);
+            }
+            if new.path_len() + prog.len() + 4 > MAX_INSNS_PER_PATH {
+                return Err(LinuxError::ENOMEM);
+            }
+            new.mode = Mode::Filter;
+            new.filter = Some(Arc::new(Filter {
+                prog,
+                log: flags & SECCOMP_FILTER_FLAG_LOG != 0,
+                prev: new.filter.take(),
+            }));
+
+            if flags & SECCOMP_FILTER_FLAG_TSYNC == 0 {
+                *curr.task_ext().seccomp.lock() = new;
+                return Ok(0);
+            }
+            // Every thread must be on the same filters as the caller, or on an
+            // earlier part of them.
+            let threads = curr.task_ext().process.threads();
+            for thread in &threads {
+                if !thread.task_ext().seccomp.lock().is_ancestor_of(&new) {
+                    if flags & SECCOMP_FILTER_FLAG_TSYNC_ESRCH != 0 {
+                        return Err(LinuxError::ESRCH);
+                    }
+                    return Ok(thread.id().as_u64() as isize);
+                }
+            }
+            for thread in &threads {
+                let mut seccomp = thread.task_ext().seccomp.lock();
+                let no_new_privs = seccomp.no_new_privs;
+                *seccomp = Seccomp {
+                    no_new_privs,
+                    ..new.clone()
+                };
+            }
+            Ok(0)
+        }
+        SECCOMP_GET_ACTION_AVAIL => {
+            if flags != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            match UserPtr::<u32>::new(args).read()? {
+                SECCOMP_RET_KILL_PROCESS
+                | SECCOMP_RET_KILL_THREAD
+                | SECCOMP_RET_TRAP
+                | SECCOMP_RET_ERRNO
+                | SECCOMP_RET_LOG
+                | SECCOMP_RET_ALLOW => Ok(0),
+                _ => Err(LinuxError::EOPNOTSUPP),
+            }
+        }
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+/// Sets the seccomp mode, installs a filter, or checks whether a filter
+/// action is supported, according to `op`.
+pub fn sys_seccomp(op: u32, flags: u32, args: usize) -> isize {
+    syscall_body!(sys_seccomp, do_seccomp(op, flags, args))
+}
+
+/// Performs the process operation `option`. Only the ones about seccomp and
+/// `no_new_privs` are supported.
+pub fn sys_prctl(option: u32, arg2: usize, arg3: usize, arg4: usize, arg5: usize) -> isize {
+    syscall_body!(sys_prctl, {
+        let curr = current();
+        match option {
+            PR_GET_SECCOMP => Ok(curr.task_ext().seccomp.lock().mode as isize),
+            PR_SET_SECCOMP => match arg2 {
+                1 => do_seccomp(SECCOMP_SET_MODE_STRICT, 0, 0),
+                2 => do_seccomp(SECCOMP_SET_MODE_FILTER, 0, arg3),
+                _ => Err(LinuxError::EINVAL),
+            },
+            PR_SET_NO_NEW_PRIVS => {
+                if arg2 != 1 || arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                curr.task_ext().seccomp.lock().no_new_privs = true;
+                Ok(0)
+            }
+            PR_GET_NO_NEW_PRIVS => {
+                if arg2 != 0 || arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                Ok(curr.task_ext().seccomp.lock().no_new_privs as isize)
+            }
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
+}
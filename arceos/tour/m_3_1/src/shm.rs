@@ -0,0 +1,475 @@
+//! Shared memory between processes: memfds (`memfd_create`) and System V
+//! shared memory segments (`shmget`, `shmat`, `shmdt` and `shmctl`).
+//!
+//! Both are backed by [`SharedPages`], which every mapping of them refers
+//! to, so the memory lives until the last mapping and the last handle (the
+//! file descriptor or the segment ID) are gone.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::ffi::c_char;
+
+use arceos_posix_api::{self as api, ctypes, FileLike};
+use axerrno::{LinuxError, LinuxResult};
+use axfs::cred::Cred;
+use axhal::mem::{VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::MappingFlags;
+use axhal::time::NANOS_PER_SEC;
+use axio::PollState;
+use axmm::SharedPages;
+use axsync::Mutex;
+use axsyscall::{syscall_body, UserPtr};
+use axtask::{current, TaskExtRef};
+use memory_addr::VirtAddrRange;
+
+use crate::mm::find_mmap_area;
+
+/// `flags` of `memfd_create`: set `FD_CLOEXEC` on the new file descriptor.
+const MFD_CLOEXEC: u32 = 0x1;
+/// `flags` of `memfd_create`: allow sealing. Seals are not supported, but
+/// the flag is accepted.
+const MFD_ALLOW_SEALING: u32 = 0x2;
+/// The maximum length of the name of a memfd, excluding the terminating
+/// null byte.
+const MFD_NAME_MAX: usize = 249;
+
+/// The key of `shmget` that always creates a new segment.
+const IPC_PRIVATE: i32 = 0;
+/// `shmflg` of `shmget`: create the segment if it does not exist.
+const IPC_CREAT: u32 = 0o1000;
+/// `shmflg` of `shmget`: fail if the segment exists.
+const IPC_EXCL: u32 = 0o2000;
+/// `cmd` of `shmctl`: mark the segment to be destroyed.
+const IPC_RMID: u32 = 0;
+/// `cmd` of `shmctl`: set the permissions of the segment.
+const IPC_SET: u32 = 1;
+/// `cmd` of `shmctl`: get the state of the segment.
+const IPC_STAT: u32 = 2;
+/// Set in `cmd` of `shmctl` by the C library for the 64-bit `shmid_ds`,
+/// which is the only one there is on 64-bit architectures.
+const IPC_64: u32 = 0x100;
+/// `shmflg` of `shmat`: attach read-only.
+const SHM_RDONLY: u32 = 0o10000;
+/// `shmflg` of `shmat`: round `shmaddr` down to `SHMLBA`.
+const SHM_RND: u32 = 0o20000;
+/// `shmflg` of `shmat`: replace the mappings at `shmaddr`.
+const SHM_REMAP: u32 = 0o40000;
+/// `shmflg` of `shmat`: allow executing the segment.
+const SHM_EXEC: u32 = 0o100000;
+/// Set in the mode of a segment that has been marked to be destroyed.
+const SHM_DEST: u32 = 0o1000;
+/// The alignment of attach addresses.
+const SHMLBA: usize = PAGE_SIZE_4K;
+
+/// The file mode of a memfd: a regular file, readable and writable by all.
+const MEMFD_MODE: u32 = 0o100777;
+
+/// A file that refers to anonymous shared pages, created by
+/// `memfd_create`.
+pub struct MemFd {
+    /// The name given to `memfd_create`, for `/proc/<pid>/fd`.
+    name: String,
+    pages: Arc<SharedPages>,
+    /// The file offset of `read` and `write`.
+    offset: Mutex<usize>,
+    /// The credentials of the creator, whose effective IDs own the file.
+    cred: Arc<Cred>,
+}
+
+impl MemFd {
+    /// Returns the pages that the file refers to, which `mmap` maps.
+    pub fn pages(&self) -> &Arc<SharedPages> {
+        &self.pages
+    }
+}
+
+impl FileLike for MemFd {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        let mut offset = self.offset.lock();
+        let n = self.pages.read_at(*offset, buf);
+        *offset += n;
+        Ok(n)
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        let mut offset = self.offset.lock();
+        let n = self.pages.write_at(*offset, buf);
+        if n == 0 && !buf.is_empty() {
+            return Err(LinuxError::ENOSPC);
+        }
+        *offset += n;
+        Ok(n)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let size = self.pages.size();
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode: MEMFD_MODE,
+            st_uid: self.cred.euid,
+            st_gid: self.cred.egid,
+            st_size: size as _,
+            st_blocks: size.div_ceil(512) as _,
+            st_blksize: PAGE_SIZE_4K as _,
+            ..Default::default()
+        })
+    }
+
+    fn path(&self) -> String {
+        format!("/memfd:{} (deleted)", self.name)
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: true,
+        })
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+        Ok(())
+    }
+}
+
+/// Creates an empty memfd, and returns its file descriptor. The name is not
+/// visible in the file system, only in `/proc/<pid>/fd`.
+pub fn sys_memfd_create(name: UserPtr<c_char>, flags: u32) -> isize {
+    syscall_body!(sys_memfd_create, {
+        if flags & !(MFD_CLOEXEC | MFD_ALLOW_SEALING) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let name = name.read_str(MFD_NAME_MAX + 1).map_err(|e| match e {
+            LinuxError::ENAMETOOLONG => LinuxError::EINVAL,
+            e => e,
+        })?;
+        let memfd = MemFd {
+            name,
+            pages: SharedPages::new(0),
+            offset: Mutex::new(0),
+            cred: current_cred(),
+        };
+        let fd = current()
+            .task_ext()
+            .fd_table
+            .add(Arc::new(memfd), flags & MFD_CLOEXEC != 0)?;
+        Ok(fd as isize)
+    })
+}
+
+/// Truncates or extends the file `fd` to `length` bytes.
+pub fn sys_ftruncate(fd: i32, length: i64) -> isize {
+    syscall_body!(sys_ftruncate, {
+        match api::get_file_like(fd)?.into_any().downcast::<MemFd>() {
+            Ok(memfd) => {
+                if length < 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                memfd.pages.set_size(length as usize);
+                Ok(0)
+            }
+            Err(_) => Ok(api::sys_ftruncate(fd, length as _) as isize),
+        }
+    })
+}
+
+/// `struct ipc64_perm` of Linux.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct IpcPerm {
+    key: i32,
+    uid: u32,
+    gid: u32,
+    cuid: u32,
+    cgid: u32,
+    mode: u32,
+    seq: u16,
+    _pad: [u16; 3],
+    _unused: [u64; 2],
+}
+
+/// `struct shmid64_ds` of Linux.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ShmidDs {
+    perm: IpcPerm,
+    segsz: usize,
+    atime: i64,
+    dtime: i64,
+    ctime: i64,
+    cpid: i32,
+    lpid: i32,
+    nattch: u64,
+    _unused: [u64; 2],
+}
+
+/// A System V shared memory segment.
+struct ShmSegment {
+    /// The key, which is `IPC_PRIVATE` once the segment is marked to be
+    /// destroyed.
+    key: i32,
+    /// The size requested by `shmget`.
+    size: usize,
+    /// The permission bits, and `SHM_DEST`.
+    mode: u32,
+    /// The pages, held until the segment is marked to be destroyed. From
+    /// then on, only the attachments hold them.
+    owner: Option<Arc<SharedPages>>,
+    pages: Weak<SharedPages>,
+    /// The process that created the segment, and its credentials, whose
+    /// effective IDs own the segment.
+    cpid: i32,
+    cred: Arc<Cred>,
+    /// The process that attached or detached it last.
+    lpid: i32,
+    /// The times of the last attach, detach and change, in seconds.
+    atime: i64,
+    dtime: i64,
+    ctime: i64,
+}
+
+impl ShmSegment {
+    /// The number of attachments, i.e., the mappings of the segment in all
+    /// address spaces. As on Linux, a mapping split in two counts twice.
+    fn nattch(&self) -> usize {
+        self.pages.strong_count() - self.owner.is_some() as usize
+    }
+
+    fn pages(&self) -> Option<Arc<SharedPages>> {
+        self.pages.upgrade()
+    }
+
+    fn stat(&self) -> ShmidDs {
+        ShmidDs {
+            perm: IpcPerm {
+                key: self.key,
+                uid: self.cred.euid,
+                gid: self.cred.egid,
+                cuid: self.cred.euid,
+                cgid: self.cred.egid,
+                mode: self.mode,
+                ..Default::default()
+            },
+            segsz: self.size,
+            atime: self.atime,
+            dtime: self.dtime,
+            ctime: self.ctime,
+            cpid: self.cpid,
+            lpid: self.lpid,
+            nattch: self.nattch() as u64,
+            ..Default::default()
+        }
+    }
+}
+
+/// All shared memory segments, keyed by their IDs.
+struct ShmTable {
+    segments: BTreeMap<i32, ShmSegment>,
+    next_id: i32,
+}
+
+impl ShmTable {
+    /// Removes the segments that are marked to be destroyed and no longer
+    /// attached.
+    fn prune(&mut self) {
+        self.segments
+            .retain(|_, seg| seg.owner.is_some() || seg.pages.strong_count() > 0);
+    }
+
+    fn get(&mut self, shmid: i32) -> LinuxResult<&mut ShmSegment> {
+        self.prune();
+        self.segments.get_mut(&shmid).ok_or(LinuxError::EINVAL)
+    }
+}
+
+static SHM_TABLE: Mutex<ShmTable> = Mutex::new(ShmTable {
+    segments: BTreeMap::new(),
+    next_id: 0,
+});
+
+/// The current wall time in seconds.
+fn now() -> i64 {
+    (crate::vdso::clock_nanos(true) / NANOS_PER_SEC) as i64
+}
+
+fn current_pid() -> i32 {
+    current().task_ext().proc_id as i32
+}
+
+fn current_cred() -> Arc<Cred> {
+    current().task_ext().process.cred()
+}
+
+/// Returns the ID of the segment of `key`, creating it with `size` bytes if
+/// `key` is `IPC_PRIVATE`, or if it does not exist and `IPC_CREAT` is given.
+pub fn sys_shmget(key: i32, size: usize, shmflg: u32) -> isize {
+    syscall_body!(sys_shmget, {
+        let mut table = SHM_TABLE.lock();
+        table.prune();
+        if key != IPC_PRIVATE {
+            let existing = table.segments.iter().find(|(_, seg)| seg.key == key);
+            match existing {
+                Some(_) if shmflg & IPC_CREAT != 0 && shmflg & IPC_EXCL != 0 => {
+                    return Err(LinuxError::EEXIST)
+                }
+                Some((_, seg)) if size > seg.size => return Err(LinuxError::EINVAL),
+                Some((&shmid, _)) => return Ok(shmid as isize),
+                None if shmflg & IPC_CREAT == 0 => return Err(LinuxError::ENOENT),
+                None => {}
+            }
+        }
+        if size == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+
+        let pages = SharedPages::new(size.div_ceil(PAGE_SIZE_4K) * PAGE_SIZE_4K);
+        let shmid = table.next_id;
+        table.next_id = table.next_id.wrapping_add(1) & i32::MAX;
+        table.segments.insert(
+            shmid,
+            ShmSegment {
+                key,
+                size,
+                mode: shmflg & 0o777,
+                pages: Arc::downgrade(&pages),
+                owner: Some(pages),
+                cpid: current_pid(),
+                cred: current_cred(),
+                lpid: 0,
+                atime: 0,
+                dtime: 0,
+                ctime: now(),
+            },
+        );
+        Ok(shmid as isize)
+    })
+}
+
+/// Attaches the segment `shmid` at `shmaddr`, or at an address chosen by
+/// the kernel if it is 0, and returns the address.
+pub fn sys_shmat(shmid: i32, shmaddr: usize, shmflg: u32) -> isize {
+    syscall_body!(sys_shmat, {
+        let mut table = SHM_TABLE.lock();
+        let seg = table.get(shmid)?;
+        let pages = seg.pages().ok_or(LinuxError::EIDRM)?;
+        let size = pages.size();
+
+        let mut flags = MappingFlags::READ | MappingFlags::USER;
+        if shmflg & SHM_RDONLY == 0 {
+            flags |= MappingFlags::WRITE;
+        }
+        if shmflg & SHM_EXEC != 0 {
+            flags |= MappingFlags::EXECUTE;
+        }
+
+        let curr = current();
+        let mut aspace = curr.task_ext().aspace.lock();
+        let start = if shmaddr == 0 {
+            find_mmap_area(&aspace, 0, size)?
+        } else {
+            let addr = if shmflg & SHM_RND != 0 {
+                shmaddr / SHMLBA * SHMLBA
+            } else if shmaddr % SHMLBA == 0 {
+                shmaddr
+            } else {
+                return Err(LinuxError::EINVAL);
+            };
+            let start = VirtAddr::from(addr);
+            if !aspace.contains_range(start, size) {
+                return Err(LinuxError::EINVAL);
+            }
+            if shmflg & SHM_REMAP != 0 {
+                aspace.unmap(start, size).map_err(|_| LinuxError::EINVAL)?;
+            } else {
+                let range = VirtAddrRange::from_start_size(start, size);
+                if aspace.find_free_area(start, size, range) != Some(start) {
+                    return Err(LinuxError::EINVAL);
+                }
+            }
+            start
+        };
+        aspace
+            .map_shared(start, size, flags, pages, 0)
+            .map_err(|_| LinuxError::ENOMEM)?;
+        seg.lpid = current_pid();
+        seg.atime = now();
+        Ok(start.as_usize() as isize)
+    })
+}
+
+/// Detaches the segment attached at `shmaddr`.
+pub fn sys_shmdt(shmaddr: usize) -> isize {
+    syscall_body!(sys_shmdt, {
+        // The table is always locked before the address space.
+        let mut table = SHM_TABLE.lock();
+        let curr = current();
+        let mut aspace = curr.task_ext().aspace.lock();
+        let start = VirtAddr::from(shmaddr);
+        let (pages, base) = aspace.shared_pages_at(start).ok_or(LinuxError::EINVAL)?;
+        if base != start {
+            return Err(LinuxError::EINVAL);
+        }
+        let seg = table
+            .segments
+            .values_mut()
+            .find(|seg| Weak::as_ptr(&seg.pages) == Arc::as_ptr(&pages))
+            .ok_or(LinuxError::EINVAL)?;
+        seg.lpid = current_pid();
+        seg.dtime = now();
+        // Only the parts still mapped to the segment are unmapped, as other
+        // mappings may have taken the place of the parts unmapped by the user.
+        let end = start + pages.size();
+        let attached: Vec<(VirtAddr, VirtAddr)> = aspace
+            .areas()
+            .filter(|&(area, size, _)| area < end && start < area + size)
+            .filter(|&(area, ..)| {
+                aspace
+                    .shared_pages_at(area)
+                    .is_some_and(|(p, base)| Arc::ptr_eq(&p, &pages) && base == start)
+            })
+            .map(|(area, size, _)| (area.max(start), (area + size).min(end)))
+            .collect();
+        drop(pages);
+        for (area_start, area_end) in attached {
+            aspace
+                .unmap(area_start, area_end - area_start)
+                .map_err(|_| LinuxError::EINVAL)?;
+        }
+        table.prune();
+        Ok(0)
+    })
+}
+
+/// Gets or sets the state of the segment `shmid` (`IPC_STAT` and
+/// `IPC_SET`), or marks it to be destroyed once it is no longer attached
+/// (`IPC_RMID`).
+pub fn sys_shmctl(shmid: i32, cmd: u32, buf: UserPtr<ShmidDs>) -> isize {
+    syscall_body!(sys_shmctl, {
+        let mut table = SHM_TABLE.lock();
+        let seg = table.get(shmid)?;
+        match cmd & !IPC_64 {
+            IPC_STAT => buf.write(seg.stat())?,
+            IPC_SET => {
+                let ds = buf.read()?;
+                seg.mode = (seg.mode & !0o777) | (ds.perm.mode & 0o777);
+                seg.ctime = now();
+            }
+            IPC_RMID => {
+                seg.key = IPC_PRIVATE;
+                seg.mode |= SHM_DEST;
+                seg.owner = None;
+                seg.ctime = now();
+                table.prune();
+            }
+            _ => return Err(LinuxError::EINVAL),
+        }
+        Ok(0)
+    })
+}
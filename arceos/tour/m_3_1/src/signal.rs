@@ -0,0 +1,650 @@
+//! POSIX signals of user processes.
+//!
+//! Each process has its signal dispositions ([`SignalActions`]), and each
+//! thread has its pending and blocked signals ([`ThreadSignals`]). Pending
+//! signals are delivered right before the thread returns to user space: a
+//! signal frame that saves the interrupted context is pushed onto the user
+//! stack, and the handler returns to a trampoline that calls `rt_sigreturn`.
+//! The layout of the frame is architecture-specific, see the submodules.
+
+use core::mem::size_of;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use axerrno::{AxResult, LinuxError, LinuxResult};
+use axhal::arch::{TrapFrame, UspaceContext};
+use axhal::mem::{VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::MappingFlags;
+use axhal::trap::{register_trap_handler, RETURN_TO_USER};
+use axmm::AddrSpace;
+use axsync::Mutex;
+use axsyscall::{syscall_body, UserPtr};
+use axtask::{current, AxTaskRef, TaskExtRef};
+
+use crate::process::{find_process, init_pid, processes, Process};
+
+#[cfg(target_arch = "aarch64")]
+#[path = "signal/aarch64.rs"]
+mod arch;
+#[cfg(target_arch = "riscv64")]
+#[path = "signal/riscv64.rs"]
+mod arch;
+#[cfg(target_arch = "x86_64")]
+#[path = "signal/x86_64.rs"]
+mod arch;
+
+pub const SIGHUP: usize = 1;
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGABRT: usize = 6;
+pub const SIGBUS: usize = 7;
+pub const SIGFPE: usize = 8;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGSEGV: usize = 11;
+pub const SIGUSR2: usize = 12;
+pub const SIGPIPE: usize = 13;
+pub const SIGALRM: usize = 14;
+pub const SIGTERM: usize = 15;
+pub const SIGSTKFLT: usize = 16;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+pub const SIGURG: usize = 23;
+pub const SIGXCPU: usize = 24;
+pub const SIGXFSZ: usize = 25;
+pub const SIGVTALRM: usize = 26;
+pub const SIGPROF: usize = 27;
+pub const SIGWINCH: usize = 28;
+pub const SIGIO: usize = 29;
+pub const SIGPWR: usize = 30;
+pub const SIGSYS: usize = 31;
+/// The number of signals, including real-time signals.
+pub const NSIG: usize = 64;
+
+/// The default action.
+const SIG_DFL: usize = 0;
+/// Ignore the signal.
+const SIG_IGN: usize = 1;
+
+/// Do not receive `SIGCHLD` when children stop.
+pub const SA_NOCLDSTOP: usize = 0x1;
+/// Do not turn children into zombies when they exit.
+pub const SA_NOCLDWAIT: usize = 0x2;
+/// The handler takes three arguments (`int`, `siginfo_t *`, `void *`).
+pub const SA_SIGINFO: usize = 0x4;
+/// Run the handler on the alternate signal stack.
+pub const SA_ONSTACK: usize = 0x0800_0000;
+/// Restart interrupted syscalls.
+pub const SA_RESTART: usize = 0x1000_0000;
+/// Do not block the signal while its handler runs.
+pub const SA_NODEFER: usize = 0x4000_0000;
+/// Reset the handler to the default action when the signal is delivered.
+pub const SA_RESETHAND: usize = 0x8000_0000;
+/// The handler returns to `sa_restorer` instead of the kernel trampoline.
+#[cfg(not(target_arch = "riscv64"))]
+pub const SA_RESTORER: usize = 0x0400_0000;
+
+/// `how` of `rt_sigprocmask`: add signals to the mask.
+const SIG_BLOCK: usize = 0;
+/// `how` of `rt_sigprocmask`: remove signals from the mask.
+const SIG_UNBLOCK: usize = 1;
+/// `how` of `rt_sigprocmask`: replace the mask.
+const SIG_SETMASK: usize = 2;
+
+/// `si_code`: sent by `kill`.
+pub const SI_USER: i32 = 0;
+/// `si_code`: sent by `tkill` or `tgkill`.
+pub const SI_TKILL: i32 = -6;
+/// `si_code`: generated by the kernel, e.g., for a fault.
+pub const SI_KERNEL: i32 = 0x80;
+
+/// The address of the page holding the signal return trampoline, which is
+/// mapped into every user address space.
+pub const SIGRETURN_TRAMPOLINE: usize = 0x3f_0000_0000;
+
+/// A set of signals, in which bit `n - 1` stands for signal `n`.
+#[repr(transparent)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SignalSet(u64);
+
+impl SignalSet {
+    /// Signals that can be neither blocked nor caught.
+    const UNBLOCKABLE: Self = Self((1 << (SIGKILL - 1)) | (1 << (SIGSTOP - 1)));
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    pub const fn contains(self, sig: usize) -> bool {
+        self.0 & (1 << (sig - 1)) != 0
+    }
+
+    pub fn add(&mut self, sig: usize) {
+        self.0 |= 1 << (sig - 1);
+    }
+
+    pub fn remove(&mut self, sig: usize) {
+        self.0 &= !(1 << (sig - 1));
+    }
+}
+
+/// Information about a signal, i.e., the `siginfo_t` of Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SigInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    _pad: i32,
+    /// The union of `siginfo_t` fields. The first word is the fault address
+    /// of `SIGSEGV` and friends, or `si_pid` and `si_uid` of `kill`.
+    fields: [usize; 14],
+}
+
+impl SigInfo {
+    /// Information of a signal sent by the process `pid`.
+    pub const fn from_process(sig: usize, code: i32, pid: usize) -> Self {
+        let mut fields = [0; 14];
+        fields[0] = pid as u32 as usize; // si_uid = 0
+        Self {
+            signo: sig as _,
+            errno: 0,
+            code,
+            _pad: 0,
+            fields,
+        }
+    }
+
+    /// Information of a signal caused by a fault at `addr`.
+    pub const fn from_fault(sig: usize, code: i32, addr: usize) -> Self {
+        let mut fields = [0; 14];
+        fields[0] = addr;
+        Self {
+            signo: sig as _,
+            errno: 0,
+            code,
+            _pad: 0,
+            fields,
+        }
+    }
+
+    /// Information of a signal caused by the syscall `sysno` of the
+    /// architecture `arch` (an `AUDIT_ARCH_*` value), made at `call_addr`.
+    pub const fn from_syscall(
+        sig: usize,
+        code: i32,
+        call_addr: usize,
+        sysno: usize,
+        arch: u32,
+    ) -> Self {
+        let mut fields = [0; 14];
+        fields[0] = call_addr;
+        fields[1] = sysno as u32 as usize | (arch as usize) << 32; // si_syscall, si_arch
+        Self {
+            signo: sig as _,
+            errno: 0,
+            code,
+            _pad: 0,
+            fields,
+        }
+    }
+}
+
+/// The disposition of a signal, i.e., the kernel `struct sigaction` of Linux.
+/// RISC-V has no `sa_restorer`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SigAction {
+    pub handler: usize,
+    pub flags: usize,
+    #[cfg(not(target_arch = "riscv64"))]
+    pub restorer: usize,
+    pub mask: SignalSet,
+}
+
+/// What happens to a process if a signal with the default disposition is
+/// delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DefaultAction {
+    Terminate,
+    CoreDump,
+    Ignore,
+    Stop,
+}
+
+fn default_action(sig: usize) -> DefaultAction {
+    match sig {
+        SIGQUIT | SIGILL | SIGTRAP | SIGABRT | SIGBUS | SIGFPE | SIGSEGV | SIGXCPU | SIGXFSZ
+        | SIGSYS => DefaultAction::CoreDump,
+        SIGCHLD | SIGCONT | SIGURG | SIGWINCH => DefaultAction::Ignore,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+        _ => DefaultAction::Terminate,
+    }
+}
+
+/// The signal dispositions of a process.
+#[derive(Clone)]
+pub struct SignalActions([SigAction; NSIG]);
+
+impl SignalActions {
+    pub const fn new() -> Self {
+        Self(
+            [SigAction {
+                handler: SIG_DFL,
+                flags: 0,
+                #[cfg(not(target_arch = "riscv64"))]
+                restorer: 0,
+                mask: SignalSet::empty(),
+            }; NSIG],
+        )
+    }
+
+    pub fn get(&self, sig: usize) -> SigAction {
+        self.0[sig - 1]
+    }
+
+    pub fn set(&mut self, sig: usize, action: SigAction) {
+        self.0[sig - 1] = action;
+    }
+
+    /// Whether exited children are reaped automatically, i.e., `SIGCHLD` is
+    /// ignored explicitly or has `SA_NOCLDWAIT`.
+    pub fn ignores_children(&self) -> bool {
+        let action = self.get(SIGCHLD);
+        action.handler == SIG_IGN || action.flags & SA_NOCLDWAIT != 0
+    }
+
+    /// Resets the caught signals to their default actions, which is done on
+    /// `execve`. Ignored signals stay ignored.
+    pub fn reset_handlers(&mut self) {
+        for action in self.0.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SigAction::default();
+            }
+        }
+    }
+}
+
+/// The pending and blocked signals of a thread.
+pub struct ThreadSignals {
+    pending: AtomicU64,
+    blocked: AtomicU64,
+    infos: Mutex<BTreeMap<usize, SigInfo>>,
+}
+
+impl ThreadSignals {
+    pub const fn new(blocked: SignalSet) -> Self {
+        Self {
+            pending: AtomicU64::new(0),
+            blocked: AtomicU64::new(blocked.0),
+            infos: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn pending(&self) -> SignalSet {
+        SignalSet(self.pending.load(Ordering::Acquire))
+    }
+
+    pub fn blocked(&self) -> SignalSet {
+        SignalSet(self.blocked.load(Ordering::Acquire))
+    }
+
+    /// Whether there is a pending signal that is not blocked.
+    pub fn has_deliverable(&self) -> bool {
+        self.pending().0 & !self.blocked().0 != 0
+    }
+
+    /// Sets the signal mask. `SIGKILL` and `SIGSTOP` cannot be blocked.
+    pub fn set_blocked(&self, blocked: SignalSet) {
+        self.blocked
+            .store(blocked.0 & !SignalSet::UNBLOCKABLE.0, Ordering::Release);
+    }
+
+    /// Makes the signal pending. A standard signal that is already pending
+    /// is not queued again.
+    fn send(&self, info: SigInfo) {
+        let sig = info.signo as usize;
+        self.infos.lock().entry(sig).or_insert(info);
+        self.pending.fetch_or(1 << (sig - 1), Ordering::AcqRel);
+    }
+
+    /// Takes the pending signal with the lowest number that is not blocked.
+    fn dequeue(&self) -> Option<SigInfo> {
+        let deliverable = self.pending().0 & !self.blocked().0;
+        if deliverable == 0 {
+            return None;
+        }
+        let sig = deliverable.trailing_zeros() as usize + 1;
+        let info = self.infos.lock().remove(&sig);
+        self.pending.fetch_and(!(1 << (sig - 1)), Ordering::AcqRel);
+        Some(info.unwrap_or(SigInfo::from_process(sig, SI_KERNEL, 0)))
+    }
+}
+
+/// Maps the signal return trampoline into `uspace`.
+pub fn map_sigreturn_trampoline(uspace: &mut AddrSpace) -> AxResult {
+    let addr = VirtAddr::from(SIGRETURN_TRAMPOLINE);
+    let flags = MappingFlags::READ | MappingFlags::EXECUTE | MappingFlags::USER;
+    uspace.map_alloc(addr, PAGE_SIZE_4K, flags, true)?;
+    uspace.write(addr, arch::SIGRETURN_CODE)?;
+    Ok(())
+}
+
+/// Sends a signal to the thread `task`.
+pub fn send_signal(task: &AxTaskRef, info: SigInfo) {
+    debug!("send signal {} to task {}", info.signo, task.id_name());
+    task.task_ext().signals.send(info);
+    // Interrupt the thread if it is blocked in `wait4` or `futex`.
+    task.task_ext().process.interrupt_wait();
+    crate::futex::interrupt_wait();
+}
+
+/// Sends a signal to the process `process`. It goes to a thread that does not
+/// block it if there is one, or the first thread otherwise.
+///
+/// Zombies have no threads left, so the signal is dropped.
+pub fn send_process_signal(process: &Process, info: SigInfo) {
+    let sig = info.signo as usize;
+    let threads = process.threads();
+    let target = threads
+        .iter()
+        .find(|task| !task.task_ext().signals.blocked().contains(sig))
+        .or(threads.first());
+    if let Some(task) = target {
+        send_signal(task, info);
+    }
+}
+
+/// Sends a signal for a fault of the current thread.
+///
+/// Unlike [`send_signal`], the signal is unblocked, and reset to the default
+/// action if it is ignored, as returning to the faulting instruction would
+/// fault again.
+pub fn force_signal(info: SigInfo) {
+    let curr = current();
+    let ext = curr.task_ext();
+    let sig = info.signo as usize;
+    {
+        let mut actions = ext.sig_actions.lock();
+        if actions.get(sig).handler == SIG_IGN {
+            actions.set(sig, SigAction::default());
+        }
+    }
+    let mut blocked = ext.signals.blocked();
+    blocked.remove(sig);
+    ext.signals.set_blocked(blocked);
+    ext.signals.send(info);
+}
+
+/// Delivers the pending signals of the current thread, before it returns to
+/// user space with the context `tf`.
+#[register_trap_handler(RETURN_TO_USER)]
+fn handle_pending_signals(tf: &mut TrapFrame) {
+    let curr = current();
+    let ext = curr.task_ext();
+    while let Some(info) = ext.signals.dequeue() {
+        let sig = info.signo as usize;
+        let action = ext.sig_actions.lock().get(sig);
+        match action.handler {
+            SIG_IGN => continue,
+            SIG_DFL => match default_action(sig) {
+                DefaultAction::Ignore => continue,
+                DefaultAction::Stop => {
+                    warn!("job control is not supported, ignore signal {}", sig);
+                    continue;
+                }
+                DefaultAction::Terminate => terminate(sig, false),
+                DefaultAction::CoreDump => {
+                    let core_dumped = crate::coredump::dump_core(tf, &info);
+                    terminate(sig, core_dumped)
+                }
+            },
+            _ => {
+                if let Err(e) = setup_frame(tf, &info, &action) {
+                    warn!("failed to set up the frame of signal {}: {:?}", sig, e);
+                    terminate(SIGSEGV, false);
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Kills the current process with the signal of `info` right away, whatever
+/// its disposition, dumping core if that is the default action of the signal.
+pub fn kill_current(tf: &TrapFrame, info: &SigInfo) -> ! {
+    let sig = info.signo as usize;
+    let core_dumped =
+        default_action(sig) == DefaultAction::CoreDump && crate::coredump::dump_core(tf, info);
+    terminate(sig, core_dumped)
+}
+
+/// Terminates the current process, which is killed by the signal `sig`, and
+/// has dumped core if `core_dumped` is set.
+fn terminate(sig: usize, core_dumped: bool) -> ! {
+    let curr = current();
+    // `SIGKILL` from the kernel ends the other threads of an exiting group.
+    if sig != SIGKILL || !curr.task_ext().process.is_group_exiting() {
+        let core = if core_dumped { " (core dumped)" } else { "" };
+        ax_println!("{}: killed by signal {}{}, exit!", curr.id_name(), sig, core);
+    }
+    crate::process::exit_current_by_signal(sig, core_dumped)
+}
+
+/// Pushes a signal frame onto the user stack, and redirects `tf` to the
+/// signal handler.
+fn setup_frame(tf: &mut TrapFrame, info: &SigInfo, action: &SigAction) -> LinuxResult {
+    let curr = current();
+    let ext = curr.task_ext();
+    let sig = info.signo as usize;
+    let blocked = ext.signals.blocked();
+
+    #[cfg(not(target_arch = "riscv64"))]
+    let ret_addr = if action.flags & SA_RESTORER != 0 && action.restorer != 0 {
+        action.restorer
+    } else {
+        SIGRETURN_TRAMPOLINE
+    };
+    #[cfg(target_arch = "riscv64")]
+    let ret_addr = SIGRETURN_TRAMPOLINE;
+    let siginfo = action.flags & SA_SIGINFO != 0;
+    arch::push_frame(tf, info, blocked, action.handler, siginfo, ret_addr)?;
+
+    let mut new_blocked = SignalSet(blocked.0 | action.mask.0);
+    if action.flags & SA_NODEFER == 0 {
+        new_blocked.add(sig);
+    }
+    ext.signals.set_blocked(new_blocked);
+    if action.flags & SA_RESETHAND != 0 {
+        ext.sig_actions.lock().set(sig, SigAction::default());
+    }
+    Ok(())
+}
+
+fn check_signal(sig: usize) -> LinuxResult {
+    if (1..=NSIG).contains(&sig) {
+        Ok(())
+    } else {
+        Err(LinuxError::EINVAL)
+    }
+}
+
+fn check_sigset_size(sigsetsize: usize) -> LinuxResult {
+    if sigsetsize == size_of::<SignalSet>() {
+        Ok(())
+    } else {
+        Err(LinuxError::EINVAL)
+    }
+}
+
+/// Examines and changes the disposition of the signal `sig`.
+pub fn sys_rt_sigaction(
+    sig: usize,
+    act: UserPtr<SigAction>,
+    oldact: UserPtr<SigAction>,
+    sigsetsize: usize,
+) -> isize {
+    syscall_body!(sys_rt_sigaction, {
+        check_signal(sig)?;
+        check_sigset_size(sigsetsize)?;
+        let new_action = if act.is_null() {
+            None
+        } else {
+            if SignalSet::UNBLOCKABLE.contains(sig) {
+                return Err(LinuxError::EINVAL);
+            }
+            Some(act.read()?)
+        };
+
+        let curr = current();
+        let old_action = {
+            let mut actions = curr.task_ext().sig_actions.lock();
+            let old_action = actions.get(sig);
+            if let Some(mut action) = new_action {
+                action.mask = SignalSet(action.mask.0 & !SignalSet::UNBLOCKABLE.0);
+                actions.set(sig, action);
+            }
+            old_action
+        };
+        if !oldact.is_null() {
+            oldact.write(old_action)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Examines and changes the signal mask of the calling thread.
+pub fn sys_rt_sigprocmask(
+    how: usize,
+    set: UserPtr<SignalSet>,
+    oldset: UserPtr<SignalSet>,
+    sigsetsize: usize,
+) -> isize {
+    syscall_body!(sys_rt_sigprocmask, {
+        check_sigset_size(sigsetsize)?;
+        let curr = current();
+        let signals = &curr.task_ext().signals;
+        let old = signals.blocked();
+        if !set.is_null() {
+            let set = set.read()?;
+            let new = match how {
+                SIG_BLOCK => SignalSet(old.0 | set.0),
+                SIG_UNBLOCK => SignalSet(old.0 & !set.0),
+                SIG_SETMASK => set,
+                _ => return Err(LinuxError::EINVAL),
+            };
+            signals.set_blocked(new);
+        }
+        if !oldset.is_null() {
+            oldset.write(old)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Returns the signals that are pending for the calling thread.
+pub fn sys_rt_sigpending(set: UserPtr<SignalSet>, sigsetsize: usize) -> isize {
+    syscall_body!(sys_rt_sigpending, {
+        check_sigset_size(sigsetsize)?;
+        let pending = current().task_ext().signals.pending();
+        set.write(pending)?;
+        Ok(0)
+    })
+}
+
+/// Sends the signal `sig` to the process `pid`.
+///
+/// If `pid` is 0, the signal is sent to every process in the process group of
+/// the caller, or in the process group `-pid` if `pid` is less than -1. If
+/// `pid` is -1, it is sent to all processes except init and the caller.
+///
+/// If `sig` is 0, only the existence of the target is checked.
+pub fn sys_kill(pid: isize, sig: usize) -> isize {
+    syscall_body!(sys_kill, {
+        if sig != 0 {
+            check_signal(sig)?;
+        }
+        let curr = current();
+        let caller = curr.task_ext().process.clone();
+        let targets: Vec<_> = match pid {
+            pid if pid > 0 => alloc::vec![find_process(pid as usize).ok_or(LinuxError::ESRCH)?],
+            -1 => processes()
+                .into_iter()
+                .filter(|p| p.pid() != init_pid() && p.pid() != caller.pid())
+                .collect(),
+            pid => {
+                let pgid = if pid == 0 { caller.pgid() } else { pid.unsigned_abs() };
+                processes()
+                    .into_iter()
+                    .filter(|p| p.pgid() == pgid)
+                    .collect()
+            }
+        };
+        if targets.is_empty() {
+            return Err(LinuxError::ESRCH);
+        }
+        if sig != 0 {
+            for process in &targets {
+                send_process_signal(process, SigInfo::from_process(sig, SI_USER, caller.pid()));
+            }
+        }
+        Ok(0)
+    })
+}
+
+/// Sends the signal `sig` to the thread `tid` in the thread group `tgid`.
+///
+/// If `tgid` is `None` (for `tkill`), the thread group is not checked.
+pub fn sys_tgkill(tgid: Option<usize>, tid: usize, sig: usize) -> isize {
+    syscall_body!(sys_tgkill, {
+        if sig != 0 {
+            check_signal(sig)?;
+        }
+        let task = processes()
+            .into_iter()
+            .filter(|p| tgid.map_or(true, |tgid| p.pid() == tgid))
+            .flat_map(|p| p.threads())
+            .find(|task| task.id().as_u64() as usize == tid)
+            .ok_or(LinuxError::ESRCH)?;
+        if sig != 0 {
+            let self_pid = current().task_ext().proc_id;
+            send_signal(&task, SigInfo::from_process(sig, SI_TKILL, self_pid));
+        }
+        Ok(0)
+    })
+}
+
+/// Returns from a signal handler, restoring the context saved by
+/// [`setup_frame`].
+pub fn sys_rt_sigreturn(tf: &TrapFrame) -> isize {
+    let curr = current();
+    let mut new_tf = *tf;
+    let sigmask = match arch::pop_frame(&mut new_tf) {
+        Ok(sigmask) => sigmask,
+        Err(e) => {
+            warn!("sys_rt_sigreturn: bad signal frame: {:?}", e);
+            terminate(SIGSEGV, false);
+        }
+    };
+    curr.task_ext().signals.set_blocked(sigmask);
+
+    // Signals unblocked by the restored mask are delivered right away.
+    handle_pending_signals(&mut new_tf);
+    let kstack_top = curr.kernel_stack_top().unwrap();
+    unsafe { UspaceContext::from(&new_tf).enter_uspace(kstack_top) }
+}
@@ -0,0 +1,121 @@
+//! Signal frames of Linux on AArch64.
+
+use core::mem::{offset_of, size_of};
+
+use axerrno::LinuxResult;
+use axhal::arch::TrapFrame;
+use axsyscall::UserPtr;
+
+use super::{SigInfo, SignalSet};
+
+/// `mov x8, #SYS_RT_SIGRETURN; svc #0`
+pub const SIGRETURN_CODE: &[u8] = &[0x68, 0x11, 0x80, 0xd2, 0x01, 0x00, 0x00, 0xd4];
+
+/// The condition flags (`NZCV`) in `pstate`, which `rt_sigreturn` may
+/// restore.
+const PSTATE_NZCV: u64 = 0xf000_0000;
+
+/// The context saved on the user stack when a signal handler is called,
+/// i.e., the `struct rt_sigframe` of Linux on AArch64.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalFrame {
+    info: SigInfo,
+    ucontext: UContext,
+}
+
+/// `struct ucontext` of Linux on AArch64.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UContext {
+    flags: usize,
+    link: usize,
+    /// `stack_t`, the alternate signal stack.
+    stack: [usize; 3],
+    sigmask: SignalSet,
+    _unused: [u8; 1024 / 8 - size_of::<SignalSet>()],
+    mcontext: MContext,
+}
+
+/// `struct sigcontext` of Linux on AArch64.
+///
+/// The FP/SIMD registers are not saved yet, so the reserved area only holds
+/// the terminating (all-zero) record.
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+struct MContext {
+    fault_address: u64,
+    regs: [u64; 31],
+    sp: u64,
+    pc: u64,
+    pstate: u64,
+    reserved: [u8; 4096],
+}
+
+impl MContext {
+    fn save(tf: &TrapFrame) -> Self {
+        Self {
+            fault_address: 0,
+            regs: tf.r,
+            sp: tf.usp,
+            pc: tf.elr,
+            pstate: tf.spsr,
+            reserved: [0; 4096],
+        }
+    }
+
+    /// Restores the general registers and the condition flags. The rest of
+    /// `pstate` is kept, so that the user cannot gain privileges.
+    fn restore(&self, tf: &mut TrapFrame) {
+        tf.r = self.regs;
+        tf.usp = self.sp;
+        tf.elr = self.pc;
+        tf.spsr = (tf.spsr & !PSTATE_NZCV) | (self.pstate & PSTATE_NZCV);
+    }
+}
+
+/// Pushes a signal frame that saves `tf` and `sigmask` onto the user stack,
+/// and redirects `tf` to `handler`, which returns to `ret_addr`.
+///
+/// The handler takes `siginfo_t *` and `ucontext_t *` as well if `siginfo`
+/// is true.
+pub fn push_frame(
+    tf: &mut TrapFrame,
+    info: &SigInfo,
+    sigmask: SignalSet,
+    handler: usize,
+    siginfo: bool,
+    ret_addr: usize,
+) -> LinuxResult {
+    let frame_addr = (tf.usp as usize - size_of::<SignalFrame>()) & !0xf;
+    let frame = SignalFrame {
+        info: *info,
+        ucontext: UContext {
+            flags: 0,
+            link: 0,
+            stack: [0; 3],
+            sigmask,
+            _unused: [0; 1024 / 8 - size_of::<SignalSet>()],
+            mcontext: MContext::save(tf),
+        },
+    };
+    UserPtr::<SignalFrame>::new(frame_addr).write(frame)?;
+
+    tf.elr = handler as u64;
+    tf.usp = frame_addr as u64;
+    tf.r[30] = ret_addr as u64;
+    tf.r[0] = info.signo as u64;
+    if siginfo {
+        tf.r[1] = (frame_addr + offset_of!(SignalFrame, info)) as u64;
+        tf.r[2] = (frame_addr + offset_of!(SignalFrame, ucontext)) as u64;
+    }
+    Ok(())
+}
+
+/// Restores `tf` from the signal frame on the user stack when the handler
+/// returns with `rt_sigreturn`, and returns the saved signal mask.
+pub fn pop_frame(tf: &mut TrapFrame) -> LinuxResult<SignalSet> {
+    let ucontext = UserPtr::<SignalFrame>::new(tf.usp as usize).read()?.ucontext;
+    ucontext.mcontext.restore(tf);
+    Ok(ucontext.sigmask)
+}
@@ -0,0 +1,113 @@
+//! Signal frames of Linux on RISC-V.
+
+use core::mem::{offset_of, size_of};
+
+use axerrno::LinuxResult;
+use axhal::arch::{GeneralRegisters, TrapFrame};
+use axsyscall::UserPtr;
+
+use super::{SigInfo, SignalSet};
+
+/// `li a7, SYS_RT_SIGRETURN; ecall`
+pub const SIGRETURN_CODE: &[u8] = &[0x93, 0x08, 0xb0, 0x08, 0x73, 0x00, 0x00, 0x00];
+
+/// The context saved on the user stack when a signal handler is called,
+/// i.e., the `struct rt_sigframe` of Linux on RISC-V.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalFrame {
+    info: SigInfo,
+    ucontext: UContext,
+}
+
+/// `struct ucontext` of Linux on RISC-V.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UContext {
+    flags: usize,
+    link: usize,
+    /// `stack_t`, the alternate signal stack.
+    stack: [usize; 3],
+    sigmask: SignalSet,
+    _unused: [u8; 1024 / 8 - size_of::<SignalSet>()],
+    mcontext: MContext,
+}
+
+/// `struct sigcontext` of Linux on RISC-V.
+///
+/// Floating-point registers are not saved yet, as the kernel does not
+/// switch them.
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+struct MContext {
+    /// `pc` followed by `x1` to `x31`.
+    regs: [usize; 32],
+    fpregs: [u64; 66],
+}
+
+impl MContext {
+    fn save(tf: &TrapFrame) -> Self {
+        let mut regs = [0; 32];
+        regs[0] = tf.sepc;
+        regs[1..].copy_from_slice(&unsafe {
+            core::mem::transmute::<GeneralRegisters, [usize; 31]>(tf.regs)
+        });
+        Self {
+            regs,
+            fpregs: [0; 66],
+        }
+    }
+
+    fn restore(&self, tf: &mut TrapFrame) {
+        tf.sepc = self.regs[0];
+        let mut regs = [0; 31];
+        regs.copy_from_slice(&self.regs[1..]);
+        tf.regs = unsafe { core::mem::transmute::<[usize; 31], GeneralRegisters>(regs) };
+    }
+}
+
+/// Pushes a signal frame that saves `tf` and `sigmask` onto the user stack,
+/// and redirects `tf` to `handler`, which returns to `ret_addr`.
+///
+/// The handler takes `siginfo_t *` and `ucontext_t *` as well if `siginfo`
+/// is true.
+pub fn push_frame(
+    tf: &mut TrapFrame,
+    info: &SigInfo,
+    sigmask: SignalSet,
+    handler: usize,
+    siginfo: bool,
+    ret_addr: usize,
+) -> LinuxResult {
+    let frame_addr = (tf.regs.sp - size_of::<SignalFrame>()) & !0xf;
+    let frame = SignalFrame {
+        info: *info,
+        ucontext: UContext {
+            flags: 0,
+            link: 0,
+            stack: [0; 3],
+            sigmask,
+            _unused: [0; 1024 / 8 - size_of::<SignalSet>()],
+            mcontext: MContext::save(tf),
+        },
+    };
+    UserPtr::<SignalFrame>::new(frame_addr).write(frame)?;
+
+    tf.sepc = handler;
+    tf.regs.sp = frame_addr;
+    tf.regs.ra = ret_addr;
+    tf.regs.a0 = info.signo as usize;
+    if siginfo {
+        tf.regs.a1 = frame_addr + offset_of!(SignalFrame, info);
+        tf.regs.a2 = frame_addr + offset_of!(SignalFrame, ucontext);
+    }
+    Ok(())
+}
+
+/// Restores `tf` from the signal frame on the user stack when the handler
+/// returns with `rt_sigreturn`, and returns the saved signal mask.
+pub fn pop_frame(tf: &mut TrapFrame) -> LinuxResult<SignalSet> {
+    let ucontext = UserPtr::<SignalFrame>::new(tf.regs.sp).read()?.ucontext;
+    ucontext.mcontext.restore(tf);
+    Ok(ucontext.sigmask)
+}
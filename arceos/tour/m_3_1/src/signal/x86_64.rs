@@ -0,0 +1,190 @@
+//! Signal frames of Linux on x86_64.
+
+use core::mem::{offset_of, size_of};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::arch::TrapFrame;
+use axsyscall::UserPtr;
+
+use super::{SigInfo, SignalSet};
+
+/// `mov eax, SYS_RT_SIGRETURN; syscall`
+pub const SIGRETURN_CODE: &[u8] = &[0xb8, 0x0f, 0x00, 0x00, 0x00, 0x0f, 0x05];
+
+/// The area below the user stack pointer that the handler must not clobber.
+const RED_ZONE_SIZE: usize = 128;
+
+/// The flags in `rflags` that `rt_sigreturn` may restore.
+const USER_RFLAGS: u64 = 0x50dd5; // AC | RF | OF | DF | TF | SF | ZF | AF | PF | CF
+
+/// User code addresses must be below it, i.e., canonical in the lower half,
+/// or returning to user space faults in the kernel.
+const USER_IP_LIMIT: usize = 1 << 47;
+
+/// The context saved on the user stack when a signal handler is called,
+/// i.e., the `struct rt_sigframe` of Linux on x86_64.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalFrame {
+    /// The return address of the handler.
+    pretcode: usize,
+    ucontext: UContext,
+    info: SigInfo,
+}
+
+/// `struct ucontext` of Linux on x86_64.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UContext {
+    flags: usize,
+    link: usize,
+    /// `stack_t`, the alternate signal stack.
+    stack: [usize; 3],
+    mcontext: MContext,
+    sigmask: SignalSet,
+}
+
+/// `struct sigcontext` of Linux on x86_64.
+///
+/// The FPU state is not saved yet, so `fpstate` is always NULL.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MContext {
+    r8: u64,
+    r9: u64,
+    r10: u64,
+    r11: u64,
+    r12: u64,
+    r13: u64,
+    r14: u64,
+    r15: u64,
+    rdi: u64,
+    rsi: u64,
+    rbp: u64,
+    rbx: u64,
+    rdx: u64,
+    rax: u64,
+    rcx: u64,
+    rsp: u64,
+    rip: u64,
+    rflags: u64,
+    /// `cs`, `gs`, `fs` and `ss`.
+    segments: [u16; 4],
+    err: u64,
+    trapno: u64,
+    oldmask: u64,
+    cr2: u64,
+    fpstate: u64,
+    _reserved: [u64; 8],
+}
+
+impl MContext {
+    fn save(tf: &TrapFrame) -> Self {
+        Self {
+            r8: tf.r8,
+            r9: tf.r9,
+            r10: tf.r10,
+            r11: tf.r11,
+            r12: tf.r12,
+            r13: tf.r13,
+            r14: tf.r14,
+            r15: tf.r15,
+            rdi: tf.rdi,
+            rsi: tf.rsi,
+            rbp: tf.rbp,
+            rbx: tf.rbx,
+            rdx: tf.rdx,
+            rax: tf.rax,
+            rcx: tf.rcx,
+            rsp: tf.rsp,
+            rip: tf.rip,
+            rflags: tf.rflags,
+            segments: [tf.cs as u16, 0, 0, tf.ss as u16],
+            err: tf.error_code,
+            trapno: tf.vector,
+            oldmask: 0,
+            cr2: 0,
+            fpstate: 0,
+            _reserved: [0; 8],
+        }
+    }
+
+    /// Restores the general registers. The segments and the privileged flags
+    /// are kept, so that the user cannot gain privileges.
+    fn restore(&self, tf: &mut TrapFrame) {
+        tf.r8 = self.r8;
+        tf.r9 = self.r9;
+        tf.r10 = self.r10;
+        tf.r11 = self.r11;
+        tf.r12 = self.r12;
+        tf.r13 = self.r13;
+        tf.r14 = self.r14;
+        tf.r15 = self.r15;
+        tf.rdi = self.rdi;
+        tf.rsi = self.rsi;
+        tf.rbp = self.rbp;
+        tf.rbx = self.rbx;
+        tf.rdx = self.rdx;
+        tf.rax = self.rax;
+        tf.rcx = self.rcx;
+        tf.rsp = self.rsp;
+        tf.rip = self.rip;
+        tf.rflags = (tf.rflags & !USER_RFLAGS) | (self.rflags & USER_RFLAGS);
+    }
+}
+
+/// Pushes a signal frame that saves `tf` and `sigmask` onto the user stack,
+/// and redirects `tf` to `handler`, which returns to `ret_addr`.
+///
+/// The handler takes `siginfo_t *` and `ucontext_t *` as well if `siginfo`
+/// is true.
+pub fn push_frame(
+    tf: &mut TrapFrame,
+    info: &SigInfo,
+    sigmask: SignalSet,
+    handler: usize,
+    siginfo: bool,
+    ret_addr: usize,
+) -> LinuxResult {
+    if handler >= USER_IP_LIMIT {
+        return Err(LinuxError::EFAULT);
+    }
+    let sp = tf.rsp as usize - RED_ZONE_SIZE;
+    // As if `pretcode` is pushed by a `call`, `rsp + 8` is 16-byte aligned.
+    let frame_addr = ((sp - size_of::<SignalFrame>()) & !0xf) - 8;
+    let frame = SignalFrame {
+        pretcode: ret_addr,
+        ucontext: UContext {
+            flags: 0,
+            link: 0,
+            stack: [0; 3],
+            mcontext: MContext::save(tf),
+            sigmask,
+        },
+        info: *info,
+    };
+    UserPtr::<SignalFrame>::new(frame_addr).write(frame)?;
+
+    tf.rip = handler as u64;
+    tf.rsp = frame_addr as u64;
+    tf.rdi = info.signo as u64;
+    tf.rax = 0;
+    if siginfo {
+        tf.rsi = (frame_addr + offset_of!(SignalFrame, info)) as u64;
+        tf.rdx = (frame_addr + offset_of!(SignalFrame, ucontext)) as u64;
+    }
+    Ok(())
+}
+
+/// Restores `tf` from the signal frame on the user stack when the handler
+/// returns with `rt_sigreturn`, and returns the saved signal mask.
+pub fn pop_frame(tf: &mut TrapFrame) -> LinuxResult<SignalSet> {
+    // `pretcode` has been popped by the `ret` of the handler.
+    let frame_addr = tf.rsp as usize - size_of::<usize>();
+    let ucontext = UserPtr::<SignalFrame>::new(frame_addr).read()?.ucontext;
+    if ucontext.mcontext.rip as usize >= USER_IP_LIMIT {
+        return Err(LinuxError::EFAULT);
+    }
+    ucontext.mcontext.restore(tf);
+    Ok(ucontext.sigmask)
+}
@@ -1,80 +1,905 @@
 #![allow(dead_code)]
 
-use core::ffi::{c_void, c_char, c_int};
-use axhal::arch::TrapFrame;
+use core::ffi::{c_void, c_char};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use axhal::arch::{TrapFrame, UspaceContext};
+use axhal::mem::VirtAddr;
 use axhal::trap::{register_trap_handler, SYSCALL};
-use axerrno::LinuxError;
+use axerrno::{LinuxError, LinuxResult};
+use axsync::Mutex;
+use axsyscall::sysno::*;
+use axsyscall::{syscall_body, ArgFmt, Syscall, SyscallTable, UserPtr, UserSlice};
 use axtask::current;
 use axtask::TaskExtRef;
 use arceos_posix_api as api;
+use axfs::fops::Cap;
 
-const SYS_IOCTL: usize = 29;
-const SYS_OPENAT: usize = 56;
-const SYS_CLOSE: usize = 57;
-const SYS_READ: usize = 63;
-const SYS_WRITE: usize = 64;
-const SYS_WRITEV: usize = 66;
-const SYS_EXIT: usize = 93;
-const SYS_EXIT_GROUP: usize = 94;
-const SYS_SET_TID_ADDRESS: usize = 96;
+use crate::cred;
+use crate::fs::{self, PATH_MAX};
+use crate::futex;
+use crate::loader::{load_user_app, UserApp};
+use crate::mm;
+use crate::rlimit;
+use crate::seccomp;
+use crate::shm;
+use crate::signal;
+use crate::time;
+use crate::process::{self, exit_current, exit_group_current, ExecInfo, Process};
+use crate::task::{new_user_task, spawn_user_task, TaskExt};
 
-const AT_FDCWD: i32 = -100;
+/// The maximum length of an argument or environment string of `execve`.
+const MAX_ARG_STRLEN: usize = 0x20000;
+/// The maximum number of arguments and environment strings of `execve`.
+const MAX_ARG_COUNT: usize = 0x8000;
+/// The maximum number of buffers of `writev`.
+const IOV_MAX: i32 = 1024;
+/// Reads and writes are cut at this size, to bound the kernel buffer.
+const MAX_RW_CHUNK: usize = 0x10000;
+
+/// Share the virtual memory.
+const CLONE_VM: usize = 0x100;
+/// Share the file descriptor table.
+const CLONE_FILES: usize = 0x400;
+/// Share the signal dispositions.
+const CLONE_SIGHAND: usize = 0x800;
+/// Put the child in the same thread group as the caller.
+const CLONE_THREAD: usize = 0x10000;
+/// Set the user thread pointer of the child to `tls`.
+const CLONE_SETTLS: usize = 0x80000;
+/// Store the thread ID of the child at `ptid`.
+const CLONE_PARENT_SETTID: usize = 0x100000;
+/// Clear the word at `ctid` and wake up a futex waiter on it when the child
+/// exits, as `set_tid_address` does.
+const CLONE_CHILD_CLEARTID: usize = 0x200000;
+
+/// Registers the syscalls of this kernel.
+pub fn init_syscalls() {
+    use ArgFmt::*;
+    let mut table = SyscallTable::new();
+    table
+        .register(
+            SYS_IOCTL,
+            Syscall::new("ioctl", &[Int, Hex, Ptr], |args| {
+                sys_ioctl(args.arg(0), args.arg(1), args.arg(2)) as _
+            }),
+        )
+        .register(
+            SYS_SET_TID_ADDRESS,
+            Syscall::new("set_tid_address", &[Ptr], |args| {
+                sys_set_tid_address(args.arg(0))
+            }),
+        )
+        .register(
+            SYS_FUTEX,
+            Syscall::new("futex", &[Ptr, Int, Uint, Ptr, Ptr, Uint], |args| {
+                futex::sys_futex(
+                    args.arg(0),
+                    args.arg(1),
+                    args.arg(2),
+                    args.arg(3),
+                    args.arg(4),
+                    args.arg(5),
+                )
+            }),
+        )
+        .register(SYS_GETPID, Syscall::new("getpid", &[], |_| sys_getpid()))
+        .register(SYS_GETTID, Syscall::new("gettid", &[], |_| sys_gettid()))
+        .register(SYS_GETPPID, Syscall::new("getppid", &[], |_| process::sys_getppid()))
+        .register(
+            SYS_GETPGID,
+            Syscall::new("getpgid", &[Int], |args| process::sys_getpgid(args.arg(0))),
+        )
+        .register(
+            SYS_SETPGID,
+            Syscall::new("setpgid", &[Int, Int], |args| {
+                process::sys_setpgid(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(SYS_GETUID, Syscall::new("getuid", &[], |_| cred::sys_getuid()))
+        .register(SYS_GETEUID, Syscall::new("geteuid", &[], |_| cred::sys_geteuid()))
+        .register(SYS_GETGID, Syscall::new("getgid", &[], |_| cred::sys_getgid()))
+        .register(SYS_GETEGID, Syscall::new("getegid", &[], |_| cred::sys_getegid()))
+        .register(
+            SYS_SETUID,
+            Syscall::new("setuid", &[Int], |args| cred::sys_setuid(args.arg(0))),
+        )
+        .register(
+            SYS_SETGID,
+            Syscall::new("setgid", &[Int], |args| cred::sys_setgid(args.arg(0))),
+        )
+        .register(
+            SYS_SETREUID,
+            Syscall::new("setreuid", &[Int, Int], |args| {
+                cred::sys_setreuid(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_SETREGID,
+            Syscall::new("setregid", &[Int, Int], |args| {
+                cred::sys_setregid(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_SETRESUID,
+            Syscall::new("setresuid", &[Int, Int, Int], |args| {
+                cred::sys_setresuid(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_SETRESGID,
+            Syscall::new("setresgid", &[Int, Int, Int], |args| {
+                cred::sys_setresgid(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_GETRESUID,
+            Syscall::new("getresuid", &[Ptr, Ptr, Ptr], |args| {
+                cred::sys_getresuid(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_GETRESGID,
+            Syscall::new("getresgid", &[Ptr, Ptr, Ptr], |args| {
+                cred::sys_getresgid(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_GETGROUPS,
+            Syscall::new("getgroups", &[Int, Ptr], |args| {
+                cred::sys_getgroups(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_SETGROUPS,
+            Syscall::new("setgroups", &[Uint, Ptr], |args| {
+                cred::sys_setgroups(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_GETRLIMIT,
+            Syscall::new("getrlimit", &[Int, Ptr], |args| {
+                rlimit::sys_getrlimit(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_SETRLIMIT,
+            Syscall::new("setrlimit", &[Int, Ptr], |args| {
+                rlimit::sys_setrlimit(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_PRLIMIT64,
+            Syscall::new("prlimit64", &[Int, Int, Ptr, Ptr], |args| {
+                rlimit::sys_prlimit64(args.arg(0), args.arg(1), args.arg(2), args.arg(3))
+            }),
+        )
+        .register(
+            SYS_CLOCK_GETTIME,
+            Syscall::new("clock_gettime", &[Int, Ptr], |args| {
+                time::sys_clock_gettime(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_GETTIMEOFDAY,
+            Syscall::new("gettimeofday", &[Ptr, Ptr], |args| {
+                time::sys_gettimeofday(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_GETCPU,
+            Syscall::new("getcpu", &[Ptr, Ptr, Ptr], |args| {
+                time::sys_getcpu(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_KILL,
+            Syscall::new("kill", &[Int, Int], |args| {
+                signal::sys_kill(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_TKILL,
+            Syscall::new("tkill", &[Int, Int], |args| {
+                signal::sys_tgkill(None, args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_TGKILL,
+            Syscall::new("tgkill", &[Int, Int, Int], |args| {
+                signal::sys_tgkill(Some(args.arg(0)), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_RT_SIGACTION,
+            Syscall::new("rt_sigaction", &[Int, Ptr, Ptr, Uint], |args| {
+                signal::sys_rt_sigaction(args.arg(0), args.arg(1), args.arg(2), args.arg(3))
+            }),
+        )
+        .register(
+            SYS_RT_SIGPROCMASK,
+            Syscall::new("rt_sigprocmask", &[Int, Ptr, Ptr, Uint], |args| {
+                signal::sys_rt_sigprocmask(args.arg(0), args.arg(1), args.arg(2), args.arg(3))
+            }),
+        )
+        .register(
+            SYS_RT_SIGPENDING,
+            Syscall::new("rt_sigpending", &[Ptr, Uint], |args| {
+                signal::sys_rt_sigpending(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_RT_SIGRETURN,
+            Syscall::new("rt_sigreturn", &[], |args| {
+                signal::sys_rt_sigreturn(args.trap_frame())
+            })
+            .noreturn(),
+        )
+        .register(
+            SYS_CLONE,
+            Syscall::new(
+                "clone",
+                &[Flags(axsyscall::CLONE_FLAGS), Ptr, Ptr, Ptr, Ptr],
+                |args| {
+                    // x86_64 swaps the last two arguments.
+                    #[cfg(target_arch = "x86_64")]
+                    let (ctid, tls) = (args.arg(3), args.arg(4));
+                    #[cfg(not(target_arch = "x86_64"))]
+                    let (tls, ctid) = (args.arg(3), args.arg(4));
+                    sys_clone(args.trap_frame(), args.arg(0), args.arg(1), args.arg(2), tls, ctid)
+                },
+            ),
+        )
+        .register(
+            SYS_EXECVE,
+            Syscall::new("execve", &[Str, Ptr, Ptr], |args| {
+                sys_execve(args.arg(0), args.arg(1), args.arg(2))
+            })
+            .noreturn(),
+        )
+        .register(SYS_BRK, Syscall::new("brk", &[Ptr], |args| mm::sys_brk(args.arg(0))))
+        .register(
+            SYS_MMAP,
+            Syscall::new(
+                "mmap",
+                &[
+                    Ptr,
+                    Hex,
+                    Flags(axsyscall::PROT_FLAGS),
+                    Flags(axsyscall::MAP_FLAGS),
+                    Int,
+                    Hex,
+                ],
+                |args| {
+                    mm::sys_mmap(
+                        args.arg(0),
+                        args.arg(1),
+                        args.arg(2),
+                        args.arg(3),
+                        args.arg(4),
+                        args.arg(5),
+                    )
+                },
+            ),
+        )
+        .register(
+            SYS_MUNMAP,
+            Syscall::new("munmap", &[Ptr, Hex], |args| {
+                mm::sys_munmap(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_MREMAP,
+            Syscall::new("mremap", &[Ptr, Hex, Hex, Hex, Ptr], |args| {
+                mm::sys_mremap(args.arg(0), args.arg(1), args.arg(2), args.arg(3), args.arg(4))
+            }),
+        )
+        .register(
+            SYS_MSYNC,
+            Syscall::new("msync", &[Ptr, Hex, Hex], |args| {
+                mm::sys_msync(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_MPROTECT,
+            Syscall::new("mprotect", &[Ptr, Hex, Flags(axsyscall::PROT_FLAGS)], |args| {
+                mm::sys_mprotect(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_MEMFD_CREATE,
+            Syscall::new("memfd_create", &[Str, Hex], |args| {
+                shm::sys_memfd_create(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_FTRUNCATE,
+            Syscall::new("ftruncate", &[Int, Int], |args| {
+                shm::sys_ftruncate(args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_SHMGET,
+            Syscall::new("shmget", &[Int, Hex, Oct], |args| {
+                shm::sys_shmget(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_SHMAT,
+            Syscall::new("shmat", &[Int, Ptr, Oct], |args| {
+                shm::sys_shmat(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_SHMDT,
+            Syscall::new("shmdt", &[Ptr], |args| shm::sys_shmdt(args.arg(0))),
+        )
+        .register(
+            SYS_SHMCTL,
+            Syscall::new("shmctl", &[Int, Hex, Ptr], |args| {
+                shm::sys_shmctl(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_PRCTL,
+            Syscall::new("prctl", &[Int, Hex, Hex, Hex, Hex], |args| {
+                seccomp::sys_prctl(args.arg(0), args.arg(1), args.arg(2), args.arg(3), args.arg(4))
+            }),
+        )
+        .register(
+            SYS_SECCOMP,
+            Syscall::new("seccomp", &[Uint, Hex, Ptr], |args| {
+                seccomp::sys_seccomp(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_WAIT4,
+            Syscall::new("wait4", &[Int, Ptr, Hex, Ptr], |args| {
+                process::sys_wait4(args.arg(0), args.arg(1), args.arg(2), args.arg(3))
+            }),
+        )
+        .register(SYS_DUP, Syscall::new("dup", &[Int], |args| sys_dup(args.arg(0))))
+        .register(
+            SYS_DUP3,
+            Syscall::new(
+                "dup3",
+                &[Int, Int, Flags(axsyscall::OPEN_FLAGS)],
+                |args| sys_dup3(args.arg(0), args.arg(1), args.arg(2)),
+            ),
+        )
+        .register(
+            SYS_FCNTL,
+            Syscall::new("fcntl", &[Int, Int, Hex], |args| {
+                sys_fcntl(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_OPENAT,
+            Syscall::new(
+                "openat",
+                &[Int, Str, Flags(axsyscall::OPEN_FLAGS), Oct],
+                |args| fs::sys_openat(args.arg(0), args.arg(1), args.arg(2), args.arg(3)),
+            ),
+        )
+        .register(
+            SYS_MKDIRAT,
+            Syscall::new("mkdirat", &[Int, Str, Oct], |args| {
+                fs::sys_mkdirat(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_UNLINKAT,
+            Syscall::new("unlinkat", &[Int, Str, Flags(axsyscall::AT_FLAGS)], |args| {
+                fs::sys_unlinkat(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_RENAMEAT2,
+            Syscall::new("renameat2", &[Int, Str, Int, Str, Hex], |args| {
+                fs::sys_renameat2(args.arg(0), args.arg(1), args.arg(2), args.arg(3), args.arg(4))
+            }),
+        )
+        .register(
+            SYS_NEWFSTATAT,
+            Syscall::new(
+                "newfstatat",
+                &[Int, Str, Ptr, Flags(axsyscall::AT_FLAGS)],
+                |args| fs::sys_fstatat(args.arg(0), args.arg(1), args.arg(2), args.arg(3)),
+            ),
+        )
+        .register(
+            SYS_FSTAT,
+            Syscall::new("fstat", &[Int, Ptr], |args| fs::sys_fstat(args.arg(0), args.arg(1))),
+        )
+        .register(
+            SYS_FACCESSAT,
+            Syscall::new("faccessat", &[Int, Str, Oct], |args| {
+                fs::sys_faccessat(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_FCHMODAT,
+            Syscall::new("fchmodat", &[Int, Str, Oct], |args| {
+                fs::sys_fchmodat(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_FCHMOD,
+            Syscall::new("fchmod", &[Int, Oct], |args| fs::sys_fchmod(args.arg(0), args.arg(1))),
+        )
+        .register(
+            SYS_FCHOWNAT,
+            Syscall::new(
+                "fchownat",
+                &[Int, Str, Int, Int, Flags(axsyscall::AT_FLAGS)],
+                |args| {
+                    fs::sys_fchownat(args.arg(0), args.arg(1), args.arg(2), args.arg(3), args.arg(4))
+                },
+            ),
+        )
+        .register(
+            SYS_FCHOWN,
+            Syscall::new("fchown", &[Int, Int, Int], |args| {
+                fs::sys_fchown(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_READLINKAT,
+            Syscall::new("readlinkat", &[Int, Str, Ptr, Uint], |args| {
+                fs::sys_readlinkat(args.arg(0), args.arg(1), args.arg(2), args.arg(3))
+            }),
+        )
+        .register(
+            SYS_GETDENTS64,
+            Syscall::new("getdents64", &[Int, Ptr, Uint], |args| {
+                fs::sys_getdents64(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_CLOSE,
+            Syscall::new("close", &[Int], |args| sys_close(args.arg(0))),
+        )
+        .register(
+            SYS_READ,
+            Syscall::new("read", &[Int, Ptr, Uint], |args| {
+                sys_read(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_WRITE,
+            Syscall::new("write", &[Int, Ptr, Uint], |args| {
+                sys_write(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_WRITEV,
+            Syscall::new("writev", &[Int, Ptr, Int], |args| {
+                sys_writev(args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_EXIT_GROUP,
+            Syscall::new("exit_group", &[Int], |args| sys_exit_group(args.arg(0))).noreturn(),
+        )
+        .register(
+            SYS_EXIT,
+            Syscall::new("exit", &[Int], |args| sys_exit(args.arg(0))).noreturn(),
+        );
+    // Legacy syscalls that only x86_64 has.
+    #[cfg(target_arch = "x86_64")]
+    table
+        .register(
+            SYS_OPEN,
+            Syscall::new("open", &[Str, Flags(axsyscall::OPEN_FLAGS), Oct], |args| {
+                fs::sys_openat(fs::AT_FDCWD, args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_STAT,
+            Syscall::new("stat", &[Str, Ptr], |args| {
+                fs::sys_fstatat(fs::AT_FDCWD, args.arg(0), args.arg(1), 0)
+            }),
+        )
+        .register(
+            SYS_LSTAT,
+            Syscall::new("lstat", &[Str, Ptr], |args| {
+                fs::sys_fstatat(fs::AT_FDCWD, args.arg(0), args.arg(1), fs::AT_SYMLINK_NOFOLLOW)
+            }),
+        )
+        .register(
+            SYS_ACCESS,
+            Syscall::new("access", &[Str, Oct], |args| {
+                fs::sys_faccessat(fs::AT_FDCWD, args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_CHMOD,
+            Syscall::new("chmod", &[Str, Oct], |args| {
+                fs::sys_fchmodat(fs::AT_FDCWD, args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_CHOWN,
+            Syscall::new("chown", &[Str, Int, Int], |args| {
+                fs::sys_fchownat(fs::AT_FDCWD, args.arg(0), args.arg(1), args.arg(2), 0)
+            }),
+        )
+        .register(
+            SYS_LCHOWN,
+            Syscall::new("lchown", &[Str, Int, Int], |args| {
+                let flags = fs::AT_SYMLINK_NOFOLLOW;
+                fs::sys_fchownat(fs::AT_FDCWD, args.arg(0), args.arg(1), args.arg(2), flags)
+            }),
+        )
+        .register(
+            SYS_MKDIR,
+            Syscall::new("mkdir", &[Str, Oct], |args| {
+                fs::sys_mkdirat(fs::AT_FDCWD, args.arg(0), args.arg(1))
+            }),
+        )
+        .register(
+            SYS_RMDIR,
+            Syscall::new("rmdir", &[Str], |args| {
+                fs::sys_unlinkat(fs::AT_FDCWD, args.arg(0), fs::AT_REMOVEDIR)
+            }),
+        )
+        .register(
+            SYS_UNLINK,
+            Syscall::new("unlink", &[Str], |args| fs::sys_unlinkat(fs::AT_FDCWD, args.arg(0), 0)),
+        )
+        .register(
+            SYS_RENAME,
+            Syscall::new("rename", &[Str, Str], |args| {
+                fs::sys_renameat2(fs::AT_FDCWD, args.arg(0), fs::AT_FDCWD, args.arg(1), 0)
+            }),
+        )
+        .register(
+            SYS_RENAMEAT,
+            Syscall::new("renameat", &[Int, Str, Int, Str], |args| {
+                fs::sys_renameat2(args.arg(0), args.arg(1), args.arg(2), args.arg(3), 0)
+            }),
+        )
+        .register(
+            SYS_READLINK,
+            Syscall::new("readlink", &[Str, Ptr, Uint], |args| {
+                fs::sys_readlinkat(fs::AT_FDCWD, args.arg(0), args.arg(1), args.arg(2))
+            }),
+        )
+        .register(
+            SYS_ARCH_PRCTL,
+            Syscall::new("arch_prctl", &[Hex, Hex], |args| {
+                sys_arch_prctl(args.arg(0), args.arg(1))
+            }),
+        );
+    axsyscall::init(table);
+}
 
 #[register_trap_handler(SYSCALL)]
 fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
-    ax_println!("handle_syscall [{}] ...", syscall_num);
-    let ret = match syscall_num {
-         SYS_IOCTL => sys_ioctl(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _) as _,
-        SYS_SET_TID_ADDRESS => sys_set_tid_address(tf.arg0() as _),
-        SYS_OPENAT => sys_openat(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _, tf.arg3() as _),
-        SYS_CLOSE => sys_close(tf.arg0() as _),
-        SYS_READ => sys_read(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITE => sys_write(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITEV => sys_writev(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_EXIT_GROUP => {
-            ax_println!("[SYS_EXIT_GROUP]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        SYS_EXIT => {
-            ax_println!("[SYS_EXIT]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
-            -LinuxError::ENOSYS.code() as _
-        }
-    };
-    ret
+    if let Some(ret) = seccomp::check_syscall(tf, syscall_num) {
+        return ret;
+    }
+    axsyscall::dispatch(tf, syscall_num)
+}
+
+/// Terminates all threads of the calling process.
+fn sys_exit_group(exit_code: i32) -> ! {
+    exit_group_current(exit_code)
 }
 
-fn sys_openat(dfd: c_int, fname: *const c_char, flags: c_int, mode: api::ctypes::mode_t) -> isize {
-    assert_eq!(dfd, AT_FDCWD);
-    api::sys_open(fname, flags, mode) as isize
+/// Terminates the calling thread. The process exits with its last thread.
+fn sys_exit(exit_code: i32) -> ! {
+    exit_current(exit_code)
+}
+
+/// Sets the `FS` base, which is the user thread pointer on x86_64.
+#[cfg(target_arch = "x86_64")]
+const ARCH_SET_FS: usize = 0x1002;
+/// Gets the `FS` base.
+#[cfg(target_arch = "x86_64")]
+const ARCH_GET_FS: usize = 0x1003;
+
+/// Sets or gets the architecture-specific thread state. Only the `FS` base is
+/// supported.
+#[cfg(target_arch = "x86_64")]
+fn sys_arch_prctl(code: usize, addr: usize) -> isize {
+    syscall_body!(sys_arch_prctl, {
+        match code {
+            ARCH_SET_FS => {
+                // Loading a non-canonical base would fault in the kernel.
+                if addr >= 1 << 47 {
+                    return Err(LinuxError::EPERM);
+                }
+                // Saved into the task context on the next context switch.
+                unsafe { axhal::arch::write_thread_pointer(addr) };
+                Ok(0)
+            }
+            ARCH_GET_FS => {
+                UserPtr::<usize>::new(addr).write(axhal::arch::read_thread_pointer())?;
+                Ok(0)
+            }
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
 }
 
 fn sys_close(fd: i32) -> isize {
     api::sys_close(fd) as isize
 }
 
-fn sys_read(fd: i32, buf: *mut c_void, count: usize) -> isize {
-    api::sys_read(fd, buf, count)
+fn sys_dup(old_fd: i32) -> isize {
+    api::sys_dup(old_fd) as isize
+}
+
+fn sys_dup3(old_fd: i32, new_fd: i32, flags: i32) -> isize {
+    api::sys_dup3(old_fd, new_fd, flags) as isize
+}
+
+fn sys_fcntl(fd: i32, cmd: i32, arg: usize) -> isize {
+    api::sys_fcntl(fd, cmd, arg) as isize
 }
 
-fn sys_write(fd: i32, buf: *const c_void, count: usize) -> isize {
-    api::sys_write(fd, buf, count)
+/// Reads at most `count` bytes into the user buffer `buf`. Large reads are
+/// cut short, which the caller must handle anyway.
+fn sys_read(fd: i32, buf: UserPtr<u8>, count: usize) -> isize {
+    syscall_body!(sys_read, {
+        let mut kbuf = vec![0u8; count.min(MAX_RW_CHUNK)];
+        let n = api::sys_read(fd, kbuf.as_mut_ptr() as _, kbuf.len());
+        if n < 0 {
+            return Ok(n);
+        }
+        UserSlice::new(buf.addr(), count).write(&kbuf[..n as usize])?;
+        Ok(n)
+    })
 }
 
-fn sys_writev(fd: i32, iov: *const api::ctypes::iovec, iocnt: i32) -> isize {
-    unsafe { api::sys_writev(fd, iov, iocnt) }
+/// Writes at most `count` bytes from the user buffer `buf`. Like reads, large
+/// writes are cut short.
+fn sys_write(fd: i32, buf: UserPtr<u8>, count: usize) -> isize {
+    syscall_body!(sys_write, {
+        let kbuf = UserSlice::new(buf.addr(), count.min(MAX_RW_CHUNK)).read_to_vec()?;
+        Ok(api::sys_write(fd, kbuf.as_ptr() as _, kbuf.len()))
+    })
 }
 
-fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
+/// Writes the user buffers described by `iov` in order, stopping at the first
+/// short write, or once `MAX_RW_CHUNK` bytes are written.
+fn sys_writev(fd: i32, iov: UserPtr<api::ctypes::iovec>, iocnt: i32) -> isize {
+    syscall_body!(sys_writev, {
+        if !(0..=IOV_MAX).contains(&iocnt) {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut total = 0;
+        for iov in iov.read_array(iocnt as usize)? {
+            let len = iov.iov_len.min(MAX_RW_CHUNK - total as usize);
+            if len == 0 && iov.iov_len != 0 {
+                break;
+            }
+            let kbuf = UserSlice::new(iov.iov_base as usize, len).read_to_vec()?;
+            let n = api::sys_write(fd, kbuf.as_ptr() as _, kbuf.len());
+            if n < 0 {
+                return if total == 0 { Ok(n) } else { Ok(total) };
+            }
+            total += n;
+            if (n as usize) < iov.iov_len {
+                break;
+            }
+        }
+        Ok(total)
+    })
+}
+
+fn sys_set_tid_address(tid_ptd: UserPtr<i32>) -> isize {
     let curr = current();
-    curr.task_ext().set_clear_child_tid(tid_ptd as _);
+    curr.task_ext().set_clear_child_tid(tid_ptd.addr() as _);
     curr.id().as_u64() as isize
 }
 
-fn sys_ioctl(_fd: i32, _op: usize, _argp: *mut c_void) -> i32 {
-    ax_println!("Ignore SYS_IOCTL");
+fn sys_getpid() -> isize {
+    current().task_ext().proc_id as isize
+}
+
+fn sys_gettid() -> isize {
+    current().id().as_u64() as isize
+}
+
+/// Creates a child process or thread.
+///
+/// By default, the child is a new process, which runs in a copy-on-write
+/// duplicate of the caller's address space and inherits a copy of its file
+/// descriptor table and signal dispositions. `CLONE_VM`, `CLONE_FILES` and
+/// `CLONE_SIGHAND` share them instead, and `CLONE_THREAD` puts the child in
+/// the process of the caller, as `pthread_create` does. The child inherits
+/// the signal mask in any case.
+///
+/// The child starts on the stack `newsp` if it is not 0, with the thread
+/// pointer `tls` if `CLONE_SETTLS` is given. `ptid` and `ctid` are used by
+/// `CLONE_PARENT_SETTID` and `CLONE_CHILD_CLEARTID` respectively.
+///
+/// Sharing the address space without `CLONE_THREAD` (as `vfork` does) is not
+/// supported yet, since `execve` of the child would replace it in place.
+fn sys_clone(
+    tf: &TrapFrame,
+    flags: usize,
+    newsp: usize,
+    ptid: UserPtr<u32>,
+    tls: usize,
+    ctid: usize,
+) -> isize {
+    syscall_body!(sys_clone, {
+        let has = |flag| flags & flag != 0;
+        if has(CLONE_THREAD) && !has(CLONE_SIGHAND)
+            || has(CLONE_SIGHAND) && !has(CLONE_VM)
+            || has(CLONE_VM) && !has(CLONE_THREAD)
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let curr = current();
+        let ext = curr.task_ext();
+        let aspace = if has(CLONE_VM) {
+            ext.aspace.clone()
+        } else {
+            Arc::new(Mutex::new(ext.aspace.lock().clone_cow()?))
+        };
+        let fd_table = if has(CLONE_FILES) {
+            ext.fd_table.clone()
+        } else {
+            Arc::new(ext.fd_table.fork())
+        };
+        let sig_actions = if has(CLONE_SIGHAND) {
+            ext.sig_actions.clone()
+        } else {
+            Arc::new(Mutex::new(ext.sig_actions.lock().clone()))
+        };
+        // The user thread pointer is `tp` in the trap frame on RISC-V.
+        #[cfg(target_arch = "riscv64")]
+        let tf = &{
+            let mut tf = *tf;
+            if has(CLONE_SETTLS) {
+                tf.regs.tp = tls;
+            }
+            tf
+        };
+        // The child returns 0 from the syscall. `tf` already points to the
+        // next instruction.
+        let mut uctx = UspaceContext::from(tf);
+        uctx.set_retval(0);
+        if newsp != 0 {
+            uctx.set_sp(newsp);
+        }
+        let new_ext = |tid| {
+            // A new process is named after its main thread.
+            let process = if has(CLONE_THREAD) {
+                ext.process.clone()
+            } else {
+                Process::new(tid, Some(&ext.process))
+            };
+            let child_ext = TaskExt::new(
+                process,
+                uctx,
+                aspace,
+                fd_table,
+                sig_actions,
+                ext.signals.blocked(),
+                ext.seccomp.lock().clone(),
+            );
+            if has(CLONE_CHILD_CLEARTID) {
+                child_ext.set_clear_child_tid(ctid as u64);
+            }
+            child_ext
+        };
+        let task = new_user_task(new_ext, has(CLONE_SETTLS).then_some(tls));
+
+        // Store the thread ID before the child runs, or it may exit and
+        // clear the same word (as `pthread_join` waits on) first. As on
+        // Linux, a bad `ptid` is not an error.
+        let tid = task.id().as_u64() as usize;
+        if has(CLONE_PARENT_SETTID) {
+            let _ = ptid.write(tid as u32);
+        }
+        spawn_user_task(task);
+        Ok(tid)
+    })
+}
+
+/// Replaces the program of the calling process with the ELF file at `path`.
+///
+/// It only returns on failure. Once the old program has been torn down,
+/// failing to load the new one kills the process.
+fn sys_execve(
+    path: UserPtr<c_char>,
+    argv: UserPtr<UserPtr<c_char>>,
+    envp: UserPtr<UserPtr<c_char>>,
+) -> isize {
+    match exec_user_app(path, argv, envp) {
+        Ok((entry, ustack_top)) => {
+            let curr = current();
+            let kstack_top = curr.kernel_stack_top().unwrap();
+            info!(
+                "Execve: entry={:#x}, ustack={:#x}, kstack={:#x}",
+                entry, ustack_top, kstack_top,
+            );
+            // The new program sets up its own TLS.
+            #[cfg(not(target_arch = "riscv64"))]
+            unsafe {
+                axhal::arch::write_thread_pointer(0)
+            };
+            unsafe { UspaceContext::new(entry, ustack_top).enter_uspace(kstack_top) }
+        }
+        Err(e) => {
+            info!("sys_execve => {:?}", e);
+            -e.code() as _
+        }
+    }
+}
+
+fn exec_user_app(
+    path: UserPtr<c_char>,
+    argv: UserPtr<UserPtr<c_char>>,
+    envp: UserPtr<UserPtr<c_char>>,
+) -> LinuxResult<(usize, VirtAddr)> {
+    // Copy everything out of the old program before tearing it down.
+    let path = path.read_str(PATH_MAX)?;
+    let args = user_str_array(argv)?;
+    let envs = user_str_array(envp)?;
+    if !std::fs::metadata(&path)?.is_file() {
+        return Err(LinuxError::EACCES);
+    }
+    axfs::fops::check_access(&path, Cap::EXECUTE, &axfs::cred::current_cred())?;
+    // A bad file fails here, while the old program can still get the error.
+    let app = UserApp::open(&path)?;
+
+    let curr = current();
+    // As on Linux, the other threads die before the old program is gone.
+    curr.task_ext().process.kill_other_threads()?;
+    let mut aspace = curr.task_ext().aspace.lock();
+    let stack_limit = rlimit::stack_limit(&curr.task_ext().process);
+    let cred = curr.task_ext().process.cred();
+    let loaded = aspace
+        .clear()
+        .and_then(|_| load_user_app(&app, &args, &envs, &cred, stack_limit, &mut aspace));
+    match loaded {
+        Ok((entry, ustack_top, auxv)) => {
+            curr.task_ext().fd_table.close_on_exec();
+            curr.task_ext().sig_actions.lock().reset_handlers();
+            curr.task_ext()
+                .process
+                .set_exec_info(ExecInfo { path, args, auxv });
+            Ok((entry, ustack_top))
+        }
+        Err(err) => {
+            drop(aspace);
+            ax_println!("{}: cannot execute {}: {:?}, exit!", curr.id_name(), path, err);
+            exit_group_current(-1);
+        }
+    }
+}
+
+/// Copies a NULL-terminated array of strings (e.g. `argv`) from user space.
+fn user_str_array(ptr: UserPtr<UserPtr<c_char>>) -> LinuxResult<Vec<String>> {
+    let mut strs = Vec::new();
+    if ptr.is_null() {
+        return Ok(strs);
+    }
+    loop {
+        let s = ptr.add(strs.len())?.read()?;
+        if s.is_null() {
+            break;
+        }
+        if strs.len() >= MAX_ARG_COUNT {
+            return Err(LinuxError::E2BIG);
+        }
+        strs.push(s.read_str(MAX_ARG_STRLEN).map_err(|e| match e {
+            LinuxError::ENAMETOOLONG => LinuxError::E2BIG,
+            e => e,
+        })?);
+    }
+    Ok(strs)
+}
+
+/// Not supported: every request is ignored, and succeeds.
+fn sys_ioctl(_fd: i32, _op: usize, _argp: UserPtr<c_void>) -> i32 {
     0
 }
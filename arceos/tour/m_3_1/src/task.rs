@@ -1,18 +1,26 @@
 #![allow(dead_code)]
 
-use core::sync::atomic::AtomicU64;
+use core::sync::atomic::{AtomicU64, Ordering};
 
 use alloc::sync::Arc;
 
+use arceos_posix_api::FdTable;
+use axfs::cred::Cred;
 use axhal::arch::UspaceContext;
 use axmm::AddrSpace;
 use axsync::Mutex;
 use axtask::{AxTaskRef, TaskExtRef, TaskInner};
 
+use crate::process::Process;
+use crate::seccomp::Seccomp;
+use crate::signal::{SignalActions, SignalSet, ThreadSignals};
+
 /// Task extended data for the monolithic kernel.
 pub struct TaskExt {
     /// The process ID.
     pub proc_id: usize,
+    /// The process that the task belongs to.
+    pub process: Arc<Process>,
     /// The clear thread tid field
     ///
     /// See <https://manpages.debian.org/unstable/manpages-dev/set_tid_address.2.en.html#clear_child_tid>
@@ -23,32 +31,88 @@ pub struct TaskExt {
     pub uctx: UspaceContext,
     /// The virtual memory address space.
     pub aspace: Arc<Mutex<AddrSpace>>,
+    /// The file descriptor table.
+    pub fd_table: Arc<FdTable>,
+    /// The signal dispositions of the process.
+    pub sig_actions: Arc<Mutex<SignalActions>>,
+    /// The pending and blocked signals of the thread.
+    pub signals: ThreadSignals,
+    /// The syscall filtering state of the thread.
+    pub seccomp: Mutex<Seccomp>,
 }
 
 impl TaskExt {
-    pub const fn new(uctx: UspaceContext, aspace: Arc<Mutex<AddrSpace>>) -> Self {
+    pub fn new(
+        process: Arc<Process>,
+        uctx: UspaceContext,
+        aspace: Arc<Mutex<AddrSpace>>,
+        fd_table: Arc<FdTable>,
+        sig_actions: Arc<Mutex<SignalActions>>,
+        blocked: SignalSet,
+        seccomp: Seccomp,
+    ) -> Self {
         Self {
-            proc_id: 233,
+            proc_id: process.pid(),
+            process,
             uctx,
             clear_child_tid: AtomicU64::new(0),
             aspace,
+            fd_table,
+            sig_actions,
+            signals: ThreadSignals::new(blocked),
+            seccomp: Mutex::new(seccomp),
         }
     }
 
     pub(crate) fn clear_child_tid(&self) -> u64 {
-        self.clear_child_tid
-            .load(core::sync::atomic::Ordering::Relaxed)
+        self.clear_child_tid.load(Ordering::Relaxed)
     }
 
     pub(crate) fn set_clear_child_tid(&self, clear_child_tid: u64) {
         self.clear_child_tid
-            .store(clear_child_tid, core::sync::atomic::Ordering::Relaxed);
+            .store(clear_child_tid, Ordering::Relaxed);
     }
 }
 
 axtask::def_task_ext!(TaskExt);
 
-pub fn spawn_user_task(aspace: Arc<Mutex<AddrSpace>>, uctx: UspaceContext) -> AxTaskRef {
+/// Returns the file descriptor table of the current user process, or `None`
+/// for kernel tasks.
+pub fn current_fd_table() -> Option<Arc<FdTable>> {
+    let curr = axtask::current();
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return None;
+    }
+    Some(curr.task_ext().fd_table.clone())
+}
+
+/// Returns the credentials of the current user process, or `None` for kernel
+/// tasks.
+pub fn current_cred() -> Option<Arc<Cred>> {
+    let curr = axtask::current();
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return None;
+    }
+    Some(curr.task_ext().process.cred())
+}
+
+/// Returns the address space of the current user task.
+pub fn current_aspace() -> Arc<Mutex<AddrSpace>> {
+    axtask::current().task_ext().aspace.clone()
+}
+
+/// Creates a user task with the extended data returned by `new_ext`, which
+/// is called with the thread ID of the task. The task runs in `ext.aspace`
+/// and starts from the user context `ext.uctx`. It is not runnable until
+/// [`spawn_user_task`].
+///
+/// For a child process, `uctx` is usually copied from the trap frame of the
+/// parent, with the return value set to 0.
+///
+/// `tls` is the initial user thread pointer on x86_64 and AArch64, or `None`
+/// to inherit the one of the caller. On RISC-V, it is `tp` in `uctx`, and
+/// `tls` is ignored.
+pub fn new_user_task(new_ext: impl FnOnce(usize) -> TaskExt, tls: Option<usize>) -> TaskInner {
     let mut task = TaskInner::new(
         || {
             let curr = axtask::current();
@@ -64,8 +128,26 @@ pub fn spawn_user_task(aspace: Arc<Mutex<AddrSpace>>, uctx: UspaceContext) -> Ax
         "userboot".into(),
         crate::KERNEL_STACK_SIZE,
     );
+    let ext = new_ext(task.id().as_u64() as usize);
     task.ctx_mut()
-        .set_page_table_root(aspace.lock().page_table_root());
-    task.init_task_ext(TaskExt::new(uctx, aspace));
-    axtask::spawn_task(task)
+        .set_page_table_root(ext.aspace.lock().page_table_root());
+    #[cfg(target_arch = "x86_64")]
+    {
+        task.ctx_mut().fs_base = tls.unwrap_or_else(axhal::arch::read_thread_pointer);
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        task.ctx_mut().tpidr_el0 = tls.unwrap_or_else(axhal::arch::read_thread_pointer) as u64;
+    }
+    #[cfg(target_arch = "riscv64")]
+    let _ = tls;
+    task.init_task_ext(ext);
+    task
+}
+
+/// Spawns a user task created by [`new_user_task`], and adds it to its
+/// process.
+pub fn spawn_user_task(task: TaskInner) -> AxTaskRef {
+    let process = task.task_ext().process.clone();
+    process.add_thread(|| axtask::spawn_task(task))
 }
@@ -0,0 +1,61 @@
+//! Syscalls that read the clocks and the current CPU.
+//!
+//! User space usually calls them through the vDSO, which falls back to them
+//! only when it cannot serve a request by itself.
+
+use arceos_posix_api::ctypes;
+use axerrno::LinuxError;
+use axhal::time::{NANOS_PER_MICROS, NANOS_PER_SEC};
+use axsyscall::{syscall_body, UserPtr};
+
+use crate::vdso;
+
+/// Stores the time of the clock `clock_id` at `ts`.
+pub fn sys_clock_gettime(clock_id: i32, ts: UserPtr<ctypes::timespec>) -> isize {
+    syscall_body!(sys_clock_gettime, {
+        // CPU-time clocks have negative IDs.
+        let clock_id = clock_id as u32;
+        if !vdso::is_clock_supported(clock_id) {
+            return Err(LinuxError::EINVAL);
+        }
+        let nanos = vdso::clock_nanos(vdso::is_realtime_clock(clock_id));
+        ts.write(ctypes::timespec {
+            tv_sec: (nanos / NANOS_PER_SEC) as _,
+            tv_nsec: (nanos % NANOS_PER_SEC) as _,
+        })?;
+        Ok(0)
+    })
+}
+
+/// Stores the wall time at `tv` and the timezone, which is always UTC, at
+/// `tz`. Either may be null.
+pub fn sys_gettimeofday(tv: UserPtr<ctypes::timeval>, tz: UserPtr<[i32; 2]>) -> isize {
+    syscall_body!(sys_gettimeofday, {
+        if !tv.is_null() {
+            let nanos = vdso::clock_nanos(true);
+            tv.write(ctypes::timeval {
+                tv_sec: (nanos / NANOS_PER_SEC) as _,
+                tv_usec: (nanos % NANOS_PER_SEC / NANOS_PER_MICROS) as _,
+            })?;
+        }
+        if !tz.is_null() {
+            // `tz_minuteswest` and `tz_dsttime`.
+            tz.write([0; 2])?;
+        }
+        Ok(0)
+    })
+}
+
+/// Stores the CPU and the NUMA node that the calling thread runs on at `cpu`
+/// and `node`. Either may be null.
+pub fn sys_getcpu(cpu: UserPtr<u32>, node: UserPtr<u32>) -> isize {
+    syscall_body!(sys_getcpu, {
+        if !cpu.is_null() {
+            cpu.write(axhal::cpu::this_cpu_id() as u32)?;
+        }
+        if !node.is_null() {
+            node.write(0)?;
+        }
+        Ok(0)
+    })
+}
@@ -0,0 +1,370 @@
+//! The vDSO, a small shared object mapped into every user address space, with
+//! which `clock_gettime`, `gettimeofday` and `getcpu` run without entering the
+//! kernel.
+//!
+//! The kernel writes the clock parameters into a data page ("vvar"), which is
+//! shared by all address spaces and mapped read-only right before the vDSO
+//! image. The image is an ELF shared object built around the
+//! position-independent code in `vdso/<arch>.S`, and is advertised to user
+//! space with `AT_SYSINFO_EHDR`. The code falls back to the real syscalls for
+//! the clocks it does not support, and `getcpu` only avoids the syscall on
+//! x86_64 with `rdtscp`.
+//!
+//! The syscalls convert the counter to nanoseconds with the same parameters,
+//! so that the clocks read by both agree.
+
+use core::mem::{offset_of, size_of};
+#[cfg(target_arch = "x86_64")]
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::vec;
+use alloc::vec::Vec;
+use axerrno::AxResult;
+use axhal::mem::{virt_to_phys, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::MappingFlags;
+use axmm::AddrSpace;
+use axsyscall::sysno::{SYS_CLOCK_GETTIME, SYS_GETCPU};
+use elf::abi::{
+    DT_HASH, DT_NULL, DT_SONAME, DT_STRSZ, DT_STRTAB, DT_SYMENT, DT_SYMTAB, ET_DYN, EV_CURRENT,
+    PF_R, PF_X, PT_DYNAMIC, PT_LOAD, STB_GLOBAL, STT_FUNC,
+};
+use elf::dynamic::Elf64_Dyn;
+use elf::file::Elf64_Ehdr;
+use elf::segment::Elf64_Phdr;
+use elf::symbol::Elf64_Sym;
+
+use crate::coredump::{as_bytes, elf_ident, ELF_MACHINE};
+
+/// The address of the data page, which the vDSO image follows.
+pub const VVAR_BASE: usize = 0x3f_0001_0000;
+/// The address of the vDSO image, i.e., its ELF header.
+pub const VDSO_BASE: usize = VVAR_BASE + PAGE_SIZE_4K;
+
+/// The offset of the code in the vDSO image, after the ELF header and the
+/// dynamic section.
+const TEXT_OFFSET: usize = 0x400;
+
+/// The counter is converted to nanoseconds with a fixed-point multiplier of
+/// `CLOCK_SHIFT` fraction bits.
+const CLOCK_SHIFT: u32 = 40;
+
+/// The clocks (`CLOCK_REALTIME`, `CLOCK_MONOTONIC`, `CLOCK_MONOTONIC_RAW`,
+/// `CLOCK_REALTIME_COARSE`, `CLOCK_MONOTONIC_COARSE` and `CLOCK_BOOTTIME`),
+/// one bit per clock ID.
+const CLOCKS: u32 = 0b1111_0011;
+/// The clocks of `CLOCKS` that are based on the wall time.
+const REALTIME_CLOCKS: u32 = 0b0010_0001;
+
+/// `VdsoData::flags`: `rdtscp` returns the CPU number in `ecx`.
+#[cfg(target_arch = "x86_64")]
+const VDSO_RDTSCP: u32 = 1;
+
+/// The clock parameters shared with the vDSO.
+///
+/// The monotonic time is `nanos_base + ((counter - cycle_base) * mult >>
+/// CLOCK_SHIFT)` nanoseconds, where `counter` is the raw hardware counter.
+/// The wall time adds `realtime_offset`.
+#[repr(C, align(4096))]
+struct VdsoData {
+    cycle_base: AtomicU64,
+    nanos_base: AtomicU64,
+    mult: AtomicU64,
+    realtime_offset: AtomicU64,
+    /// `VDSO_RDTSCP` if `rdtscp` returns the CPU number.
+    #[cfg(target_arch = "x86_64")]
+    flags: AtomicU32,
+}
+
+static VDSO_DATA: VdsoData = VdsoData {
+    cycle_base: AtomicU64::new(0),
+    nanos_base: AtomicU64::new(0),
+    mult: AtomicU64::new(0),
+    realtime_offset: AtomicU64::new(0),
+    #[cfg(target_arch = "x86_64")]
+    flags: AtomicU32::new(0),
+};
+
+#[cfg(target_arch = "x86_64")]
+core::arch::global_asm!(
+    include_str!("vdso/x86_64.S"),
+    vvar_offset = const TEXT_OFFSET + PAGE_SIZE_4K,
+    cycle_base = const offset_of!(VdsoData, cycle_base),
+    nanos_base = const offset_of!(VdsoData, nanos_base),
+    mult = const offset_of!(VdsoData, mult),
+    realtime_offset = const offset_of!(VdsoData, realtime_offset),
+    flags = const offset_of!(VdsoData, flags),
+    rdtscp_flag = const VDSO_RDTSCP,
+    shift = const CLOCK_SHIFT,
+    clocks = const CLOCKS,
+    realtime_clocks = const REALTIME_CLOCKS,
+    sys_clock_gettime = const SYS_CLOCK_GETTIME,
+    sys_getcpu = const SYS_GETCPU,
+);
+#[cfg(target_arch = "aarch64")]
+core::arch::global_asm!(
+    include_str!("vdso/aarch64.S"),
+    vvar_offset = const TEXT_OFFSET + PAGE_SIZE_4K,
+    cycle_base = const offset_of!(VdsoData, cycle_base),
+    nanos_base = const offset_of!(VdsoData, nanos_base),
+    mult = const offset_of!(VdsoData, mult),
+    realtime_offset = const offset_of!(VdsoData, realtime_offset),
+    shift = const CLOCK_SHIFT,
+    clocks = const CLOCKS,
+    realtime_clocks = const REALTIME_CLOCKS,
+    sys_clock_gettime = const SYS_CLOCK_GETTIME,
+    sys_getcpu = const SYS_GETCPU,
+);
+#[cfg(target_arch = "riscv64")]
+core::arch::global_asm!(
+    include_str!("vdso/riscv64.S"),
+    vvar_offset = const TEXT_OFFSET + PAGE_SIZE_4K,
+    cycle_base = const offset_of!(VdsoData, cycle_base),
+    nanos_base = const offset_of!(VdsoData, nanos_base),
+    mult = const offset_of!(VdsoData, mult),
+    realtime_offset = const offset_of!(VdsoData, realtime_offset),
+    shift = const CLOCK_SHIFT,
+    clocks = const CLOCKS,
+    realtime_clocks = const REALTIME_CLOCKS,
+    sys_clock_gettime = const SYS_CLOCK_GETTIME,
+    sys_getcpu = const SYS_GETCPU,
+);
+
+extern "C" {
+    fn vdso_text_start();
+    fn vdso_text_end();
+    fn vdso_clock_gettime();
+    fn vdso_gettimeofday();
+    fn vdso_getcpu();
+}
+
+/// The symbols exported by the vDSO and their code.
+#[cfg(not(target_arch = "aarch64"))]
+static SYMBOLS: &[(&str, unsafe extern "C" fn())] = &[
+    ("__vdso_clock_gettime", vdso_clock_gettime),
+    ("__vdso_gettimeofday", vdso_gettimeofday),
+    ("__vdso_getcpu", vdso_getcpu),
+];
+/// The symbols exported by the vDSO and their code. The C libraries look up
+/// the `__kernel_*` names on aarch64.
+#[cfg(target_arch = "aarch64")]
+static SYMBOLS: &[(&str, unsafe extern "C" fn())] = &[
+    ("__vdso_clock_gettime", vdso_clock_gettime),
+    ("__vdso_gettimeofday", vdso_gettimeofday),
+    ("__vdso_getcpu", vdso_getcpu),
+    ("__kernel_clock_gettime", vdso_clock_gettime),
+    ("__kernel_gettimeofday", vdso_gettimeofday),
+];
+
+/// Reads the hardware counter that the vDSO reads, i.e., the raw TSC, while
+/// `current_ticks` counts from boot on x86_64.
+#[cfg(target_arch = "x86_64")]
+fn read_counter() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Reads the hardware counter that the vDSO reads.
+#[cfg(not(target_arch = "x86_64"))]
+fn read_counter() -> u64 {
+    axhal::time::current_ticks()
+}
+
+/// Fills the data page with the clock parameters of `axhal::time`.
+pub fn init() {
+    let data = &VDSO_DATA;
+    data.cycle_base.store(read_counter(), Ordering::Relaxed);
+    data.nanos_base.store(axhal::time::monotonic_time_nanos(), Ordering::Relaxed);
+    data.mult.store(axhal::time::ticks_to_nanos(1 << CLOCK_SHIFT), Ordering::Relaxed);
+    data.realtime_offset.store(axhal::time::epochoffset_nanos(), Ordering::Relaxed);
+    // `axhal` stores the CPU number in `IA32_TSC_AUX` if `rdtscp` is
+    // supported, i.e., bit 27 of `EDX` of CPUID leaf 0x8000_0001.
+    #[cfg(target_arch = "x86_64")]
+    if unsafe { core::arch::x86_64::__cpuid(0x8000_0001).edx } & (1 << 27) != 0 {
+        data.flags.store(VDSO_RDTSCP, Ordering::Relaxed);
+    }
+}
+
+/// Returns the current time in nanoseconds as the vDSO computes it, since
+/// boot or since the epoch if `realtime` is set.
+pub fn clock_nanos(realtime: bool) -> u64 {
+    let data = &VDSO_DATA;
+    let cycles = read_counter().wrapping_sub(data.cycle_base.load(Ordering::Relaxed));
+    let mult = data.mult.load(Ordering::Relaxed);
+    let mut nanos = ((cycles as u128 * mult as u128) >> CLOCK_SHIFT) as u64
+        + data.nanos_base.load(Ordering::Relaxed);
+    if realtime {
+        nanos += data.realtime_offset.load(Ordering::Relaxed);
+    }
+    nanos
+}
+
+/// Whether the clock `clock_id` is supported.
+pub fn is_clock_supported(clock_id: u32) -> bool {
+    clock_id < 32 && CLOCKS & (1 << clock_id) != 0
+}
+
+/// Whether the clock `clock_id` is based on the wall time.
+pub fn is_realtime_clock(clock_id: u32) -> bool {
+    clock_id < 32 && REALTIME_CLOCKS & (1 << clock_id) != 0
+}
+
+/// Whether `[addr, addr + size)` overlaps the data page, which user space
+/// must not make writable.
+pub fn overlaps_vvar(addr: usize, size: usize) -> bool {
+    addr < VVAR_BASE + PAGE_SIZE_4K && VVAR_BASE < addr.saturating_add(size)
+}
+
+/// Appends `s` and a NUL to the string table `strtab`, and returns its
+/// offset.
+fn push_str(strtab: &mut Vec<u8>, s: &str) -> usize {
+    let offset = strtab.len();
+    strtab.extend_from_slice(s.as_bytes());
+    strtab.push(0);
+    offset
+}
+
+/// Builds the vDSO image, which is linked at address 0:
+///
+/// ```text
+/// +------------------------+ 0
+/// | ELF header             |
+/// | PT_LOAD, PT_DYNAMIC    |
+/// | .hash                  |
+/// | .dynsym                |
+/// | .dynstr                |
+/// | .dynamic               |
+/// +------------------------+ TEXT_OFFSET
+/// | code                   |
+/// +------------------------+
+/// ```
+///
+/// There are no section headers. The symbols refer to section 1 just to be
+/// defined, as the C libraries skip undefined ones.
+fn build_image() -> Vec<u8> {
+    let text_start = vdso_text_start as usize;
+    let text = unsafe {
+        core::slice::from_raw_parts(text_start as *const u8, vdso_text_end as usize - text_start)
+    };
+
+    let mut strtab = vec![0];
+    let soname = push_str(&mut strtab, "linux-vdso.so.1");
+    let mut syms = vec![Elf64_Sym {
+        st_name: 0,
+        st_info: 0,
+        st_other: 0,
+        st_shndx: 0,
+        st_value: 0,
+        st_size: 0,
+    }];
+    for &(name, code) in SYMBOLS {
+        syms.push(Elf64_Sym {
+            st_name: push_str(&mut strtab, name) as u32,
+            st_info: (STB_GLOBAL << 4) | STT_FUNC,
+            st_other: 0,
+            st_shndx: 1,
+            st_value: (TEXT_OFFSET + (code as usize - text_start)) as u64,
+            st_size: 0,
+        });
+    }
+
+    // A single bucket, whose chain goes through all symbols.
+    let nsyms = syms.len() as u32;
+    let mut hash = vec![1, nsyms, 1, 0];
+    hash.extend((2..=nsyms).map(|next| if next < nsyms { next } else { 0 }));
+
+    let phoff = size_of::<Elf64_Ehdr>();
+    let hash_offset = phoff + 2 * size_of::<Elf64_Phdr>();
+    let sym_offset = (hash_offset + hash.len() * size_of::<u32>()).next_multiple_of(8);
+    let str_offset = sym_offset + syms.len() * size_of::<Elf64_Sym>();
+    let dyn_offset = (str_offset + strtab.len()).next_multiple_of(8);
+    let dynamic = [
+        (DT_HASH, hash_offset),
+        (DT_SYMTAB, sym_offset),
+        (DT_SYMENT, size_of::<Elf64_Sym>()),
+        (DT_STRTAB, str_offset),
+        (DT_STRSZ, strtab.len()),
+        (DT_SONAME, soname),
+        (DT_NULL, 0),
+    ]
+    .map(|(d_tag, d_un)| Elf64_Dyn {
+        d_tag,
+        d_un: d_un as u64,
+    });
+    let dyn_size = dynamic.len() * size_of::<Elf64_Dyn>();
+    assert!(dyn_offset + dyn_size <= TEXT_OFFSET);
+    assert!(TEXT_OFFSET + text.len() <= PAGE_SIZE_4K);
+
+    let image_size = TEXT_OFFSET + text.len();
+    let ehdr = Elf64_Ehdr {
+        e_ident: elf_ident(),
+        e_type: ET_DYN,
+        e_machine: ELF_MACHINE,
+        e_version: EV_CURRENT as u32,
+        e_entry: 0,
+        e_phoff: phoff as u64,
+        e_shoff: 0,
+        e_flags: 0,
+        e_ehsize: size_of::<Elf64_Ehdr>() as u16,
+        e_phentsize: size_of::<Elf64_Phdr>() as u16,
+        e_phnum: 2,
+        e_shentsize: 0,
+        e_shnum: 0,
+        e_shstrndx: 0,
+    };
+    let phdrs = [
+        Elf64_Phdr {
+            p_type: PT_LOAD,
+            p_flags: PF_R | PF_X,
+            p_offset: 0,
+            p_vaddr: 0,
+            p_paddr: 0,
+            p_filesz: image_size as u64,
+            p_memsz: image_size as u64,
+            p_align: PAGE_SIZE_4K as u64,
+        },
+        Elf64_Phdr {
+            p_type: PT_DYNAMIC,
+            p_flags: PF_R,
+            p_offset: dyn_offset as u64,
+            p_vaddr: dyn_offset as u64,
+            p_paddr: 0,
+            p_filesz: dyn_size as u64,
+            p_memsz: dyn_size as u64,
+            p_align: 8,
+        },
+    ];
+
+    let mut image = vec![0; image_size];
+    let mut put = |offset: usize, data: &[u8]| {
+        image[offset..offset + data.len()].copy_from_slice(data);
+    };
+    put(0, as_bytes(&ehdr));
+    put(phoff, as_bytes(&phdrs));
+    let hash: Vec<u8> = hash.iter().flat_map(|word| word.to_ne_bytes()).collect();
+    put(hash_offset, &hash);
+    for (i, sym) in syms.iter().enumerate() {
+        put(sym_offset + i * size_of::<Elf64_Sym>(), as_bytes(sym));
+    }
+    put(str_offset, &strtab);
+    put(dyn_offset, as_bytes(&dynamic));
+    put(TEXT_OFFSET, text);
+    image
+}
+
+/// Maps the data page and the vDSO image into `uspace`. Returns the address
+/// of the image for `AT_SYSINFO_EHDR`.
+pub fn map_vdso(uspace: &mut AddrSpace) -> AxResult<usize> {
+    let data = VirtAddr::from(&VDSO_DATA as *const VdsoData as usize);
+    uspace.map_linear(
+        VVAR_BASE.into(),
+        virt_to_phys(data),
+        PAGE_SIZE_4K,
+        MappingFlags::READ | MappingFlags::USER,
+    )?;
+
+    let image = build_image();
+    let flags = MappingFlags::READ | MappingFlags::EXECUTE | MappingFlags::USER;
+    uspace.map_alloc(VDSO_BASE.into(), PAGE_SIZE_4K, flags, true)?;
+    uspace.write(VDSO_BASE.into(), &image)?;
+    Ok(VDSO_BASE)
+}